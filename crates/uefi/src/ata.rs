@@ -0,0 +1,125 @@
+use crate::device::DevicePath;
+use crate::prelude::*;
+
+pub const ATA_PASS_THRU_ATTRIBUTES_PHYSICAL: u32 = 0x0001;
+pub const ATA_PASS_THRU_ATTRIBUTES_LOGICAL: u32 = 0x0002;
+pub const ATA_PASS_THRU_ATTRIBUTES_NONBLOCKIO: u32 = 0x0004;
+
+// Command protocols
+pub const ATA_PASS_THRU_PROTOCOL_ATA_HARDWARE_RESET: u8 = 0x00;
+pub const ATA_PASS_THRU_PROTOCOL_ATA_SOFTWARE_RESET: u8 = 0x01;
+pub const ATA_PASS_THRU_PROTOCOL_ATA_NON_DATA: u8 = 0x02;
+pub const ATA_PASS_THRU_PROTOCOL_PIO_DATA_IN: u8 = 0x04;
+pub const ATA_PASS_THRU_PROTOCOL_PIO_DATA_OUT: u8 = 0x05;
+pub const ATA_PASS_THRU_PROTOCOL_DMA: u8 = 0x06;
+pub const ATA_PASS_THRU_PROTOCOL_DMA_QUEUED: u8 = 0x07;
+pub const ATA_PASS_THRU_PROTOCOL_DEVICE_DIAGNOSTIC: u8 = 0x08;
+pub const ATA_PASS_THRU_PROTOCOL_DEVICE_RESET: u8 = 0x09;
+pub const ATA_PASS_THRU_PROTOCOL_UDMA_DATA_IN: u8 = 0x0A;
+pub const ATA_PASS_THRU_PROTOCOL_UDMA_DATA_OUT: u8 = 0x0B;
+pub const ATA_PASS_THRU_PROTOCOL_FPDMA: u8 = 0x0C;
+pub const ATA_PASS_THRU_PROTOCOL_RETURN_RESPONSE: u8 = 0xFF;
+
+// Transfer length encodings
+pub const ATA_PASS_THRU_LENGTH_BYTES: u8 = 0x80;
+pub const ATA_PASS_THRU_LENGTH_MASK: u8 = 0x70;
+pub const ATA_PASS_THRU_LENGTH_NO_DATA_TRANSFER: u8 = 0x00;
+pub const ATA_PASS_THRU_LENGTH_FEATURES: u8 = 0x10;
+pub const ATA_PASS_THRU_LENGTH_SECTOR_COUNT: u8 = 0x20;
+pub const ATA_PASS_THRU_LENGTH_TPSIU: u8 = 0x30;
+pub const ATA_PASS_THRU_LENGTH_COUNT: u8 = 0x0F;
+
+/// Returned by GetNextPort and GetNextDevice to begin
+pub const ATA_ALL_PORTS: u16 = 0xFFFF;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct AtaPassThruMode {
+    pub Attributes: u32,
+    pub IoAlign: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct AtaStatusBlock {
+    pub Reserved1: [u8; 2],
+    pub AtaStatus: u8,
+    pub AtaError: u8,
+    pub AtaSectorNumber: u8,
+    pub AtaCylinderLow: u8,
+    pub AtaCylinderHigh: u8,
+    pub AtaDeviceHead: u8,
+    pub AtaSectorNumberExp: u8,
+    pub AtaCylinderLowExp: u8,
+    pub AtaCylinderHighExp: u8,
+    pub Reserved2: u8,
+    pub AtaSectorCount: u8,
+    pub AtaSectorCountExp: u8,
+    pub Reserved3: [u8; 6],
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct AtaCommandBlock {
+    pub Reserved1: [u8; 2],
+    pub AtaCommand: u8,
+    pub AtaFeatures: u8,
+    pub AtaSectorNumber: u8,
+    pub AtaCylinderLow: u8,
+    pub AtaCylinderHigh: u8,
+    pub AtaDeviceHead: u8,
+    pub AtaSectorNumberExp: u8,
+    pub AtaCylinderLowExp: u8,
+    pub AtaCylinderHighExp: u8,
+    pub AtaFeaturesExp: u8,
+    pub AtaSectorCount: u8,
+    pub AtaSectorCountExp: u8,
+    pub Reserved2: [u8; 6],
+}
+
+#[repr(C)]
+pub struct AtaPassThruCommandPacket {
+    pub Asb: *mut AtaStatusBlock,
+    pub Acb: *mut AtaCommandBlock,
+    /// The timeout in 100ns units, or 0 to wait forever
+    pub Timeout: u64,
+    pub InDataBuffer: *mut u8,
+    pub OutDataBuffer: *const u8,
+    pub InTransferLength: u32,
+    pub OutTransferLength: u32,
+    pub Protocol: u8,
+    pub Length: u8,
+}
+
+#[repr(C)]
+pub struct AtaPassThru {
+    pub Mode: &'static AtaPassThruMode,
+    pub PassThru: extern "efiapi" fn(
+        &AtaPassThru,
+        Port: u16,
+        PortMultiplierPort: u16,
+        Packet: &mut AtaPassThruCommandPacket,
+        Event: Event,
+    ) -> Status,
+    pub GetNextPort: extern "efiapi" fn(&AtaPassThru, Port: &mut u16) -> Status,
+    pub GetNextDevice:
+        extern "efiapi" fn(&AtaPassThru, Port: u16, PortMultiplierPort: &mut u16) -> Status,
+    pub BuildDevicePath: extern "efiapi" fn(
+        &AtaPassThru,
+        Port: u16,
+        PortMultiplierPort: u16,
+        DevicePath: &mut *mut DevicePath,
+    ) -> Status,
+    pub GetDevice: extern "efiapi" fn(
+        &AtaPassThru,
+        DevicePath: *const DevicePath,
+        Port: &mut u16,
+        PortMultiplierPort: &mut u16,
+    ) -> Status,
+    pub ResetPort: extern "efiapi" fn(&AtaPassThru, Port: u16) -> Status,
+    pub ResetDevice: extern "efiapi" fn(&AtaPassThru, Port: u16, PortMultiplierPort: u16) -> Status,
+}
+
+impl AtaPassThru {
+    pub const GUID: Guid = guid!("1d3de7f0-0807-424f-aa69-11a54e19a46f");
+}
@@ -38,3 +38,37 @@ pub struct BlockIo {
 impl BlockIo {
     pub const GUID: Guid = guid!("964e5b21-6459-11d2-8e39-00a0c969723b");
 }
+
+#[repr(C)]
+pub struct BlockIo2Token {
+    /// Signaled when the request completes, or null for a blocking request
+    pub Event: Event,
+    pub TransactionStatus: Status,
+}
+
+#[repr(C)]
+pub struct BlockIo2 {
+    pub Media: &'static BlockIoMedia,
+    pub Reset: extern "efiapi" fn(&BlockIo2, ExtendedVerification: bool) -> Status,
+    pub ReadBlocksEx: extern "efiapi" fn(
+        &BlockIo2,
+        MediaId: u32,
+        LBA: u64,
+        Token: *mut BlockIo2Token,
+        BufferSize: usize,
+        Buffer: *mut u8,
+    ) -> Status,
+    pub WriteBlocksEx: extern "efiapi" fn(
+        &BlockIo2,
+        MediaId: u32,
+        LBA: u64,
+        Token: *mut BlockIo2Token,
+        BufferSize: usize,
+        Buffer: *const u8,
+    ) -> Status,
+    pub FlushBlocksEx: extern "efiapi" fn(&BlockIo2, Token: *mut BlockIo2Token) -> Status,
+}
+
+impl BlockIo2 {
+    pub const GUID: Guid = guid!("a77b2472-e282-4e9f-a245-c2c0e27bbcc1");
+}
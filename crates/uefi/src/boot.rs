@@ -1,7 +1,35 @@
+use crate::device::DevicePath;
 use crate::memory::{MemoryDescriptor, MemoryType};
 use crate::prelude::*;
 use crate::TableHeader;
 
+pub const EVT_TIMER: u32 = 0x80000000;
+pub const EVT_RUNTIME: u32 = 0x40000000;
+pub const EVT_NOTIFY_WAIT: u32 = 0x00000100;
+pub const EVT_NOTIFY_SIGNAL: u32 = 0x00000200;
+pub const EVT_SIGNAL_EXIT_BOOT_SERVICES: u32 = 0x00000201;
+pub const EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE: u32 = 0x60000202;
+
+#[repr(C)]
+pub enum TimerDelay {
+    /// Cancel the timer
+    Cancel,
+    /// Signal the event every TriggerTime 100ns units
+    Periodic,
+    /// Signal the event once after TriggerTime 100ns units
+    Relative,
+}
+
+#[repr(C)]
+pub enum AllocateType {
+    /// Allocate pages anywhere
+    AnyPages,
+    /// Allocate pages at or below the given address
+    MaxAddress,
+    /// Allocate pages at the given address
+    Address,
+}
+
 #[repr(C)]
 pub enum InterfaceType {
     Native,
@@ -46,12 +74,12 @@ pub struct BootServices {
         NotifyContext: usize, /* *mut c_void */
         Event: &mut Event,
     ) -> Status,
-    SetTimer: extern "efiapi" fn(),
+    pub SetTimer: extern "efiapi" fn(Event: Event, Type: TimerDelay, TriggerTime: u64) -> Status,
     pub WaitForEvent:
         extern "efiapi" fn(NumberOfEvents: usize, Event: *const Event, Index: &mut usize) -> Status,
-    SignalEvent: extern "efiapi" fn(),
-    CloseEvent: extern "efiapi" fn(),
-    CheckEvent: extern "efiapi" fn(),
+    pub SignalEvent: extern "efiapi" fn(Event: Event) -> Status,
+    pub CloseEvent: extern "efiapi" fn(Event: Event) -> Status,
+    pub CheckEvent: extern "efiapi" fn(Event: Event) -> Status,
     pub InstallProtocolInterface: extern "efiapi" fn(
         Handle: &mut Handle,
         Protocol: &Guid,
@@ -72,7 +100,11 @@ pub struct BootServices {
         BufferSize: &mut usize,
         Buffer: *mut Handle,
     ) -> Status,
-    LocateDevicePath: extern "efiapi" fn(),
+    pub LocateDevicePath: extern "efiapi" fn(
+        Protocol: &Guid,
+        DevicePath: &mut *const DevicePath,
+        Device: &mut Handle,
+    ) -> Status,
     InstallConfigurationTable: extern "efiapi" fn(),
     pub LoadImage: extern "efiapi" fn(
         BootPolicy: bool,
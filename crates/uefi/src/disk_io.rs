@@ -0,0 +1,58 @@
+use crate::prelude::*;
+
+#[repr(C)]
+pub struct DiskIo {
+    pub Revision: u64,
+    pub ReadDisk: extern "efiapi" fn(
+        &DiskIo,
+        MediaId: u32,
+        Offset: u64,
+        BufferSize: usize,
+        Buffer: *mut u8,
+    ) -> Status,
+    pub WriteDisk: extern "efiapi" fn(
+        &DiskIo,
+        MediaId: u32,
+        Offset: u64,
+        BufferSize: usize,
+        Buffer: *const u8,
+    ) -> Status,
+}
+
+impl DiskIo {
+    pub const GUID: Guid = guid!("ce345171-ba0b-11d2-8e4f-00a0c969723b");
+}
+
+#[repr(C)]
+pub struct DiskIo2Token {
+    /// Signaled when the request completes, or null for a blocking request
+    pub Event: Event,
+    pub TransactionStatus: Status,
+}
+
+#[repr(C)]
+pub struct DiskIo2 {
+    pub Revision: u64,
+    pub Cancel: extern "efiapi" fn(&DiskIo2) -> Status,
+    pub ReadDiskEx: extern "efiapi" fn(
+        &DiskIo2,
+        MediaId: u32,
+        Offset: u64,
+        Token: *mut DiskIo2Token,
+        BufferSize: usize,
+        Buffer: *mut u8,
+    ) -> Status,
+    pub WriteDiskEx: extern "efiapi" fn(
+        &DiskIo2,
+        MediaId: u32,
+        Offset: u64,
+        Token: *mut DiskIo2Token,
+        BufferSize: usize,
+        Buffer: *const u8,
+    ) -> Status,
+    pub FlushDiskEx: extern "efiapi" fn(&DiskIo2, Token: *mut DiskIo2Token) -> Status,
+}
+
+impl DiskIo2 {
+    pub const GUID: Guid = guid!("151c8eae-7f2c-472c-9e54-9828194f6a88");
+}
@@ -0,0 +1,46 @@
+use crate::prelude::*;
+
+/// Use the discovered EDID rather than the one returned by the override
+pub const EDID_OVERRIDE_DONT_OVERRIDE: u32 = 0x01;
+/// Treat the video output as supporting hot plug
+pub const EDID_OVERRIDE_ENABLE_HOT_PLUG: u32 = 0x02;
+
+/// The EDID of the display attached to a video output, as read by the GOP
+/// driver
+#[repr(C)]
+pub struct EdidDiscovered {
+    pub SizeOfEdid: u32,
+    pub Edid: *const u8,
+}
+
+impl EdidDiscovered {
+    pub const GUID: Guid = guid!("1c0c34f6-d380-41fa-a049-8ad06c1a66aa");
+}
+
+/// The EDID in use, which is the discovered EDID unless a platform override
+/// replaced it
+#[repr(C)]
+pub struct EdidActive {
+    pub SizeOfEdid: u32,
+    pub Edid: *const u8,
+}
+
+impl EdidActive {
+    pub const GUID: Guid = guid!("bd8c1056-9f36-44ec-92a8-a6337f817986");
+}
+
+/// Provided by the platform to replace the EDID of a display
+#[repr(C)]
+pub struct EdidOverride {
+    pub GetEdid: extern "efiapi" fn(
+        &EdidOverride,
+        ChildHandle: &Handle,
+        Attributes: &mut u32,
+        EdidSize: &mut usize,
+        Edid: &mut *mut u8,
+    ) -> Status,
+}
+
+impl EdidOverride {
+    pub const GUID: Guid = guid!("48ecb431-fb72-45c0-a922-f458fe040bd5");
+}
@@ -55,6 +55,33 @@ impl FileInfo {
     pub const ID: Guid = guid!("09576e92-6d3f-11d2-8e39-00a0c969723b");
 }
 
+/// EFI_FILE_SYSTEM_INFO, followed by a null-terminated volume label
+#[derive(Debug)]
+#[repr(C)]
+pub struct FileSystemInfo {
+    pub Size: u64,
+    pub ReadOnly: bool,
+    pub VolumeSize: u64,
+    pub FreeSpace: u64,
+    pub BlockSize: u32,
+    pub VolumeLabel: [u16; 0],
+}
+
+impl FileSystemInfo {
+    pub const ID: Guid = guid!("09576e93-6d3f-11d2-8e39-00a0c969723b");
+}
+
+/// EFI_FILE_SYSTEM_VOLUME_LABEL, a null-terminated volume label
+#[derive(Debug)]
+#[repr(C)]
+pub struct FileSystemVolumeLabel {
+    pub VolumeLabel: [u16; 0],
+}
+
+impl FileSystemVolumeLabel {
+    pub const ID: Guid = guid!("db47d7d3-fe81-11d3-9a35-0090273fc14d");
+}
+
 #[repr(C)]
 pub struct File {
     pub Revision: u64,
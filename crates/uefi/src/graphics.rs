@@ -1,6 +1,6 @@
 use crate::prelude::*;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 #[repr(C)]
 pub struct GraphicsBltPixel {
     pub Blue: u8,
@@ -9,6 +9,17 @@ pub struct GraphicsBltPixel {
     pub Reserved: u8,
 }
 
+impl GraphicsBltPixel {
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        GraphicsBltPixel {
+            Blue: blue,
+            Green: green,
+            Red: red,
+            Reserved: 0,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub enum GraphicsBltOp {
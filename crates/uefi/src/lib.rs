@@ -5,12 +5,15 @@
 #[macro_use]
 pub mod macros;
 
+pub mod ata;
 pub mod block_io;
 pub mod boot;
 pub mod capsule;
 pub mod component_name;
 pub mod config;
 pub mod device;
+pub mod disk_io;
+pub mod edid;
 pub mod firmware_volume;
 pub mod fs;
 pub mod graphics;
@@ -18,12 +21,17 @@ pub mod guid;
 pub mod hii;
 pub mod loaded_image;
 pub mod memory;
+pub mod nvme;
+pub mod partition;
 pub mod pointer;
 pub mod prelude;
+pub mod ram_disk;
 pub mod reset;
 pub mod runtime;
+pub mod scsi;
 pub mod shell;
 pub mod status;
+pub mod storage_security;
 pub mod system;
 pub mod text;
 pub mod time;
@@ -50,3 +58,10 @@ pub struct TableHeader {
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(transparent)]
 pub struct Tpl(pub usize);
+
+impl Tpl {
+    pub const APPLICATION: Self = Self(4);
+    pub const CALLBACK: Self = Self(8);
+    pub const NOTIFY: Self = Self(16);
+    pub const HIGH_LEVEL: Self = Self(31);
+}
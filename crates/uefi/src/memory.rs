@@ -1,3 +1,6 @@
+/// The size of the pages allocated by boot services
+pub const PAGE_SIZE: usize = 4096;
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 #[repr(transparent)]
 pub struct PhysicalAddress(pub u64);
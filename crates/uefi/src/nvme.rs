@@ -0,0 +1,98 @@
+use crate::device::DevicePath;
+use crate::prelude::*;
+
+pub const NVME_PASS_THRU_ATTRIBUTES_PHYSICAL: u32 = 0x0001;
+pub const NVME_PASS_THRU_ATTRIBUTES_LOGICAL: u32 = 0x0002;
+pub const NVME_PASS_THRU_ATTRIBUTES_NONBLOCKIO: u32 = 0x0004;
+pub const NVME_PASS_THRU_ATTRIBUTES_CMD_SET_NVM: u32 = 0x0008;
+
+// Valid command dwords
+pub const CDW2_VALID: u8 = 0x01;
+pub const CDW3_VALID: u8 = 0x02;
+pub const CDW10_VALID: u8 = 0x04;
+pub const CDW11_VALID: u8 = 0x08;
+pub const CDW12_VALID: u8 = 0x10;
+pub const CDW13_VALID: u8 = 0x20;
+pub const CDW14_VALID: u8 = 0x40;
+pub const CDW15_VALID: u8 = 0x80;
+
+// Queue types
+pub const NVME_ADMIN_QUEUE: u8 = 0x00;
+pub const NVME_IO_QUEUE: u8 = 0x01;
+
+/// Passed to GetNextNamespace to begin
+pub const NVME_ALL_NAMESPACES: u32 = 0xFFFFFFFF;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct NvmExpressPassThruMode {
+    pub Attributes: u32,
+    pub IoAlign: u32,
+    pub NvmeVersion: u32,
+}
+
+/// EFI_NVME_COMMAND, where Cdw0 holds the opcode in bits 0-7 and the fused
+/// operation in bits 8-9
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct NvmeCommand {
+    pub Cdw0: u32,
+    pub Flags: u8,
+    pub Nsid: u32,
+    pub Cdw2: u32,
+    pub Cdw3: u32,
+    pub Cdw10: u32,
+    pub Cdw11: u32,
+    pub Cdw12: u32,
+    pub Cdw13: u32,
+    pub Cdw14: u32,
+    pub Cdw15: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct NvmeCompletion {
+    pub DW0: u32,
+    pub DW1: u32,
+    pub DW2: u32,
+    pub DW3: u32,
+}
+
+#[repr(C)]
+pub struct NvmExpressPassThruCommandPacket {
+    /// The timeout in 100ns units, or 0 to wait forever
+    pub CommandTimeout: u64,
+    pub TransferBuffer: *mut u8,
+    pub TransferLength: u32,
+    pub MetadataBuffer: *mut u8,
+    pub MetadataLength: u32,
+    pub QueueType: u8,
+    pub NvmeCmd: *mut NvmeCommand,
+    pub NvmeCompletion: *mut NvmeCompletion,
+}
+
+#[repr(C)]
+pub struct NvmExpressPassThru {
+    pub Mode: &'static NvmExpressPassThruMode,
+    pub PassThru: extern "efiapi" fn(
+        &NvmExpressPassThru,
+        NamespaceId: u32,
+        Packet: &mut NvmExpressPassThruCommandPacket,
+        Event: Event,
+    ) -> Status,
+    pub GetNextNamespace: extern "efiapi" fn(&NvmExpressPassThru, NamespaceId: &mut u32) -> Status,
+    pub BuildDevicePath: extern "efiapi" fn(
+        &NvmExpressPassThru,
+        NamespaceId: u32,
+        DevicePath: &mut *mut DevicePath,
+    ) -> Status,
+    pub GetNamespace: extern "efiapi" fn(
+        &NvmExpressPassThru,
+        DevicePath: *const DevicePath,
+        NamespaceId: &mut u32,
+    ) -> Status,
+}
+
+impl NvmExpressPassThru {
+    pub const GUID: Guid = guid!("52c78312-8edc-4233-98f2-1a1aa5e388a5");
+}
@@ -0,0 +1,57 @@
+use crate::prelude::*;
+
+pub const PARTITION_TYPE_OTHER: u32 = 0x00;
+pub const PARTITION_TYPE_MBR: u32 = 0x01;
+pub const PARTITION_TYPE_GPT: u32 = 0x02;
+
+/// MBR_PARTITION_RECORD
+#[derive(Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct MbrPartitionRecord {
+    pub BootIndicator: u8,
+    pub StartHead: u8,
+    pub StartSector: u8,
+    pub StartTrack: u8,
+    pub OSIndicator: u8,
+    pub EndHead: u8,
+    pub EndSector: u8,
+    pub EndTrack: u8,
+    pub StartingLBA: [u8; 4],
+    pub SizeInLBA: [u8; 4],
+}
+
+/// EFI_PARTITION_ENTRY
+#[derive(Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct PartitionEntry {
+    pub PartitionTypeGUID: Guid,
+    pub UniquePartitionGUID: Guid,
+    pub StartingLBA: u64,
+    pub EndingLBA: u64,
+    pub Attributes: u64,
+    pub PartitionName: [u16; 36],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub union PartitionInfoRecord {
+    pub Mbr: MbrPartitionRecord,
+    pub Gpt: PartitionEntry,
+}
+
+/// EFI_PARTITION_INFO_PROTOCOL
+#[repr(C, packed)]
+pub struct PartitionInfo {
+    pub Revision: u32,
+    /// One of the PARTITION_TYPE_* values
+    pub Type: u32,
+    /// Non-zero for an EFI system partition
+    pub System: u8,
+    pub Reserved: [u8; 7],
+    pub Info: PartitionInfoRecord,
+}
+
+impl PartitionInfo {
+    pub const GUID: Guid = guid!("8cf2f62c-bc9b-4821-808d-ec9ec421a1a0");
+    pub const REVISION: u32 = 0x0001000;
+}
@@ -0,0 +1,27 @@
+use crate::device::DevicePath;
+use crate::prelude::*;
+
+/// A volatile disk image
+pub const VIRTUAL_DISK_GUID: Guid = guid!("77ab535a-45fc-624b-5560-f7b281d1f96e");
+/// A volatile CD or DVD image
+pub const VIRTUAL_CD_GUID: Guid = guid!("3d5abd30-4175-87ce-6d64-d2ade523c4bb");
+/// A disk image that persists across reboots
+pub const PERSISTENT_VIRTUAL_DISK_GUID: Guid = guid!("5cea02c9-4d07-69d3-269f-4496fbe096f9");
+/// A CD or DVD image that persists across reboots
+pub const PERSISTENT_VIRTUAL_CD_GUID: Guid = guid!("08018188-42cd-bb48-100f-5387d53ded3d");
+
+#[repr(C)]
+pub struct RamDisk {
+    pub Register: extern "efiapi" fn(
+        RamDiskBase: u64,
+        RamDiskSize: u64,
+        RamDiskType: &Guid,
+        ParentDevicePath: *const DevicePath,
+        DevicePath: &mut *mut DevicePath,
+    ) -> Status,
+    pub Unregister: extern "efiapi" fn(DevicePath: *const DevicePath) -> Status,
+}
+
+impl RamDisk {
+    pub const GUID: Guid = guid!("ab38a0df-6873-44a9-87e6-d4eb56148449");
+}
@@ -0,0 +1,73 @@
+use crate::device::DevicePath;
+use crate::prelude::*;
+
+/// The size of a SCSI target ID
+pub const TARGET_MAX_BYTES: usize = 0x10;
+
+pub const EXT_SCSI_PASS_THRU_ATTRIBUTES_PHYSICAL: u32 = 0x0001;
+pub const EXT_SCSI_PASS_THRU_ATTRIBUTES_LOGICAL: u32 = 0x0002;
+pub const EXT_SCSI_PASS_THRU_ATTRIBUTES_NONBLOCKIO: u32 = 0x0004;
+
+// Data directions
+pub const EXT_SCSI_DATA_DIRECTION_READ: u8 = 0;
+pub const EXT_SCSI_DATA_DIRECTION_WRITE: u8 = 1;
+pub const EXT_SCSI_DATA_DIRECTION_BIDIRECTIONAL: u8 = 2;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct ExtScsiPassThruMode {
+    pub AdapterId: u32,
+    pub Attributes: u32,
+    pub IoAlign: u32,
+}
+
+#[repr(C)]
+pub struct ExtScsiPassThruScsiRequestPacket {
+    /// The timeout in 100ns units, or 0 to wait forever
+    pub Timeout: u64,
+    pub InDataBuffer: *mut u8,
+    pub OutDataBuffer: *const u8,
+    pub SenseData: *mut u8,
+    pub Cdb: *const u8,
+    pub InTransferLength: u32,
+    pub OutTransferLength: u32,
+    pub CdbLength: u8,
+    pub DataDirection: u8,
+    pub HostAdapterStatus: u8,
+    pub TargetStatus: u8,
+    pub SenseDataLength: u8,
+}
+
+#[repr(C)]
+pub struct ExtScsiPassThru {
+    pub Mode: &'static ExtScsiPassThruMode,
+    pub PassThru: extern "efiapi" fn(
+        &ExtScsiPassThru,
+        Target: *const u8,
+        Lun: u64,
+        Packet: &mut ExtScsiPassThruScsiRequestPacket,
+        Event: Event,
+    ) -> Status,
+    /// Begins with a target of all 0xFF bytes
+    pub GetNextTargetLun:
+        extern "efiapi" fn(&ExtScsiPassThru, Target: &mut *mut u8, Lun: &mut u64) -> Status,
+    pub BuildDevicePath: extern "efiapi" fn(
+        &ExtScsiPassThru,
+        Target: *const u8,
+        Lun: u64,
+        DevicePath: &mut *mut DevicePath,
+    ) -> Status,
+    pub GetTargetLun: extern "efiapi" fn(
+        &ExtScsiPassThru,
+        DevicePath: *const DevicePath,
+        Target: &mut *mut u8,
+        Lun: &mut u64,
+    ) -> Status,
+    pub ResetChannel: extern "efiapi" fn(&ExtScsiPassThru) -> Status,
+    pub ResetTargetLun: extern "efiapi" fn(&ExtScsiPassThru, Target: *const u8, Lun: u64) -> Status,
+    pub GetNextTarget: extern "efiapi" fn(&ExtScsiPassThru, Target: &mut *mut u8) -> Status,
+}
+
+impl ExtScsiPassThru {
+    pub const GUID: Guid = guid!("143b7632-b81b-4cb7-abd3-b625a5b9bffe");
+}
@@ -0,0 +1,28 @@
+use crate::prelude::*;
+
+#[repr(C)]
+pub struct StorageSecurityCommand {
+    pub ReceiveData: extern "efiapi" fn(
+        &StorageSecurityCommand,
+        MediaId: u32,
+        Timeout: u64,
+        SecurityProtocolId: u8,
+        SecurityProtocolSpecificData: u16,
+        PayloadBufferSize: usize,
+        PayloadBuffer: *mut u8,
+        PayloadTransferSize: &mut usize,
+    ) -> Status,
+    pub SendData: extern "efiapi" fn(
+        &StorageSecurityCommand,
+        MediaId: u32,
+        Timeout: u64,
+        SecurityProtocolId: u8,
+        SecurityProtocolSpecificData: u16,
+        PayloadBufferSize: usize,
+        PayloadBuffer: *const u8,
+    ) -> Status,
+}
+
+impl StorageSecurityCommand {
+    pub const GUID: Guid = guid!("c88b0b6d-0dfc-49a7-9cb4-49074b4c3a78");
+}
@@ -1,5 +1,74 @@
 use crate::prelude::*;
 
+// Scan codes of keys without a character
+pub const SCAN_NULL: u16 = 0x0000;
+pub const SCAN_UP: u16 = 0x0001;
+pub const SCAN_DOWN: u16 = 0x0002;
+pub const SCAN_RIGHT: u16 = 0x0003;
+pub const SCAN_LEFT: u16 = 0x0004;
+pub const SCAN_HOME: u16 = 0x0005;
+pub const SCAN_END: u16 = 0x0006;
+pub const SCAN_INSERT: u16 = 0x0007;
+pub const SCAN_DELETE: u16 = 0x0008;
+pub const SCAN_PAGE_UP: u16 = 0x0009;
+pub const SCAN_PAGE_DOWN: u16 = 0x000A;
+pub const SCAN_F1: u16 = 0x000B;
+pub const SCAN_F2: u16 = 0x000C;
+pub const SCAN_F3: u16 = 0x000D;
+pub const SCAN_F4: u16 = 0x000E;
+pub const SCAN_F5: u16 = 0x000F;
+pub const SCAN_F6: u16 = 0x0010;
+pub const SCAN_F7: u16 = 0x0011;
+pub const SCAN_F8: u16 = 0x0012;
+pub const SCAN_F9: u16 = 0x0013;
+pub const SCAN_F10: u16 = 0x0014;
+pub const SCAN_F11: u16 = 0x0015;
+pub const SCAN_F12: u16 = 0x0016;
+pub const SCAN_ESC: u16 = 0x0017;
+pub const SCAN_F13: u16 = 0x0068;
+pub const SCAN_F14: u16 = 0x0069;
+pub const SCAN_F15: u16 = 0x006A;
+pub const SCAN_F16: u16 = 0x006B;
+pub const SCAN_F17: u16 = 0x006C;
+pub const SCAN_F18: u16 = 0x006D;
+pub const SCAN_F19: u16 = 0x006E;
+pub const SCAN_F20: u16 = 0x006F;
+pub const SCAN_F21: u16 = 0x0070;
+pub const SCAN_F22: u16 = 0x0071;
+pub const SCAN_F23: u16 = 0x0072;
+pub const SCAN_F24: u16 = 0x0073;
+pub const SCAN_MUTE: u16 = 0x007F;
+pub const SCAN_VOLUME_UP: u16 = 0x0080;
+pub const SCAN_VOLUME_DOWN: u16 = 0x0081;
+pub const SCAN_BRIGHTNESS_UP: u16 = 0x0100;
+pub const SCAN_BRIGHTNESS_DOWN: u16 = 0x0101;
+pub const SCAN_SUSPEND: u16 = 0x0102;
+pub const SCAN_HIBERNATE: u16 = 0x0103;
+pub const SCAN_TOGGLE_DISPLAY: u16 = 0x0104;
+pub const SCAN_RECOVERY: u16 = 0x0105;
+pub const SCAN_EJECT: u16 = 0x0106;
+
+// Shift states, valid when SHIFT_STATE_VALID is set
+pub const SHIFT_STATE_VALID: u32 = 0x8000_0000;
+pub const RIGHT_SHIFT_PRESSED: u32 = 0x0000_0001;
+pub const LEFT_SHIFT_PRESSED: u32 = 0x0000_0002;
+pub const RIGHT_CONTROL_PRESSED: u32 = 0x0000_0004;
+pub const LEFT_CONTROL_PRESSED: u32 = 0x0000_0008;
+pub const RIGHT_ALT_PRESSED: u32 = 0x0000_0010;
+pub const LEFT_ALT_PRESSED: u32 = 0x0000_0020;
+pub const RIGHT_LOGO_PRESSED: u32 = 0x0000_0040;
+pub const LEFT_LOGO_PRESSED: u32 = 0x0000_0080;
+pub const MENU_KEY_PRESSED: u32 = 0x0000_0100;
+pub const SYS_REQ_PRESSED: u32 = 0x0000_0200;
+
+// Toggle states, valid when TOGGLE_STATE_VALID is set
+pub const TOGGLE_STATE_VALID: u8 = 0x80;
+/// Report keys that are normally consumed by the driver, such as a lone Shift
+pub const KEY_STATE_EXPOSED: u8 = 0x40;
+pub const SCROLL_LOCK_ACTIVE: u8 = 0x01;
+pub const NUM_LOCK_ACTIVE: u8 = 0x02;
+pub const CAPS_LOCK_ACTIVE: u8 = 0x04;
+
 #[derive(Clone, Copy, Debug, Default)]
 #[repr(C)]
 pub struct TextInputKey {
@@ -14,6 +83,74 @@ pub struct TextInput {
     pub WaitForKey: Event,
 }
 
+impl TextInput {
+    pub const GUID: Guid = guid!("387477c1-69c7-11d2-8e39-00a0c969723b");
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct KeyState {
+    pub KeyShiftState: u32,
+    pub KeyToggleState: u8,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct KeyData {
+    pub Key: TextInputKey,
+    pub KeyState: KeyState,
+}
+
+#[repr(C)]
+pub struct TextInputEx {
+    pub Reset: extern "efiapi" fn(&TextInputEx, ExtendedVerification: bool) -> Status,
+    pub ReadKeyStrokeEx: extern "efiapi" fn(&TextInputEx, KeyData: &mut KeyData) -> Status,
+    pub WaitForKeyEx: Event,
+    pub SetState: extern "efiapi" fn(&TextInputEx, KeyToggleState: &u8) -> Status,
+    pub RegisterKeyNotify: extern "efiapi" fn(
+        &TextInputEx,
+        KeyData: &KeyData,
+        KeyNotificationFunction: extern "efiapi" fn(KeyData: &KeyData) -> Status,
+        NotifyHandle: &mut usize, /* *mut c_void */
+    ) -> Status,
+    pub UnregisterKeyNotify: extern "efiapi" fn(
+        &TextInputEx,
+        NotificationHandle: usize, /* *mut c_void */
+    ) -> Status,
+}
+
+impl TextInputEx {
+    pub const GUID: Guid = guid!("dd9e7534-7762-4698-8c14-f58517a625aa");
+}
+
+// Text attributes, a foreground color combined with a background color
+pub const BLACK: usize = 0x00;
+pub const BLUE: usize = 0x01;
+pub const GREEN: usize = 0x02;
+pub const CYAN: usize = 0x03;
+pub const RED: usize = 0x04;
+pub const MAGENTA: usize = 0x05;
+pub const BROWN: usize = 0x06;
+pub const LIGHTGRAY: usize = 0x07;
+pub const DARKGRAY: usize = 0x08;
+pub const LIGHTBLUE: usize = 0x09;
+pub const LIGHTGREEN: usize = 0x0A;
+pub const LIGHTCYAN: usize = 0x0B;
+pub const LIGHTRED: usize = 0x0C;
+pub const LIGHTMAGENTA: usize = 0x0D;
+pub const YELLOW: usize = 0x0E;
+pub const WHITE: usize = 0x0F;
+/// Set in a foreground color to make it bright
+pub const BRIGHT: usize = 0x08;
+pub const BACKGROUND_BLACK: usize = 0x00;
+pub const BACKGROUND_BLUE: usize = 0x10;
+pub const BACKGROUND_GREEN: usize = 0x20;
+pub const BACKGROUND_CYAN: usize = 0x30;
+pub const BACKGROUND_RED: usize = 0x40;
+pub const BACKGROUND_MAGENTA: usize = 0x50;
+pub const BACKGROUND_BROWN: usize = 0x60;
+pub const BACKGROUND_LIGHTGRAY: usize = 0x70;
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct TextOutputMode {
@@ -0,0 +1,857 @@
+use alloc_crate::alloc::{alloc_zeroed, dealloc, Layout};
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::{cmp, hint, slice};
+use uefi::block_io::{BlockIo, BlockIo2, BlockIo2Token, BlockIoMedia};
+use uefi::boot::EVT_NOTIFY_SIGNAL;
+
+use crate::event::OwnedEvent;
+use crate::io::{self, Read, Seek, SeekFrom, Write};
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+/// The largest transfer made through a bounce buffer at once
+const BOUNCE_SIZE: usize = 64 * 1024;
+
+/// A zeroed heap buffer with a guaranteed alignment, for devices with an
+/// `IoAlign` requirement
+///
+/// Pool allocations are only 8 byte aligned whatever the layout asks for, so
+/// the buffer is over-allocated and its start rounded up to the alignment.
+pub struct AlignedBuffer {
+    /// The start of the allocation
+    alloc: NonNull<u8>,
+    alloc_layout: Layout,
+    ptr: NonNull<u8>,
+    len: usize,
+    align: usize,
+}
+
+impl AlignedBuffer {
+    /// Allocate `len` bytes aligned to `align`, where an `align` of 0 means 1
+    pub fn new(len: usize, align: usize) -> Result<Self> {
+        let align = cmp::max(align, 1);
+        if !align.is_power_of_two() {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        let alloc_len = cmp::max(len, 1)
+            .checked_add(align - 1)
+            .ok_or(Status::INVALID_PARAMETER)?;
+        let alloc_layout =
+            Layout::from_size_align(alloc_len, 1).map_err(|_| Status::INVALID_PARAMETER)?;
+        let alloc =
+            NonNull::new(unsafe { alloc_zeroed(alloc_layout) }).ok_or(Status::OUT_OF_RESOURCES)?;
+        let offset = (alloc.as_ptr() as usize).wrapping_neg() & (align - 1);
+        let ptr = NonNull::new(unsafe { alloc.as_ptr().add(offset) }).unwrap();
+        Ok(AlignedBuffer {
+            alloc,
+            alloc_layout,
+            ptr,
+            len,
+            align,
+        })
+    }
+
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.alloc.as_ptr(), self.alloc_layout) }
+    }
+}
+
+/// A snapshot of the media attached to a block device
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MediaInfo {
+    pub media_id: u32,
+    pub removable: bool,
+    pub present: bool,
+    pub logical_partition: bool,
+    pub read_only: bool,
+    pub write_caching: bool,
+    pub block_size: u32,
+    pub io_align: u32,
+    pub last_block: u64,
+}
+
+impl MediaInfo {
+    fn new(media: &BlockIoMedia) -> Self {
+        MediaInfo {
+            media_id: media.MediaId,
+            removable: media.RemovableMedia,
+            present: media.MediaPresent,
+            logical_partition: media.LogicalPartition,
+            read_only: media.ReadOnly,
+            write_caching: media.WriteCaching,
+            block_size: media.BlockSize,
+            io_align: media.IoAlign,
+            last_block: media.LastBlock,
+        }
+    }
+
+    pub fn block_count(&self) -> u64 {
+        if self.present {
+            self.last_block + 1
+        } else {
+            0
+        }
+    }
+
+    /// The size of the media in bytes
+    pub fn size(&self) -> u64 {
+        self.block_count() * u64::from(self.block_size)
+    }
+}
+
+/// A block device, readable and writable at byte granularity
+///
+/// The media present when the device is opened is remembered. If it is
+/// removed or replaced, all operations fail with `MEDIA_CHANGED` until
+/// [`BlockDevice::refresh`] is called.
+pub struct BlockDevice {
+    inner: &'static mut BlockIo,
+    media: MediaInfo,
+    position: u64,
+}
+
+impl Protocol<BlockIo> for BlockDevice {
+    fn guid() -> Guid {
+        BlockIo::GUID
+    }
+
+    fn new(inner: &'static mut BlockIo) -> Self {
+        let media = MediaInfo::new(inner.Media);
+        BlockDevice {
+            inner,
+            media,
+            position: 0,
+        }
+    }
+}
+
+impl BlockDevice {
+    pub fn inner(&self) -> &BlockIo {
+        self.inner
+    }
+
+    /// The media this device was opened with, or last refreshed to
+    pub fn media(&self) -> &MediaInfo {
+        &self.media
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.media.block_size as usize
+    }
+
+    pub fn size(&self) -> u64 {
+        self.media.size()
+    }
+
+    /// Returns `MEDIA_CHANGED` if the media was replaced, and `NO_MEDIA` if
+    /// there is none
+    pub fn check_media(&self) -> Result<()> {
+        let media = self.inner.Media;
+        if media.MediaId != self.media.media_id || media.MediaPresent != self.media.present {
+            Err(Status::MEDIA_CHANGED)
+        } else if !media.MediaPresent || media.BlockSize == 0 {
+            Err(Status::NO_MEDIA)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Accept the current media after a `MEDIA_CHANGED` error, moving back to
+    /// the start of the device
+    pub fn refresh(&mut self) -> &MediaInfo {
+        self.media = MediaInfo::new(self.inner.Media);
+        self.position = 0;
+        &self.media
+    }
+
+    /// Reset the device, and with `extended` run more thorough diagnostics
+    pub fn reset(&mut self, extended: bool) -> Result<()> {
+        (self.inner.Reset)(self.inner, extended).into()
+    }
+
+    /// Allocate a zeroed buffer of `blocks` blocks with the device alignment
+    pub fn alloc_buffer(&self, blocks: usize) -> Result<AlignedBuffer> {
+        let len = blocks
+            .checked_mul(self.block_size())
+            .ok_or(Status::INVALID_PARAMETER)?;
+        AlignedBuffer::new(len, self.media.io_align as usize)
+    }
+
+    /// The number of blocks to bounce a transfer of `len` bytes through
+    fn bounce_blocks(&self, len: usize) -> usize {
+        cmp::min(len, BOUNCE_SIZE).div_ceil(self.block_size())
+    }
+
+    fn is_aligned(&self, ptr: *const u8) -> bool {
+        let align = cmp::max(self.media.io_align as usize, 1);
+        ptr as usize % align == 0
+    }
+
+    /// Read whole blocks starting at `lba`. `buf` must be a multiple of the
+    /// block size, and is bounced through an aligned buffer if needed.
+    pub fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        self.check_media()?;
+        if buf.len() % self.block_size() != 0 {
+            return Err(Status::BAD_BUFFER_SIZE);
+        }
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        if self.is_aligned(buf.as_ptr()) {
+            return self.read_raw(lba, buf);
+        }
+
+        let mut bounce = self.alloc_buffer(self.bounce_blocks(buf.len()))?;
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(bounce.len()) {
+            let data = &mut bounce[..chunk.len()];
+            self.read_raw(lba, data)?;
+            chunk.copy_from_slice(data);
+            lba += (chunk.len() / self.block_size()) as u64;
+        }
+        Ok(())
+    }
+
+    /// Write whole blocks starting at `lba`. `buf` must be a multiple of the
+    /// block size, and is bounced through an aligned buffer if needed.
+    pub fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        self.check_media()?;
+        if self.media.read_only {
+            return Err(Status::WRITE_PROTECTED);
+        }
+        if buf.len() % self.block_size() != 0 {
+            return Err(Status::BAD_BUFFER_SIZE);
+        }
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        if self.is_aligned(buf.as_ptr()) {
+            return self.write_raw(lba, buf);
+        }
+
+        let mut bounce = self.alloc_buffer(self.bounce_blocks(buf.len()))?;
+        let mut lba = lba;
+        for chunk in buf.chunks(bounce.len()) {
+            let data = &mut bounce[..chunk.len()];
+            data.copy_from_slice(chunk);
+            self.write_raw(lba, data)?;
+            lba += (chunk.len() / self.block_size()) as u64;
+        }
+        Ok(())
+    }
+
+    fn read_raw(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        (self.inner.ReadBlocks)(
+            self.inner,
+            self.media.media_id,
+            lba,
+            buf.len(),
+            buf.as_mut_ptr(),
+        )
+        .into()
+    }
+
+    fn write_raw(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        (self.inner.WriteBlocks)(
+            self.inner,
+            self.media.media_id,
+            lba,
+            buf.len(),
+            buf.as_ptr(),
+        )
+        .into()
+    }
+
+    /// Check that `len` bytes at `offset` are on the media
+    fn check_range(&self, offset: u64, len: usize) -> Result<()> {
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= self.size() => Ok(()),
+            _ => Err(Status::INVALID_PARAMETER),
+        }
+    }
+
+    /// Fill `buf` with the bytes at `offset`, which need not be block aligned
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        self.check_media()?;
+        self.check_range(offset, buf.len())?;
+
+        let block_size = self.block_size();
+        if offset % block_size as u64 == 0 && buf.len() % block_size == 0 {
+            return self.read_blocks(offset / block_size as u64, buf);
+        }
+
+        let mut bounce = self.alloc_buffer(self.bounce_blocks(BOUNCE_SIZE))?;
+        let mut offset = offset;
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let lba = offset / block_size as u64;
+            let head = (offset % block_size as u64) as usize;
+            let count = cmp::min(buf.len(), bounce.len() - head);
+            let blocks = (head + count).div_ceil(block_size);
+
+            let data = &mut bounce[..blocks * block_size];
+            self.read_raw(lba, data)?;
+            buf[..count].copy_from_slice(&data[head..head + count]);
+
+            buf = &mut buf[count..];
+            offset += count as u64;
+        }
+        Ok(())
+    }
+
+    /// Write `buf` at `offset`, which need not be block aligned. Partial
+    /// blocks are read first so that the surrounding bytes are kept.
+    pub fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<()> {
+        self.check_media()?;
+        if self.media.read_only {
+            return Err(Status::WRITE_PROTECTED);
+        }
+        self.check_range(offset, buf.len())?;
+
+        let block_size = self.block_size();
+        if offset % block_size as u64 == 0 && buf.len() % block_size == 0 {
+            return self.write_blocks(offset / block_size as u64, buf);
+        }
+
+        let mut bounce = self.alloc_buffer(self.bounce_blocks(BOUNCE_SIZE))?;
+        let mut offset = offset;
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let lba = offset / block_size as u64;
+            let head = (offset % block_size as u64) as usize;
+            let count = cmp::min(buf.len(), bounce.len() - head);
+            let blocks = (head + count).div_ceil(block_size);
+            let tail = (head + count) % block_size;
+
+            let data = &mut bounce[..blocks * block_size];
+            if head != 0 {
+                self.read_raw(lba, &mut data[..block_size])?;
+            }
+            if tail != 0 && (head == 0 || blocks > 1) {
+                let last = (blocks - 1) * block_size;
+                self.read_raw(lba + blocks as u64 - 1, &mut data[last..])?;
+            }
+            data[head..head + count].copy_from_slice(&buf[..count]);
+            self.write_raw(lba, data)?;
+
+            buf = &buf[count..];
+            offset += count as u64;
+        }
+        Ok(())
+    }
+
+    /// Write any data cached by the device to the media
+    pub fn flush(&mut self) -> Result<()> {
+        self.check_media()?;
+        (self.inner.FlushBlocks)(self.inner).into()
+    }
+}
+
+impl Read for BlockDevice {
+    /// Reads stop at the end of the media, including after seeking past it
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size().saturating_sub(self.position);
+        let count = cmp::min(buf.len() as u64, remaining) as usize;
+        if count == 0 {
+            return Ok(0);
+        }
+        self.read_at(self.position, &mut buf[..count])?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Write for BlockDevice {
+    /// Writes stop at the end of the media
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let remaining = self.size().saturating_sub(self.position);
+        let count = cmp::min(buf.len() as u64, remaining) as usize;
+        if count == 0 {
+            return Ok(0);
+        }
+        self.write_at(self.position, &buf[..count])?;
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(BlockDevice::flush(self)?)
+    }
+}
+
+impl Seek for BlockDevice {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.size(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        self.position = base
+            .checked_add_signed(offset)
+            .ok_or(io::Error::INVALID_INPUT)?;
+        Ok(self.position)
+    }
+}
+
+/// The token passed with an asynchronous request
+pub trait IoToken {
+    fn new(event: Event) -> Self;
+
+    fn status(&self) -> Status;
+}
+
+impl IoToken for BlockIo2Token {
+    fn new(event: Event) -> Self {
+        BlockIo2Token {
+            Event: event,
+            TransactionStatus: Status::SUCCESS,
+        }
+    }
+
+    fn status(&self) -> Status {
+        self.TransactionStatus
+    }
+}
+
+pub(crate) type Callback = Box<dyn FnOnce(Status)>;
+
+struct Request<T> {
+    token: T,
+    event: Option<OwnedEvent>,
+    buffer: Option<AlignedBuffer>,
+    notify: bool,
+    done: AtomicBool,
+    callback: Option<Callback>,
+}
+
+extern "efiapi" fn complete<T: IoToken>(_event: Event, context: usize) {
+    let request = unsafe { &mut *(context as *mut Request<T>) };
+    request.done.store(true, Ordering::Release);
+    if let Some(callback) = request.callback.take() {
+        callback(request.token.status());
+    }
+}
+
+/// An asynchronous request, which owns its buffer until it completes
+///
+/// Completion is found either by polling with [`Pending::is_complete`], or
+/// through a callback run by the firmware at `TPL_CALLBACK`. Dropping a request
+/// that is still in flight blocks until it completes.
+///
+/// A request with a callback can only complete once the firmware runs the
+/// callback, so it must not be waited for or dropped at `TPL_CALLBACK` or
+/// above, such as from another event notification, as that never returns.
+pub struct Pending<T: IoToken> {
+    request: *mut Request<T>,
+}
+
+impl<T: IoToken> Pending<T> {
+    /// Create the token and event for a request, then start it with `start`
+    pub(crate) fn submit<F>(
+        buffer: AlignedBuffer,
+        callback: Option<Callback>,
+        start: F,
+    ) -> Result<Self>
+    where
+        F: FnOnce(*mut T, &mut AlignedBuffer) -> Status,
+    {
+        let notify = callback.is_some();
+        let pending = Pending {
+            request: Box::into_raw(Box::new(Request {
+                token: T::new(Event(0)),
+                event: None,
+                buffer: Some(buffer),
+                notify,
+                done: AtomicBool::new(true),
+                callback,
+            })),
+        };
+
+        let request = unsafe { &mut *pending.request };
+        let event = if notify {
+            unsafe {
+                OwnedEvent::with_notify(
+                    EVT_NOTIFY_SIGNAL,
+                    Tpl::CALLBACK,
+                    complete::<T>,
+                    pending.request as usize,
+                )?
+            }
+        } else {
+            OwnedEvent::new(0)?
+        };
+        request.token = T::new(event.event());
+        request.event = Some(event);
+
+        request.done.store(false, Ordering::Release);
+        let status = start(&mut request.token, request.buffer.as_mut().unwrap());
+        if status != Status::SUCCESS {
+            request.done.store(true, Ordering::Release);
+            return Err(status);
+        }
+
+        Ok(pending)
+    }
+
+    /// Returns true once the request has completed, without blocking
+    pub fn is_complete(&self) -> bool {
+        let request = unsafe { &*self.request };
+        if request.done.load(Ordering::Acquire) {
+            return true;
+        }
+
+        if !request.notify {
+            if let Some(Ok(true)) = request.event.as_ref().map(OwnedEvent::check) {
+                request.done.store(true, Ordering::Release);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// The result of the request, if it has completed
+    pub fn status(&self) -> Option<Status> {
+        if self.is_complete() {
+            Some(unsafe { (*self.request).token.status() })
+        } else {
+            None
+        }
+    }
+
+    fn block(&self) {
+        let request = unsafe { &*self.request };
+        while !self.is_complete() {
+            match request.event.as_ref() {
+                // Waiting clears the signal, so note completion here
+                Some(event) if !request.notify && event.wait().is_ok() => {
+                    request.done.store(true, Ordering::Release);
+                }
+                _ => hint::spin_loop(),
+            }
+        }
+    }
+
+    /// Block until the request completes, returning its buffer. For a request
+    /// with a callback, this must be called below `TPL_CALLBACK`.
+    pub fn wait(self) -> Result<AlignedBuffer> {
+        self.block();
+        let request = unsafe { &mut *self.request };
+        let buffer = request.buffer.take().unwrap();
+        match request.token.status() {
+            Status::SUCCESS => Ok(buffer),
+            status => Err(status),
+        }
+    }
+}
+
+impl<T: IoToken> Drop for Pending<T> {
+    fn drop(&mut self) {
+        self.block();
+        drop(unsafe { Box::from_raw(self.request) });
+    }
+}
+
+/// A block device supporting asynchronous requests
+///
+/// Buffers must be allocated with [`BlockDevice2::alloc_buffer`] or otherwise
+/// meet the `IoAlign` requirement of the media.
+pub struct BlockDevice2 {
+    inner: &'static mut BlockIo2,
+    media: MediaInfo,
+}
+
+impl Protocol<BlockIo2> for BlockDevice2 {
+    fn guid() -> Guid {
+        BlockIo2::GUID
+    }
+
+    fn new(inner: &'static mut BlockIo2) -> Self {
+        let media = MediaInfo::new(inner.Media);
+        BlockDevice2 { inner, media }
+    }
+}
+
+impl BlockDevice2 {
+    pub fn inner(&self) -> &BlockIo2 {
+        self.inner
+    }
+
+    /// The media this device was opened with, or last refreshed to
+    pub fn media(&self) -> &MediaInfo {
+        &self.media
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.media.block_size as usize
+    }
+
+    pub fn size(&self) -> u64 {
+        self.media.size()
+    }
+
+    /// Returns `MEDIA_CHANGED` if the media was replaced, and `NO_MEDIA` if
+    /// there is none
+    pub fn check_media(&self) -> Result<()> {
+        let media = self.inner.Media;
+        if media.MediaId != self.media.media_id || media.MediaPresent != self.media.present {
+            Err(Status::MEDIA_CHANGED)
+        } else if !media.MediaPresent || media.BlockSize == 0 {
+            Err(Status::NO_MEDIA)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Accept the current media after a `MEDIA_CHANGED` error
+    pub fn refresh(&mut self) -> &MediaInfo {
+        self.media = MediaInfo::new(self.inner.Media);
+        &self.media
+    }
+
+    /// Reset the device, aborting any requests in flight
+    pub fn reset(&mut self, extended: bool) -> Result<()> {
+        (self.inner.Reset)(self.inner, extended).into()
+    }
+
+    /// Allocate a zeroed buffer of `blocks` blocks with the device alignment
+    pub fn alloc_buffer(&self, blocks: usize) -> Result<AlignedBuffer> {
+        let len = blocks
+            .checked_mul(self.block_size())
+            .ok_or(Status::INVALID_PARAMETER)?;
+        AlignedBuffer::new(len, self.media.io_align as usize)
+    }
+
+    fn check_buffer(&self, buf: &AlignedBuffer) -> Result<()> {
+        self.check_media()?;
+        if buf.len() % self.block_size() != 0 {
+            Err(Status::BAD_BUFFER_SIZE)
+        } else if buf.as_ptr() as usize % cmp::max(self.media.io_align as usize, 1) != 0 {
+            Err(Status::INVALID_PARAMETER)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read(
+        &mut self,
+        lba: u64,
+        buf: AlignedBuffer,
+        callback: Option<Callback>,
+    ) -> Result<Pending<BlockIo2Token>> {
+        self.check_buffer(&buf)?;
+        let inner = &*self.inner;
+        let media_id = self.media.media_id;
+        Pending::submit(buf, callback, |token, buf| {
+            (inner.ReadBlocksEx)(inner, media_id, lba, token, buf.len(), buf.as_mut_ptr())
+        })
+    }
+
+    fn write(
+        &mut self,
+        lba: u64,
+        buf: AlignedBuffer,
+        callback: Option<Callback>,
+    ) -> Result<Pending<BlockIo2Token>> {
+        self.check_buffer(&buf)?;
+        if self.media.read_only {
+            return Err(Status::WRITE_PROTECTED);
+        }
+        let inner = &*self.inner;
+        let media_id = self.media.media_id;
+        Pending::submit(buf, callback, |token, buf| {
+            (inner.WriteBlocksEx)(inner, media_id, lba, token, buf.len(), buf.as_ptr())
+        })
+    }
+
+    /// Start reading blocks at `lba` into `buf`, to be polled for completion
+    pub fn read_blocks_async(
+        &mut self,
+        lba: u64,
+        buf: AlignedBuffer,
+    ) -> Result<Pending<BlockIo2Token>> {
+        self.read(lba, buf, None)
+    }
+
+    /// Start reading blocks at `lba` into `buf`, calling `callback` with the
+    /// result when done
+    pub fn read_blocks_with<F: FnOnce(Status) + 'static>(
+        &mut self,
+        lba: u64,
+        buf: AlignedBuffer,
+        callback: F,
+    ) -> Result<Pending<BlockIo2Token>> {
+        self.read(lba, buf, Some(Box::new(callback)))
+    }
+
+    /// Start writing `buf` to the blocks at `lba`, to be polled for completion
+    pub fn write_blocks_async(
+        &mut self,
+        lba: u64,
+        buf: AlignedBuffer,
+    ) -> Result<Pending<BlockIo2Token>> {
+        self.write(lba, buf, None)
+    }
+
+    /// Start writing `buf` to the blocks at `lba`, calling `callback` with the
+    /// result when done
+    pub fn write_blocks_with<F: FnOnce(Status) + 'static>(
+        &mut self,
+        lba: u64,
+        buf: AlignedBuffer,
+        callback: F,
+    ) -> Result<Pending<BlockIo2Token>> {
+        self.write(lba, buf, Some(Box::new(callback)))
+    }
+
+    /// Start flushing data cached by the device to the media
+    pub fn flush_async(&mut self) -> Result<Pending<BlockIo2Token>> {
+        self.check_media()?;
+        let inner = &*self.inner;
+        Pending::submit(AlignedBuffer::new(0, 1)?, None, |token, _| {
+            (inner.FlushBlocksEx)(inner, token)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each byte of the test media is the low byte of its offset
+    extern "efiapi" fn read_blocks(
+        this: &BlockIo,
+        _media_id: u32,
+        lba: u64,
+        size: usize,
+        buffer: *mut u8,
+    ) -> Status {
+        let start = lba * u64::from(this.Media.BlockSize);
+        let buf = unsafe { slice::from_raw_parts_mut(buffer, size) };
+        for (offset, byte) in (start..).zip(buf) {
+            *byte = offset as u8;
+        }
+        Status::SUCCESS
+    }
+
+    extern "efiapi" fn write_blocks(
+        _this: &BlockIo,
+        _media_id: u32,
+        _lba: u64,
+        _size: usize,
+        _buffer: *const u8,
+    ) -> Status {
+        Status::SUCCESS
+    }
+
+    extern "efiapi" fn reset(_this: &BlockIo, _extended: bool) -> Status {
+        Status::SUCCESS
+    }
+
+    extern "efiapi" fn flush(_this: &BlockIo) -> Status {
+        Status::SUCCESS
+    }
+
+    /// A device of four 512 byte blocks
+    fn device() -> BlockDevice {
+        let media = Box::leak(Box::new(BlockIoMedia {
+            MediaId: 1,
+            RemovableMedia: false,
+            MediaPresent: true,
+            LogicalPartition: false,
+            ReadOnly: false,
+            WriteCaching: false,
+            BlockSize: 512,
+            IoAlign: 0,
+            LastBlock: 3,
+        }));
+        BlockDevice::new(Box::leak(Box::new(BlockIo {
+            Revision: 0x00010000,
+            Media: media,
+            Reset: reset,
+            ReadBlocks: read_blocks,
+            WriteBlocks: write_blocks,
+            FlushBlocks: flush,
+        })))
+    }
+
+    #[test]
+    fn read_and_write_stop_at_end() {
+        let mut device = device();
+        device.seek(SeekFrom::Start(1000)).unwrap();
+        let mut data = Vec::new();
+        device.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), 1048);
+        assert!((1000..)
+            .zip(&data)
+            .all(|(offset, &byte)| byte == offset as u8));
+
+        // Past the end, reads and writes do nothing
+        for position in [2048, 4096] {
+            device.seek(SeekFrom::Start(position)).unwrap();
+            assert_eq!(device.read(&mut [0; 16]).unwrap(), 0);
+            assert_eq!(device.read_to_end(&mut data).unwrap(), 0);
+            assert_eq!(device.write(&[0; 16]).unwrap(), 0);
+        }
+
+        device.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(device.write(&[0; 16]).unwrap(), 4);
+    }
+
+    #[test]
+    fn aligned_buffer() {
+        for align in [0, 1, 2, 512, 4096] {
+            // Keep earlier buffers alive so later ones land elsewhere
+            let buffers = (0..4)
+                .map(|i| AlignedBuffer::new(100 + i, align).unwrap())
+                .collect::<Vec<_>>();
+            for (i, buffer) in buffers.iter().enumerate() {
+                assert_eq!(buffer.align(), cmp::max(align, 1));
+                assert_eq!(buffer.as_ptr() as usize % buffer.align(), 0);
+                assert_eq!(buffer.len(), 100 + i);
+                assert!(buffer.iter().all(|&byte| byte == 0));
+            }
+        }
+
+        let mut buffer = AlignedBuffer::new(0, 4096).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.as_mut_ptr() as usize % 4096, 0);
+
+        assert_eq!(
+            AlignedBuffer::new(16, 3).err(),
+            Some(Status::INVALID_PARAMETER)
+        );
+        assert_eq!(
+            AlignedBuffer::new(usize::MAX, 4096).err(),
+            Some(Status::INVALID_PARAMETER)
+        );
+    }
+}
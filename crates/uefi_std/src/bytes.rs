@@ -0,0 +1,44 @@
+//! Reading integers at byte offsets of on-disk and wire structures
+//!
+//! Each reader panics if `data` ends before the integer does, so callers check
+//! lengths first.
+
+pub fn le16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+pub fn le32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+pub fn le64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+pub fn be16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+pub fn be32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+pub fn be64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_order() {
+        let data = [0xFF, 1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(le16(&data, 1), 0x0201);
+        assert_eq!(le32(&data, 1), 0x04030201);
+        assert_eq!(le64(&data, 1), 0x0807060504030201);
+        assert_eq!(be16(&data, 1), 0x0102);
+        assert_eq!(be32(&data, 1), 0x01020304);
+        assert_eq!(be64(&data, 1), 0x0102030405060708);
+    }
+}
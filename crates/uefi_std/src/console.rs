@@ -0,0 +1,796 @@
+//! Text console output with colors, cursor control and ANSI escape sequences
+
+use core::{fmt, mem};
+use uefi::text::{TextOutput, BRIGHT};
+
+use crate::io::PartialUtf8;
+use crate::prelude::*;
+use crate::system_table;
+
+/// The UCS-2 units, including the terminator, passed to `OutputString` at a
+/// time
+const CHUNK_SIZE: usize = 256;
+/// The parameters of a control sequence beyond this many are ignored
+const MAX_PARAMS: usize = 16;
+const ESC: char = '\x1B';
+
+/// A console color, of which backgrounds can only use the first eight
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Color {
+    Black,
+    Blue,
+    Green,
+    Cyan,
+    Red,
+    Magenta,
+    Brown,
+    LightGray,
+    DarkGray,
+    LightBlue,
+    LightGreen,
+    LightCyan,
+    LightRed,
+    LightMagenta,
+    Yellow,
+    White,
+}
+
+impl Color {
+    const ALL: [Color; 16] = [
+        Color::Black,
+        Color::Blue,
+        Color::Green,
+        Color::Cyan,
+        Color::Red,
+        Color::Magenta,
+        Color::Brown,
+        Color::LightGray,
+        Color::DarkGray,
+        Color::LightBlue,
+        Color::LightGreen,
+        Color::LightCyan,
+        Color::LightRed,
+        Color::LightMagenta,
+        Color::Yellow,
+        Color::White,
+    ];
+
+    /// The colors of ANSI codes 30 to 37, which list red before blue
+    const ANSI: [Color; 8] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Brown,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::LightGray,
+    ];
+
+    /// The color with attribute value `value`, of which only the low four
+    /// bits are used
+    pub fn from_attribute(value: usize) -> Self {
+        Self::ALL[value & 0xF]
+    }
+
+    /// The foreground attribute value of the color
+    pub fn attribute(self) -> usize {
+        self as usize
+    }
+
+    pub fn is_bright(self) -> bool {
+        self.attribute() & BRIGHT != 0
+    }
+
+    /// The bright version of a dark color
+    pub fn bright(self) -> Self {
+        Self::from_attribute(self.attribute() | BRIGHT)
+    }
+
+    /// The dark version of a bright color, which is what a bright background
+    /// shows as
+    pub fn dark(self) -> Self {
+        Self::from_attribute(self.attribute() & !BRIGHT)
+    }
+
+    /// The nearest color to an RGB value
+    pub fn from_rgb(red: u8, green: u8, blue: u8) -> Self {
+        let max = red.max(green).max(blue);
+        if max < 0x40 {
+            return Color::Black;
+        }
+        // Channels close to the brightest one are on
+        let on = |channel: u8| u16::from(channel) * 2 >= u16::from(max);
+        let index = usize::from(on(blue)) | usize::from(on(green)) << 1 | usize::from(on(red)) << 2;
+        let color = Self::ALL[index];
+        match color {
+            // Gray levels
+            Color::LightGray if max < 0x90 => Color::DarkGray,
+            Color::LightGray if max < 0xD0 => Color::LightGray,
+            Color::LightGray => Color::White,
+            _ if max >= 0xC0 => color.bright(),
+            _ => color,
+        }
+    }
+
+    /// The color of an entry in the xterm 256 color palette
+    fn from_ansi_256(index: u16) -> Self {
+        match index {
+            0..=7 => Self::ANSI[usize::from(index)],
+            8..=15 => Self::ANSI[usize::from(index) - 8].bright(),
+            16..=231 => {
+                let level = |value: u16| if value == 0 { 0 } else { 55 + value as u8 * 40 };
+                let cube = index - 16;
+                Self::from_rgb(level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+            }
+            _ => {
+                let gray = 8 + (index.min(255) - 232) as u8 * 10;
+                Self::from_rgb(gray, gray, gray)
+            }
+        }
+    }
+}
+
+/// The state of the ANSI escape sequence parser
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum Escape {
+    #[default]
+    None,
+    /// After ESC
+    Start,
+    /// In a control sequence, after ESC [
+    Csi,
+}
+
+/// What the parser makes of a character
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Action {
+    /// Nothing to show yet
+    None,
+    Print(char),
+    /// A control sequence ended in this command, with the parameters in the
+    /// parser
+    Csi(char),
+}
+
+/// A parser of the ANSI escape sequences in printed text
+#[derive(Debug, Default)]
+struct Parser {
+    escape: Escape,
+    params: Vec<u16>,
+    /// The control sequence started with `?`
+    private: bool,
+}
+
+impl Parser {
+    /// Abandon any sequence in progress
+    fn reset(&mut self) {
+        self.escape = Escape::None;
+    }
+
+    fn next(&mut self, c: char) -> Action {
+        match self.escape {
+            Escape::None if c == ESC => self.escape = Escape::Start,
+            Escape::None => return Action::Print(c),
+            Escape::Start if c == '[' => {
+                self.escape = Escape::Csi;
+                self.params.clear();
+                self.params.push(0);
+                self.private = false;
+            }
+            // Other escape sequences have no console equivalent
+            Escape::Start => self.escape = Escape::None,
+            Escape::Csi => match c {
+                '0'..='9' => {
+                    let digit = c as u16 - u16::from(b'0');
+                    if let Some(param) = self.params.last_mut() {
+                        *param = param.saturating_mul(10).saturating_add(digit);
+                    }
+                }
+                // One extra parameter collects those past the limit
+                ';' if self.params.len() <= MAX_PARAMS => self.params.push(0),
+                ';' => (),
+                '?' => self.private = true,
+                // Intermediate bytes
+                ' '..='/' => (),
+                '@'..='~' => {
+                    self.escape = Escape::None;
+                    self.params.truncate(MAX_PARAMS);
+                    return Action::Csi(c);
+                }
+                // Abandon malformed sequences
+                _ => self.escape = Escape::None,
+            },
+        }
+        Action::None
+    }
+}
+
+/// The colors and attributes selected by ANSI SGR sequences
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Rendition {
+    /// The colors selected, before bold and inverse
+    fg: Color,
+    bg: Color,
+    bold: bool,
+    inverse: bool,
+}
+
+impl Rendition {
+    fn new((fg, bg): (Color, Color)) -> Self {
+        Rendition {
+            fg,
+            bg,
+            bold: false,
+            inverse: false,
+        }
+    }
+
+    /// Apply the parameters of a Select Graphic Rendition sequence, where
+    /// resets restore `defaults`
+    fn apply(&mut self, params: &[u16], defaults: (Color, Color)) {
+        let mut params = params.iter().copied();
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Rendition::new(defaults),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                7 => self.inverse = true,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Color::ANSI[usize::from(param - 30)],
+                39 => self.fg = defaults.0,
+                40..=47 => self.bg = Color::ANSI[usize::from(param - 40)],
+                49 => self.bg = defaults.1,
+                90..=97 => self.fg = Color::ANSI[usize::from(param - 90)].bright(),
+                100..=107 => self.bg = Color::ANSI[usize::from(param - 100)].bright(),
+                38 | 48 => match (param, extended_color(&mut params)) {
+                    (38, Some(color)) => self.fg = color,
+                    (_, Some(color)) => self.bg = color,
+                    (_, None) => (),
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// The foreground and background colors shown, after bold and inverse
+    fn colors(self) -> (Color, Color) {
+        let fg = if self.bold { self.fg.bright() } else { self.fg };
+        if self.inverse {
+            (self.bg, fg)
+        } else {
+            (fg, self.bg)
+        }
+    }
+}
+
+/// The color of the `5;n` or `2;r;g;b` parameters after SGR 38 and 48, which
+/// is `None` if they are cut short
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match params.next()? {
+        5 => params.next().map(Color::from_ansi_256),
+        2 => {
+            let mut channel = || params.next().map(|value| value.min(255) as u8);
+            let (red, green, blue) = (channel()?, channel()?, channel()?);
+            Some(Color::from_rgb(red, green, blue))
+        }
+        _ => None,
+    }
+}
+
+/// Output to a text console, collected into chunks to reduce the number of
+/// firmware calls
+///
+/// Output is written when the buffer is full, before any change of color or
+/// cursor, on [`Console::flush`] and on drop.
+pub struct Console {
+    output: &'static TextOutput,
+    buf: Vec<u16>,
+    ansi: bool,
+    parser: Parser,
+    /// The colors restored by ANSI resets
+    default_colors: (Color, Color),
+    rendition: Rendition,
+    saved_cursor: (usize, usize),
+    /// An incomplete UTF-8 sequence at the end of the last write
+    partial: PartialUtf8,
+}
+
+impl Console {
+    pub fn new(output: &'static TextOutput) -> Self {
+        let mut console = Console {
+            output,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            ansi: false,
+            parser: Parser::default(),
+            default_colors: (Color::LightGray, Color::Black),
+            rendition: Rendition::new((Color::LightGray, Color::Black)),
+            saved_cursor: (0, 0),
+            partial: PartialUtf8::default(),
+        };
+        console.default_colors = console.colors();
+        console.rendition = Rendition::new(console.default_colors);
+        console
+    }
+
+    /// The console output device
+    pub fn stdout() -> Self {
+        Self::new(system_table().ConsoleOut)
+    }
+
+    /// The console error device
+    pub fn stderr() -> Self {
+        Self::new(system_table().ConsoleError)
+    }
+
+    /// Interpret ANSI SGR and CSI sequences in printed text, such as those
+    /// for colored output, rather than showing them
+    pub fn set_ansi(&mut self, ansi: bool) {
+        self.ansi = ansi;
+        self.parser.reset();
+    }
+
+    pub fn ansi(&self) -> bool {
+        self.ansi
+    }
+
+    /// Write buffered output
+    pub fn flush(&mut self) -> Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.buf.push(0);
+        let status = (self.output.OutputString)(self.output, self.buf.as_ptr());
+        self.buf.clear();
+
+        // Characters without a glyph are shown as a placeholder
+        match status {
+            Status::SUCCESS | Status::WARN_UNKNOWN_GLYPH => Ok(()),
+            _ => Err(status),
+        }
+    }
+
+    /// Reset the device and clear the screen
+    pub fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        self.flush()?;
+        (self.output.Reset)(self.output, extended_verification).into()
+    }
+
+    /// The number of the current mode
+    pub fn mode(&self) -> usize {
+        self.output.Mode.Mode as usize
+    }
+
+    pub fn mode_count(&self) -> usize {
+        self.output.Mode.MaxMode as usize
+    }
+
+    /// The columns and rows of `mode`
+    pub fn query_mode(&self, mode: usize) -> Result<(usize, usize)> {
+        let (mut columns, mut rows) = (0, 0);
+        let status = (self.output.QueryMode)(self.output, mode, &mut columns, &mut rows);
+
+        match status {
+            Status::SUCCESS => Ok((columns, rows)),
+            _ => Err(status),
+        }
+    }
+
+    /// The columns and rows of the current mode
+    pub fn size(&self) -> Result<(usize, usize)> {
+        self.query_mode(self.mode())
+    }
+
+    /// The number, columns and rows of each supported mode
+    pub fn modes(&self) -> Vec<(usize, usize, usize)> {
+        (0..self.mode_count())
+            .filter_map(|mode| {
+                let (columns, rows) = self.query_mode(mode).ok()?;
+                Some((mode, columns, rows))
+            })
+            .collect()
+    }
+
+    /// Switch to `mode`, which clears the screen
+    pub fn set_mode(&mut self, mode: usize) -> Result<()> {
+        self.flush()?;
+        (self.output.SetMode)(self.output, mode).into()
+    }
+
+    /// Switch to the mode with the most cells, returning its columns and rows
+    pub fn set_largest_mode(&mut self) -> Result<(usize, usize)> {
+        let (mode, columns, rows) = self
+            .modes()
+            .into_iter()
+            .max_by_key(|&(_, columns, rows)| (columns * rows, columns))
+            .ok_or(Status::NOT_FOUND)?;
+        if mode != self.mode() {
+            self.set_mode(mode)?;
+        }
+        Ok((columns, rows))
+    }
+
+    /// The foreground and background colors
+    pub fn colors(&self) -> (Color, Color) {
+        let attribute = self.output.Mode.Attribute as usize;
+        (
+            Color::from_attribute(attribute),
+            Color::from_attribute((attribute >> 4) & 0x7),
+        )
+    }
+
+    /// Set the colors of text written from now on, where a bright background
+    /// is shown dark
+    pub fn set_colors(&mut self, fg: Color, bg: Color) -> Result<()> {
+        self.rendition = Rendition::new((fg, bg));
+        self.apply_colors(fg, bg)
+    }
+
+    fn apply_colors(&mut self, fg: Color, bg: Color) -> Result<()> {
+        self.flush()?;
+        let attribute = fg.attribute() | bg.dark().attribute() << 4;
+        (self.output.SetAttribute)(self.output, attribute).into()
+    }
+
+    /// Fill the screen with the background color and move the cursor home
+    pub fn clear(&mut self) -> Result<()> {
+        self.flush()?;
+        (self.output.ClearScreen)(self.output).into()
+    }
+
+    /// The column and row of the cursor, after any buffered output
+    pub fn cursor(&mut self) -> Result<(usize, usize)> {
+        self.flush()?;
+        let mode = &self.output.Mode;
+        Ok((mode.CursorColumn as usize, mode.CursorRow as usize))
+    }
+
+    /// Move the cursor, which fails if it is outside the screen
+    pub fn set_cursor(&mut self, column: usize, row: usize) -> Result<()> {
+        self.flush()?;
+        (self.output.SetCursorPosition)(self.output, column, row).into()
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.output.Mode.CursorVisible
+    }
+
+    /// Show or hide the cursor, which fails on devices that cannot hide it
+    pub fn enable_cursor(&mut self, visible: bool) -> Result<()> {
+        self.flush()?;
+        (self.output.EnableCursor)(self.output, visible).into()
+    }
+
+    fn push(&mut self, c: char) -> Result<()> {
+        let mut units = [0; 2];
+        // The console only shows the Basic Multilingual Plane
+        let unit = match c.encode_utf16(&mut units) {
+            [unit] => *unit,
+            _ => 0xFFFD,
+        };
+        if c == '\n' {
+            self.push_unit(u16::from(b'\r'))?;
+        }
+        self.push_unit(unit)
+    }
+
+    fn push_unit(&mut self, unit: u16) -> Result<()> {
+        if self.buf.len() + 1 >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        self.buf.push(unit);
+        Ok(())
+    }
+
+    /// Write `text`, converting `\n` to `\r\n`
+    pub fn print(&mut self, text: &str) -> Result<()> {
+        for c in text.chars() {
+            if self.ansi {
+                self.ansi_char(c)?;
+            } else {
+                self.push(c)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ansi_char(&mut self, c: char) -> Result<()> {
+        match self.parser.next(c) {
+            Action::None => Ok(()),
+            Action::Print(c) => self.push(c),
+            Action::Csi(command) => {
+                let params = mem::take(&mut self.parser.params);
+                let result = self.csi(command, &params);
+                self.parser.params = params;
+                result
+            }
+        }
+    }
+
+    /// Run a control sequence ending in `command`. A parameter of 0 means
+    /// the default, as with a missing one.
+    fn csi(&mut self, command: char, params: &[u16]) -> Result<()> {
+        let count = usize::from(params[0].max(1));
+        if self.parser.private {
+            // Show and hide the cursor
+            return match (command, params[0]) {
+                ('h', 25) => self.enable_cursor(true),
+                ('l', 25) => self.enable_cursor(false),
+                _ => Ok(()),
+            };
+        }
+
+        match command {
+            'm' => return self.sgr(params),
+            'J' | 'K' => return self.erase(command, params[0]),
+            's' => {
+                self.saved_cursor = self.cursor()?;
+                return Ok(());
+            }
+            'u' => {
+                let (column, row) = self.saved_cursor;
+                return self.set_cursor(column, row);
+            }
+            _ => (),
+        }
+
+        let (columns, rows) = self.size()?;
+        let (column, row) = self.cursor()?;
+        let last_column = columns.saturating_sub(1);
+        let last_row = rows.saturating_sub(1);
+        let (column, row) = match command {
+            'A' => (column, row.saturating_sub(count)),
+            'B' => (column, row.saturating_add(count).min(last_row)),
+            'C' => (column.saturating_add(count).min(last_column), row),
+            'D' => (column.saturating_sub(count), row),
+            'E' => (0, row.saturating_add(count).min(last_row)),
+            'F' => (0, row.saturating_sub(count)),
+            'G' => (count - 1, row),
+            'd' => (column, count - 1),
+            'H' | 'f' => {
+                let column = usize::from(params.get(1).copied().unwrap_or(0).max(1));
+                (column - 1, count - 1)
+            }
+            _ => return Ok(()),
+        };
+        self.set_cursor(column.min(last_column), row.min(last_row))
+    }
+
+    /// Erase the display for `J` or the line for `K`
+    ///
+    /// The console can only clear the whole screen, so other parts are
+    /// overwritten with spaces. The last cell is left alone, as writing it
+    /// would scroll the screen.
+    fn erase(&mut self, command: char, mode: u16) -> Result<()> {
+        let (columns, rows) = self.size()?;
+        let (column, row) = self.cursor()?;
+        let (start, end) = match (command, mode) {
+            ('J', 2 | 3) => return self.clear(),
+            ('J', 0) => ((column, row), (columns, rows.saturating_sub(1))),
+            ('J', 1) => ((0, 0), (column + 1, row)),
+            ('K', 0) => ((column, row), (columns, row)),
+            ('K', 1) => ((0, row), (column + 1, row)),
+            ('K', 2) => ((0, row), (columns, row)),
+            _ => return Ok(()),
+        };
+
+        for line in start.1..=end.1 {
+            let from = if line == start.1 { start.0 } else { 0 };
+            let mut to = if line == end.1 { end.0 } else { columns };
+            if line + 1 == rows {
+                to = to.min(columns - 1);
+            }
+            if from < to {
+                self.set_cursor(from, line)?;
+                for _ in from..to {
+                    self.push_unit(u16::from(b' '))?;
+                }
+            }
+        }
+        self.set_cursor(column, row)
+    }
+
+    /// Apply a Select Graphic Rendition sequence
+    fn sgr(&mut self, params: &[u16]) -> Result<()> {
+        self.rendition.apply(params, self.default_colors);
+        let (fg, bg) = self.rendition.colors();
+        self.apply_colors(fg, bg)
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.print(text).map_err(|_| fmt::Error)
+    }
+}
+
+impl crate::io::Write for Console {
+    fn write(&mut self, buf: &[u8]) -> crate::io::Result<usize> {
+        let mut partial = mem::take(&mut self.partial);
+        let res = partial.decode(buf, |text| self.print(text));
+        self.partial = partial;
+        res?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> crate::io::Result<()> {
+        Ok(Console::flush(self)?)
+    }
+}
+
+impl Drop for Console {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULTS: (Color, Color) = (Color::LightGray, Color::Black);
+
+    /// The text printed and the control sequences run for `text`
+    fn parse(text: &str) -> (String, Vec<(char, Vec<u16>, bool)>) {
+        let mut parser = Parser::default();
+        let (mut printed, mut sequences) = (String::new(), Vec::new());
+        for c in text.chars() {
+            match parser.next(c) {
+                Action::None => (),
+                Action::Print(c) => printed.push(c),
+                Action::Csi(command) => {
+                    sequences.push((command, parser.params.clone(), parser.private))
+                }
+            }
+        }
+        (printed, sequences)
+    }
+
+    /// The colors shown after each group of SGR parameters in turn
+    fn sgr(groups: &[&[u16]]) -> Vec<(Color, Color)> {
+        let mut rendition = Rendition::new(DEFAULTS);
+        groups
+            .iter()
+            .map(|params| {
+                rendition.apply(params, DEFAULTS);
+                rendition.colors()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn control_sequences() {
+        assert_eq!(
+            parse("a\x1B[1;31mb\x1B[m"),
+            (
+                "ab".to_string(),
+                vec![('m', vec![1, 31], false), ('m', vec![0], false)]
+            )
+        );
+        assert_eq!(
+            parse("\x1B[?25l\x1B[2 q"),
+            (
+                String::new(),
+                vec![('l', vec![25], true), ('q', vec![2], false)]
+            )
+        );
+        // Parameters saturate, and those past the limit are dropped
+        assert_eq!(parse("\x1B[99999H").1, [('H', vec![u16::MAX], false)]);
+        let (_, sequences) = parse(&format!("\x1B[{}m", "1;".repeat(20)));
+        assert_eq!(sequences[0].1, [1; MAX_PARAMS]);
+    }
+
+    #[test]
+    fn malformed_sequences_are_abandoned() {
+        // Escape sequences other than CSI are dropped with their first byte
+        assert_eq!(parse("\x1Bcx\x1B(B"), ("xB".to_string(), vec![]));
+        // A control character or non-ASCII byte ends a control sequence
+        assert_eq!(parse("\x1B[1\x07m"), ("m".to_string(), vec![]));
+        assert_eq!(parse("\x1B[3\u{E9}1m"), ("1m".to_string(), vec![]));
+
+        // A reset drops a sequence left open
+        let mut parser = Parser::default();
+        assert_eq!(parser.next(ESC), Action::None);
+        assert_eq!(parser.next('['), Action::None);
+        parser.reset();
+        assert_eq!(parser.next('m'), Action::Print('m'));
+    }
+
+    #[test]
+    fn bold_and_inverse() {
+        assert_eq!(
+            sgr(&[&[1], &[7], &[22], &[27]]),
+            [
+                (Color::White, Color::Black),
+                (Color::Black, Color::White),
+                (Color::Black, Color::LightGray),
+                (Color::LightGray, Color::Black),
+            ]
+        );
+        assert_eq!(sgr(&[&[1, 7, 31, 44], &[0]])[1], DEFAULTS);
+    }
+
+    #[test]
+    fn basic_colors() {
+        assert_eq!(
+            sgr(&[&[31], &[44], &[39], &[91, 104], &[49], &[37, 40]]),
+            [
+                (Color::Red, Color::Black),
+                (Color::Red, Color::Blue),
+                (Color::LightGray, Color::Blue),
+                (Color::LightRed, Color::LightBlue),
+                (Color::LightRed, Color::Black),
+                (Color::LightGray, Color::Black),
+            ]
+        );
+        // Bold brightens the foreground, which inverse moves to the background
+        assert_eq!(sgr(&[&[1, 34, 7]])[0], (Color::Black, Color::LightBlue));
+    }
+
+    #[test]
+    fn extended_colors() {
+        assert_eq!(
+            sgr(&[&[38, 5, 1], &[48, 5, 10], &[38, 5, 196], &[38, 5, 244]]),
+            [
+                (Color::Red, Color::Black),
+                (Color::Red, Color::LightGreen),
+                (Color::LightRed, Color::LightGreen),
+                (Color::DarkGray, Color::LightGreen),
+            ]
+        );
+        assert_eq!(
+            sgr(&[
+                &[38, 2, 255, 128, 0],
+                &[48, 2, 0, 0, 128],
+                &[38, 2, 999, 0, 0]
+            ]),
+            [
+                (Color::Yellow, Color::Black),
+                (Color::Yellow, Color::Blue),
+                (Color::LightRed, Color::Blue),
+            ]
+        );
+        // Parameters after an extended color still apply
+        assert_eq!(sgr(&[&[38, 5, 1, 1]])[0], (Color::LightRed, Color::Black));
+    }
+
+    #[test]
+    fn cut_short_extended_colors_are_ignored() {
+        for params in [&[38][..], &[38, 5], &[38, 2, 255, 0], &[48, 2], &[48, 3]] {
+            assert_eq!(sgr(&[params]), [DEFAULTS], "{params:?}");
+        }
+    }
+
+    #[test]
+    fn rgb_colors() {
+        assert_eq!(Color::from_rgb(0x3F, 0x3F, 0x3F), Color::Black);
+        assert_eq!(Color::from_rgb(0x80, 0x80, 0x80), Color::DarkGray);
+        assert_eq!(Color::from_rgb(0xC0, 0xC0, 0xC0), Color::LightGray);
+        assert_eq!(Color::from_rgb(0xFF, 0xFF, 0xFF), Color::White);
+        assert_eq!(Color::from_rgb(0x80, 0, 0), Color::Red);
+        assert_eq!(Color::from_rgb(0xFF, 0x20, 0x10), Color::LightRed);
+        assert_eq!(Color::from_rgb(0, 0x80, 0x80), Color::Cyan);
+        assert_eq!(Color::from_rgb(0xFF, 0xFF, 0), Color::Yellow);
+        assert_eq!(Color::from_rgb(0xA0, 0xA0, 0), Color::Brown);
+    }
+
+    #[test]
+    fn palette_colors() {
+        for index in 0..8 {
+            assert_eq!(Color::from_ansi_256(index), Color::ANSI[usize::from(index)]);
+            assert_eq!(
+                Color::from_ansi_256(index + 8),
+                Color::ANSI[usize::from(index)].bright()
+            );
+        }
+        // The color cube and gray ramp
+        assert_eq!(Color::from_ansi_256(16), Color::Black);
+        assert_eq!(Color::from_ansi_256(21), Color::LightBlue);
+        assert_eq!(Color::from_ansi_256(28), Color::Green);
+        assert_eq!(Color::from_ansi_256(231), Color::White);
+        assert_eq!(Color::from_ansi_256(232), Color::Black);
+        assert_eq!(Color::from_ansi_256(255), Color::White);
+        // Out of range entries are the last gray
+        assert_eq!(Color::from_ansi_256(1000), Color::White);
+    }
+}
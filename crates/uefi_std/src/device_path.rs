@@ -0,0 +1,112 @@
+use core::slice;
+use uefi::device::{DevicePath as UefiDevicePath, DevicePathEndType, DevicePathType};
+
+use crate::bytes::le16;
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+/// A single node of a device path
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DevicePathNode<'a> {
+    pub kind: u8,
+    pub sub_type: u8,
+    /// The node data following the four byte header
+    pub data: &'a [u8],
+}
+
+impl<'a> DevicePathNode<'a> {
+    pub fn is_end(&self) -> bool {
+        self.kind == DevicePathType::End as u8
+    }
+
+    /// Returns true for a hard drive or CD-ROM media node, which follows the
+    /// path of the whole disk in a partition's device path
+    pub fn is_partition(&self) -> bool {
+        self.kind == DevicePathType::Media as u8 && matches!(self.sub_type, 0x01 | 0x02)
+    }
+}
+
+/// Iterator over the nodes of a device path, up to the end node
+pub struct DevicePathNodes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for DevicePathNodes<'a> {
+    type Item = DevicePathNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 4 {
+            return None;
+        }
+
+        let len = le16(self.data, 2) as usize;
+        if len < 4 || len > self.data.len() {
+            return None;
+        }
+
+        let node = DevicePathNode {
+            kind: self.data[0],
+            sub_type: self.data[1],
+            data: &self.data[4..len],
+        };
+        self.data = &self.data[len..];
+        Some(node)
+    }
+}
+
+pub struct DevicePath(pub &'static mut UefiDevicePath);
+
+impl Protocol<UefiDevicePath> for DevicePath {
+    fn guid() -> Guid {
+        UefiDevicePath::GUID
+    }
+
+    fn new(inner: &'static mut UefiDevicePath) -> Self {
+        DevicePath(inner)
+    }
+}
+
+impl DevicePath {
+    /// The bytes of every node before the end of the entire path
+    pub fn as_bytes(&self) -> &[u8] {
+        let start = &*self.0 as *const UefiDevicePath as *const u8;
+        let mut len = 0;
+        loop {
+            let header = unsafe { slice::from_raw_parts(start.add(len), 4) };
+            let node_len = le16(header, 2) as usize;
+            if (header[0] == DevicePathType::End as u8
+                && header[1] == DevicePathEndType::Entire as u8)
+                || node_len < 4
+            {
+                break;
+            }
+            len += node_len;
+        }
+        unsafe { slice::from_raw_parts(start, len) }
+    }
+
+    pub fn nodes(&self) -> DevicePathNodes {
+        DevicePathNodes {
+            data: self.as_bytes(),
+        }
+    }
+
+    /// Returns true if every node of `prefix` begins this path
+    pub fn starts_with(&self, prefix: &DevicePath) -> bool {
+        self.as_bytes().starts_with(prefix.as_bytes())
+    }
+
+    /// The bytes of the nodes before the first partition node, which is the
+    /// path of the disk holding the partition
+    pub fn disk_prefix(&self) -> Option<&[u8]> {
+        let bytes = self.as_bytes();
+        let mut len = 0;
+        for node in self.nodes() {
+            if node.is_partition() {
+                return Some(&bytes[..len]);
+            }
+            len += node.data.len() + 4;
+        }
+        None
+    }
+}
@@ -0,0 +1,314 @@
+use core::cmp;
+use uefi::disk_io::{DiskIo, DiskIo2, DiskIo2Token};
+
+use crate::block_io::{AlignedBuffer, BlockDevice, Callback, IoToken, MediaInfo, Pending};
+use crate::io::{self, Read, Seek, SeekFrom, Write};
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+struct DiskIoProtocol(&'static mut DiskIo);
+
+impl Protocol<DiskIo> for DiskIoProtocol {
+    fn guid() -> Guid {
+        DiskIo::GUID
+    }
+
+    fn new(inner: &'static mut DiskIo) -> Self {
+        DiskIoProtocol(inner)
+    }
+}
+
+struct DiskIo2Protocol(&'static mut DiskIo2);
+
+impl Protocol<DiskIo2> for DiskIo2Protocol {
+    fn guid() -> Guid {
+        DiskIo2::GUID
+    }
+
+    fn new(inner: &'static mut DiskIo2) -> Self {
+        DiskIo2Protocol(inner)
+    }
+}
+
+/// Disk I/O identifies media by the block device on the same handle
+fn open_media(handle: Handle) -> Result<BlockDevice> {
+    let block = BlockDevice::handle_protocol(handle)?;
+    block.check_media()?;
+    Ok(block)
+}
+
+/// A disk readable and writable at any byte offset
+pub struct Disk {
+    inner: &'static mut DiskIo,
+    block: BlockDevice,
+    position: u64,
+}
+
+impl Disk {
+    /// Open the Disk I/O and Block I/O protocols on `handle`
+    pub fn handle_protocol(handle: Handle) -> Result<Self> {
+        let DiskIoProtocol(inner) = DiskIoProtocol::handle_protocol(handle)?;
+        Ok(Disk {
+            inner,
+            block: open_media(handle)?,
+            position: 0,
+        })
+    }
+
+    /// Open every disk in the system
+    pub fn all() -> Vec<Self> {
+        let mut disks = Vec::new();
+        for handle in DiskIoProtocol::locate_handle().unwrap_or_default() {
+            if let Ok(disk) = Self::handle_protocol(handle) {
+                disks.push(disk);
+            }
+        }
+        disks
+    }
+
+    pub fn inner(&self) -> &DiskIo {
+        self.inner
+    }
+
+    /// The block device for the same media
+    pub fn block_device(&mut self) -> &mut BlockDevice {
+        &mut self.block
+    }
+
+    pub fn media(&self) -> &MediaInfo {
+        self.block.media()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.block.size()
+    }
+
+    /// Accept the current media after a `MEDIA_CHANGED` error, moving back to
+    /// the start of the disk
+    pub fn refresh(&mut self) -> &MediaInfo {
+        self.position = 0;
+        self.block.refresh()
+    }
+
+    /// Fill `buf` with the bytes at `offset`
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        self.block.check_media()?;
+        (self.inner.ReadDisk)(
+            self.inner,
+            self.block.media().media_id,
+            offset,
+            buf.len(),
+            buf.as_mut_ptr(),
+        )
+        .into()
+    }
+
+    /// Write `buf` at `offset`
+    pub fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<()> {
+        self.block.check_media()?;
+        if self.block.media().read_only {
+            return Err(Status::WRITE_PROTECTED);
+        }
+        (self.inner.WriteDisk)(
+            self.inner,
+            self.block.media().media_id,
+            offset,
+            buf.len(),
+            buf.as_ptr(),
+        )
+        .into()
+    }
+
+    /// Write any data cached by the device to the media
+    pub fn flush(&mut self) -> Result<()> {
+        self.block.flush()
+    }
+}
+
+impl Read for Disk {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size().saturating_sub(self.position);
+        let count = cmp::min(buf.len() as u64, remaining) as usize;
+        self.read_at(self.position, &mut buf[..count])?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Write for Disk {
+    /// Writes stop at the end of the media
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let remaining = self.size().saturating_sub(self.position);
+        let count = cmp::min(buf.len() as u64, remaining) as usize;
+        self.write_at(self.position, &buf[..count])?;
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(Disk::flush(self)?)
+    }
+}
+
+impl Seek for Disk {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.size(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        self.position = base
+            .checked_add_signed(offset)
+            .ok_or(io::Error::INVALID_INPUT)?;
+        Ok(self.position)
+    }
+}
+
+impl IoToken for DiskIo2Token {
+    fn new(event: Event) -> Self {
+        DiskIo2Token {
+            Event: event,
+            TransactionStatus: Status::SUCCESS,
+        }
+    }
+
+    fn status(&self) -> Status {
+        self.TransactionStatus
+    }
+}
+
+/// A disk supporting asynchronous requests at any byte offset
+pub struct Disk2 {
+    inner: &'static mut DiskIo2,
+    block: BlockDevice,
+}
+
+impl Disk2 {
+    /// Open the Disk I/O 2 and Block I/O protocols on `handle`
+    pub fn handle_protocol(handle: Handle) -> Result<Self> {
+        let DiskIo2Protocol(inner) = DiskIo2Protocol::handle_protocol(handle)?;
+        Ok(Disk2 {
+            inner,
+            block: open_media(handle)?,
+        })
+    }
+
+    /// Open every disk in the system that supports asynchronous requests
+    pub fn all() -> Vec<Self> {
+        let mut disks = Vec::new();
+        for handle in DiskIo2Protocol::locate_handle().unwrap_or_default() {
+            if let Ok(disk) = Self::handle_protocol(handle) {
+                disks.push(disk);
+            }
+        }
+        disks
+    }
+
+    pub fn inner(&self) -> &DiskIo2 {
+        self.inner
+    }
+
+    /// The block device for the same media
+    pub fn block_device(&mut self) -> &mut BlockDevice {
+        &mut self.block
+    }
+
+    pub fn media(&self) -> &MediaInfo {
+        self.block.media()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.block.size()
+    }
+
+    /// Accept the current media after a `MEDIA_CHANGED` error
+    pub fn refresh(&mut self) -> &MediaInfo {
+        self.block.refresh()
+    }
+
+    /// Abort all requests in flight, which then complete with `ABORTED`
+    pub fn cancel(&mut self) -> Result<()> {
+        (self.inner.Cancel)(self.inner).into()
+    }
+
+    fn read(
+        &mut self,
+        offset: u64,
+        buf: AlignedBuffer,
+        callback: Option<Callback>,
+    ) -> Result<Pending<DiskIo2Token>> {
+        self.block.check_media()?;
+        let inner = &*self.inner;
+        let media_id = self.block.media().media_id;
+        Pending::submit(buf, callback, |token, buf| {
+            (inner.ReadDiskEx)(inner, media_id, offset, token, buf.len(), buf.as_mut_ptr())
+        })
+    }
+
+    fn write(
+        &mut self,
+        offset: u64,
+        buf: AlignedBuffer,
+        callback: Option<Callback>,
+    ) -> Result<Pending<DiskIo2Token>> {
+        self.block.check_media()?;
+        if self.block.media().read_only {
+            return Err(Status::WRITE_PROTECTED);
+        }
+        let inner = &*self.inner;
+        let media_id = self.block.media().media_id;
+        Pending::submit(buf, callback, |token, buf| {
+            (inner.WriteDiskEx)(inner, media_id, offset, token, buf.len(), buf.as_ptr())
+        })
+    }
+
+    /// Start reading the bytes at `offset` into `buf`, to be polled for
+    /// completion
+    pub fn read_async(&mut self, offset: u64, buf: AlignedBuffer) -> Result<Pending<DiskIo2Token>> {
+        self.read(offset, buf, None)
+    }
+
+    /// Start reading the bytes at `offset` into `buf`, calling `callback` with
+    /// the result when done
+    pub fn read_with<F: FnOnce(Status) + 'static>(
+        &mut self,
+        offset: u64,
+        buf: AlignedBuffer,
+        callback: F,
+    ) -> Result<Pending<DiskIo2Token>> {
+        self.read(offset, buf, Some(Box::new(callback)))
+    }
+
+    /// Start writing `buf` at `offset`, to be polled for completion
+    pub fn write_async(
+        &mut self,
+        offset: u64,
+        buf: AlignedBuffer,
+    ) -> Result<Pending<DiskIo2Token>> {
+        self.write(offset, buf, None)
+    }
+
+    /// Start writing `buf` at `offset`, calling `callback` with the result
+    /// when done
+    pub fn write_with<F: FnOnce(Status) + 'static>(
+        &mut self,
+        offset: u64,
+        buf: AlignedBuffer,
+        callback: F,
+    ) -> Result<Pending<DiskIo2Token>> {
+        self.write(offset, buf, Some(Box::new(callback)))
+    }
+
+    /// Start flushing data cached by the device to the media
+    pub fn flush_async(&mut self) -> Result<Pending<DiskIo2Token>> {
+        self.block.check_media()?;
+        let inner = &*self.inner;
+        Pending::submit(AlignedBuffer::new(0, 1)?, None, |token, _| {
+            (inner.FlushDiskEx)(inner, token)
+        })
+    }
+}
@@ -0,0 +1,661 @@
+//! Display identification data (EDID 1.4 with CEA-861 extensions)
+
+use core::slice;
+use uefi::edid::{EdidActive as UefiEdidActive, EdidDiscovered as UefiEdidDiscovered};
+
+use crate::bytes::{be16, le16, le32};
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+const BLOCK_SIZE: usize = 128;
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const CEA_EXTENSION: u8 = 0x02;
+
+// Display descriptor tags
+const DESCRIPTOR_SERIAL: u8 = 0xFF;
+const DESCRIPTOR_TEXT: u8 = 0xFE;
+const DESCRIPTOR_RANGE_LIMITS: u8 = 0xFD;
+const DESCRIPTOR_NAME: u8 = 0xFC;
+
+// CEA data block tags
+const CEA_AUDIO: u8 = 1;
+const CEA_VIDEO: u8 = 2;
+const CEA_VENDOR: u8 = 3;
+
+/// The IEEE OUI of HDMI Licensing, which marks an HDMI vendor block
+const HDMI_OUI: u32 = 0x000C03;
+
+/// The modes of the established timings bitmap, from the high bit of the first
+/// byte
+const ESTABLISHED: [(u32, u32, u32); 17] = [
+    (720, 400, 70),
+    (720, 400, 88),
+    (640, 480, 60),
+    (640, 480, 67),
+    (640, 480, 72),
+    (640, 480, 75),
+    (800, 600, 56),
+    (800, 600, 60),
+    (800, 600, 72),
+    (800, 600, 75),
+    (832, 624, 75),
+    (1024, 768, 87),
+    (1024, 768, 60),
+    (1024, 768, 70),
+    (1024, 768, 75),
+    (1280, 1024, 75),
+    (1152, 870, 75),
+];
+
+fn checksum_ok(block: &[u8]) -> bool {
+    block.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+/// The text of a display descriptor, which ends at a newline
+fn descriptor_text(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .unwrap_or(data.len());
+    data[..end]
+        .iter()
+        .map(|&byte| if byte.is_ascii() { byte as char } else { '?' })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// A mode given by resolution and refresh rate
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Timing {
+    pub width: u32,
+    pub height: u32,
+    /// The refresh rate in Hz
+    pub refresh: u32,
+}
+
+impl Timing {
+    /// Decode a standard timing, which is `None` if unused
+    fn standard(data: &[u8], version: (u8, u8)) -> Option<Self> {
+        if data[0] == 0x00 || (data[0] == 0x01 && data[1] == 0x01) {
+            return None;
+        }
+        let width = (u32::from(data[0]) + 31) * 8;
+        let height = match data[1] >> 6 {
+            // Before EDID 1.3 this meant 1:1
+            0 if version < (1, 3) => width,
+            0 => width * 10 / 16,
+            1 => width * 3 / 4,
+            2 => width * 4 / 5,
+            _ => width * 9 / 16,
+        };
+        Some(Timing {
+            width,
+            height,
+            refresh: u32::from(data[1] & 0x3F) + 60,
+        })
+    }
+}
+
+/// A detailed timing descriptor
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DetailedTiming {
+    /// The pixel clock in kHz
+    pub pixel_clock: u32,
+    pub h_active: u32,
+    pub h_blank: u32,
+    pub h_front_porch: u32,
+    pub h_sync: u32,
+    pub v_active: u32,
+    pub v_blank: u32,
+    pub v_front_porch: u32,
+    pub v_sync: u32,
+    /// The size of the image in millimetres
+    pub width_mm: u32,
+    pub height_mm: u32,
+    pub interlaced: bool,
+}
+
+impl DetailedTiming {
+    /// Decode an 18 byte descriptor, which is `None` if it is a display
+    /// descriptor instead
+    fn parse(data: &[u8]) -> Option<Self> {
+        let clock = le16(data, 0);
+        if clock == 0 {
+            return None;
+        }
+        let low_high = |low: u8, high: u8| u32::from(low) | u32::from(high) << 8;
+
+        Some(DetailedTiming {
+            pixel_clock: u32::from(clock) * 10,
+            h_active: low_high(data[2], data[4] >> 4),
+            h_blank: low_high(data[3], data[4] & 0xF),
+            v_active: low_high(data[5], data[7] >> 4),
+            v_blank: low_high(data[6], data[7] & 0xF),
+            h_front_porch: low_high(data[8], data[11] >> 6),
+            h_sync: low_high(data[9], (data[11] >> 4) & 0x3),
+            v_front_porch: u32::from(data[10] >> 4) | u32::from((data[11] >> 2) & 0x3) << 4,
+            v_sync: u32::from(data[10] & 0xF) | u32::from(data[11] & 0x3) << 4,
+            width_mm: low_high(data[12], data[14] >> 4),
+            height_mm: low_high(data[13], data[14] & 0xF),
+            interlaced: data[17] & 0x80 != 0,
+        })
+    }
+
+    pub fn resolution(&self) -> (u32, u32) {
+        (self.h_active, self.v_active)
+    }
+
+    /// The refresh rate in mHz, so that 59.94 Hz is 59940
+    pub fn refresh_millihertz(&self) -> u32 {
+        let total =
+            u64::from(self.h_active + self.h_blank) * u64::from(self.v_active + self.v_blank);
+        if total == 0 {
+            return 0;
+        }
+        (u64::from(self.pixel_clock) * 1_000_000 / total) as u32
+    }
+
+    /// The refresh rate rounded to the nearest Hz
+    pub fn refresh(&self) -> u32 {
+        (self.refresh_millihertz() + 500) / 1000
+    }
+}
+
+/// The display range limits descriptor
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RangeLimits {
+    /// The vertical rate range in Hz
+    pub min_v_rate: u32,
+    pub max_v_rate: u32,
+    /// The horizontal rate range in kHz
+    pub min_h_rate: u32,
+    pub max_h_rate: u32,
+    /// The maximum pixel clock in MHz, if given
+    pub max_pixel_clock: Option<u32>,
+}
+
+impl RangeLimits {
+    fn parse(data: &[u8]) -> Self {
+        // EDID 1.4 adds 255 to rates flagged in byte 4
+        let flags = data[4];
+        let offset = |bit: u8| if flags & bit != 0 { 255 } else { 0 };
+        RangeLimits {
+            min_v_rate: u32::from(data[5]) + offset(0x01),
+            max_v_rate: u32::from(data[6]) + offset(0x02),
+            min_h_rate: u32::from(data[7]) + offset(0x04),
+            max_h_rate: u32::from(data[8]) + offset(0x08),
+            max_pixel_clock: Some(u32::from(data[9]) * 10).filter(|&clock| clock != 0),
+        }
+    }
+}
+
+/// A short video descriptor from a CEA video data block
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ShortVideo {
+    /// The CEA-861 video identification code
+    pub vic: u8,
+    /// The display lists this as a native mode
+    pub native: bool,
+}
+
+/// A CEA-861 extension block, as sent by TVs and HDMI displays
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CeaExtension {
+    pub revision: u8,
+    pub underscan: bool,
+    pub basic_audio: bool,
+    pub ycbcr444: bool,
+    pub ycbcr422: bool,
+    /// The number of native formats among the detailed timings
+    pub native_formats: u8,
+    pub video: Vec<ShortVideo>,
+    /// The display has an audio data block
+    pub audio: bool,
+    /// The display has an HDMI vendor block
+    pub hdmi: bool,
+    pub detailed: Vec<DetailedTiming>,
+}
+
+impl CeaExtension {
+    fn parse(block: &[u8]) -> Result<Self> {
+        let revision = block[1];
+        let dtd_offset = usize::from(block[2]);
+        if dtd_offset > BLOCK_SIZE - 1 || (dtd_offset != 0 && dtd_offset < 4) {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let mut cea = CeaExtension {
+            revision,
+            ..Default::default()
+        };
+        if revision >= 2 {
+            let flags = block[3];
+            cea.underscan = flags & 0x80 != 0;
+            cea.basic_audio = flags & 0x40 != 0;
+            cea.ycbcr444 = flags & 0x20 != 0;
+            cea.ycbcr422 = flags & 0x10 != 0;
+            cea.native_formats = flags & 0xF;
+        }
+
+        // Data blocks exist from revision 3, between the header and the
+        // detailed timings
+        let blocks_end = if dtd_offset == 0 { 4 } else { dtd_offset };
+        let mut offset = 4;
+        while revision >= 3 && offset < blocks_end {
+            let tag = block[offset] >> 5;
+            let len = usize::from(block[offset] & 0x1F);
+            let payload = block
+                .get(offset + 1..offset + 1 + len)
+                .filter(|_| offset + 1 + len <= blocks_end)
+                .ok_or(Status::INVALID_PARAMETER)?;
+            match tag {
+                CEA_AUDIO => cea.audio = true,
+                CEA_VIDEO => {
+                    cea.video.extend(payload.iter().map(|&svd| {
+                        // Codes above 64 use all bits, and 128 is reserved
+                        if (129..=192).contains(&svd) {
+                            ShortVideo {
+                                vic: svd & 0x7F,
+                                native: true,
+                            }
+                        } else {
+                            ShortVideo {
+                                vic: svd,
+                                native: false,
+                            }
+                        }
+                    }));
+                }
+                CEA_VENDOR if len >= 3 => {
+                    let oui = u32::from_le_bytes([payload[0], payload[1], payload[2], 0]);
+                    cea.hdmi |= oui == HDMI_OUI;
+                }
+                _ => (),
+            }
+            offset += 1 + len;
+        }
+
+        if dtd_offset != 0 {
+            cea.detailed = block[dtd_offset..BLOCK_SIZE - 1]
+                .chunks_exact(18)
+                .map_while(DetailedTiming::parse)
+                .collect();
+        }
+        Ok(cea)
+    }
+}
+
+/// Parsed EDID data
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Edid {
+    /// The three letter PNP ID of the manufacturer
+    pub manufacturer: String,
+    pub product: u16,
+    pub serial: u32,
+    /// The week of manufacture, or 0 if unknown, or 255 if `year` is a model
+    /// year
+    pub week: u8,
+    pub year: u16,
+    /// The EDID version and revision
+    pub version: (u8, u8),
+    pub digital: bool,
+    /// The size of the screen in centimetres, or 0 if unknown
+    pub width_cm: u8,
+    pub height_cm: u8,
+    pub established: Vec<Timing>,
+    pub standard: Vec<Timing>,
+    /// Detailed timings, where the first is the preferred mode
+    pub detailed: Vec<DetailedTiming>,
+    pub name: Option<String>,
+    pub serial_text: Option<String>,
+    pub text: Vec<String>,
+    pub range_limits: Option<RangeLimits>,
+    pub cea: Vec<CeaExtension>,
+}
+
+impl Edid {
+    /// Parse a base block and any extension blocks. Extensions that are
+    /// missing, malformed or fail their checksum are ignored.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let base = data.get(..BLOCK_SIZE).ok_or(Status::INVALID_PARAMETER)?;
+        if base[..8] != HEADER {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        if !checksum_ok(base) {
+            return Err(Status::CRC_ERROR);
+        }
+
+        let id = be16(base, 8);
+        let letter = |shift: u16| (b'A' - 1 + ((id >> shift) & 0x1F) as u8) as char;
+        let version = (base[18], base[19]);
+
+        let mut edid = Edid {
+            manufacturer: [letter(10), letter(5), letter(0)].iter().collect(),
+            product: le16(base, 10),
+            serial: le32(base, 12),
+            week: base[16],
+            year: 1990 + u16::from(base[17]),
+            version,
+            digital: base[20] & 0x80 != 0,
+            width_cm: base[21],
+            height_cm: base[22],
+            established: Vec::new(),
+            standard: base[38..54]
+                .chunks_exact(2)
+                .filter_map(|data| Timing::standard(data, version))
+                .collect(),
+            detailed: Vec::new(),
+            name: None,
+            serial_text: None,
+            text: Vec::new(),
+            range_limits: None,
+            cea: Vec::new(),
+        };
+
+        let bits = u32::from_be_bytes([base[35], base[36], base[37], 0]);
+        for (index, &(width, height, refresh)) in ESTABLISHED.iter().enumerate() {
+            if bits & (0x8000_0000 >> index) != 0 {
+                edid.established.push(Timing {
+                    width,
+                    height,
+                    refresh,
+                });
+            }
+        }
+
+        for descriptor in base[54..126].chunks_exact(18) {
+            if let Some(timing) = DetailedTiming::parse(descriptor) {
+                edid.detailed.push(timing);
+                continue;
+            }
+            match descriptor[3] {
+                DESCRIPTOR_NAME => edid.name = Some(descriptor_text(&descriptor[5..])),
+                DESCRIPTOR_SERIAL => edid.serial_text = Some(descriptor_text(&descriptor[5..])),
+                DESCRIPTOR_TEXT => edid.text.push(descriptor_text(&descriptor[5..])),
+                DESCRIPTOR_RANGE_LIMITS => edid.range_limits = Some(RangeLimits::parse(descriptor)),
+                _ => (),
+            }
+        }
+
+        let extensions = usize::from(base[126]);
+        for block in data[BLOCK_SIZE..].chunks_exact(BLOCK_SIZE).take(extensions) {
+            if block[0] != CEA_EXTENSION || !checksum_ok(block) {
+                continue;
+            }
+            if let Ok(cea) = CeaExtension::parse(block) {
+                edid.cea.push(cea);
+            }
+        }
+
+        Ok(edid)
+    }
+
+    /// The preferred mode, which EDID 1.4 requires to be the first detailed
+    /// timing
+    pub fn preferred_timing(&self) -> Option<&DetailedTiming> {
+        self.detailed.first()
+    }
+
+    /// The resolution of the preferred mode, which is the native resolution
+    /// of a panel
+    pub fn native_resolution(&self) -> Option<(u32, u32)> {
+        self.preferred_timing().map(DetailedTiming::resolution)
+    }
+
+    /// Every detailed timing, including those from extension blocks
+    pub fn detailed_timings(&self) -> impl Iterator<Item = &DetailedTiming> {
+        self.detailed
+            .iter()
+            .chain(self.cea.iter().flat_map(|cea| cea.detailed.iter()))
+    }
+
+    /// Returns true if the display accepts HDMI signals
+    pub fn is_hdmi(&self) -> bool {
+        self.cea.iter().any(|cea| cea.hdmi)
+    }
+}
+
+/// Copy EDID data provided by firmware
+fn edid_bytes(size: u32, data: *const u8) -> Result<Vec<u8>> {
+    if data.is_null() || size == 0 {
+        return Err(Status::NOT_FOUND);
+    }
+    Ok(unsafe { slice::from_raw_parts(data, size as usize) }.to_vec())
+}
+
+/// The EDID read from the display by the GOP driver
+pub struct EdidDiscovered(pub &'static mut UefiEdidDiscovered);
+
+impl Protocol<UefiEdidDiscovered> for EdidDiscovered {
+    fn guid() -> Guid {
+        UefiEdidDiscovered::GUID
+    }
+
+    fn new(inner: &'static mut UefiEdidDiscovered) -> Self {
+        EdidDiscovered(inner)
+    }
+}
+
+impl EdidDiscovered {
+    pub fn data(&self) -> Result<Vec<u8>> {
+        edid_bytes(self.0.SizeOfEdid, self.0.Edid)
+    }
+
+    pub fn parse(&self) -> Result<Edid> {
+        Edid::parse(&self.data()?)
+    }
+}
+
+/// The EDID the GOP driver uses, which accounts for platform overrides
+pub struct EdidActive(pub &'static mut UefiEdidActive);
+
+impl Protocol<UefiEdidActive> for EdidActive {
+    fn guid() -> Guid {
+        UefiEdidActive::GUID
+    }
+
+    fn new(inner: &'static mut UefiEdidActive) -> Self {
+        EdidActive(inner)
+    }
+}
+
+impl EdidActive {
+    pub fn data(&self) -> Result<Vec<u8>> {
+        edid_bytes(self.0.SizeOfEdid, self.0.Edid)
+    }
+
+    pub fn parse(&self) -> Result<Edid> {
+        Edid::parse(&self.data()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: &[u8] = include_bytes!("../testdata/edid/monitor.bin");
+    const TV: &[u8] = include_bytes!("../testdata/edid/tv.bin");
+
+    const MODE_1080P: DetailedTiming = DetailedTiming {
+        pixel_clock: 148_500,
+        h_active: 1920,
+        h_blank: 280,
+        h_front_porch: 88,
+        h_sync: 44,
+        v_active: 1080,
+        v_blank: 45,
+        v_front_porch: 4,
+        v_sync: 5,
+        width_mm: 531,
+        height_mm: 299,
+        interlaced: false,
+    };
+
+    /// Change a byte of the block at `offset`, keeping its checksum valid
+    fn edit(data: &mut [u8], offset: usize, value: u8) {
+        let block = offset / BLOCK_SIZE * BLOCK_SIZE;
+        let old = data[offset];
+        data[offset] = value;
+        let sum = &mut data[block + BLOCK_SIZE - 1];
+        *sum = sum.wrapping_add(old).wrapping_sub(value);
+    }
+
+    fn timing(width: u32, height: u32, refresh: u32) -> Timing {
+        Timing {
+            width,
+            height,
+            refresh,
+        }
+    }
+
+    #[test]
+    fn monitor() {
+        let edid = Edid::parse(MONITOR).unwrap();
+        assert_eq!(edid.manufacturer, "RDX");
+        assert_eq!((edid.product, edid.serial), (0x1234, 0x01020304));
+        assert_eq!((edid.week, edid.year), (12, 2020));
+        assert_eq!(edid.version, (1, 4));
+        assert!(edid.digital);
+        assert_eq!((edid.width_cm, edid.height_cm), (53, 30));
+
+        assert_eq!(
+            edid.established,
+            [
+                timing(640, 480, 60),
+                timing(800, 600, 60),
+                timing(1024, 768, 60)
+            ]
+        );
+        assert_eq!(
+            edid.standard,
+            [
+                timing(1920, 1080, 60),
+                timing(1280, 1024, 60),
+                timing(1280, 800, 60),
+                timing(1680, 1050, 60),
+            ]
+        );
+
+        assert_eq!(edid.preferred_timing(), Some(&MODE_1080P));
+        assert_eq!(edid.native_resolution(), Some((1920, 1080)));
+        assert_eq!(MODE_1080P.refresh_millihertz(), 60_000);
+        assert_eq!(MODE_1080P.refresh(), 60);
+
+        assert_eq!(edid.name.as_deref(), Some("Redox Test"));
+        assert_eq!(edid.serial_text.as_deref(), Some("RDX0001"));
+        assert!(edid.text.is_empty());
+        assert_eq!(
+            edid.range_limits,
+            Some(RangeLimits {
+                min_v_rate: 56,
+                max_v_rate: 75,
+                min_h_rate: 30,
+                max_h_rate: 83,
+                max_pixel_clock: Some(150),
+            })
+        );
+        assert!(edid.cea.is_empty());
+        assert!(!edid.is_hdmi());
+    }
+
+    #[test]
+    fn cea_extension() {
+        let edid = Edid::parse(TV).unwrap();
+        assert_eq!(edid.name.as_deref(), Some("Redox TV"));
+        assert_eq!(edid.cea.len(), 1);
+        assert!(edid.is_hdmi());
+
+        let cea = &edid.cea[0];
+        assert_eq!(cea.revision, 3);
+        assert!(cea.underscan && cea.basic_audio && cea.ycbcr444 && cea.ycbcr422);
+        assert_eq!(cea.native_formats, 1);
+        assert!(cea.audio);
+        let vics = cea
+            .video
+            .iter()
+            .map(|svd| (svd.vic, svd.native))
+            .collect::<Vec<_>>();
+        assert_eq!(vics, [(16, true), (4, false), (31, false), (3, false)]);
+
+        assert_eq!(cea.detailed.len(), 1);
+        let mode_720p = cea.detailed[0];
+        assert_eq!(mode_720p.resolution(), (1280, 720));
+        assert_eq!((mode_720p.h_blank, mode_720p.v_blank), (370, 30));
+        assert_eq!(mode_720p.refresh_millihertz(), 60_000);
+
+        // The base block's preferred mode comes first
+        let modes = edid
+            .detailed_timings()
+            .map(DetailedTiming::resolution)
+            .collect::<Vec<_>>();
+        assert_eq!(modes, [(1920, 1080), (1280, 720)]);
+    }
+
+    #[test]
+    fn bad_cea_extension() {
+        // A detailed timing offset inside the header
+        let mut data = TV.to_vec();
+        edit(&mut data, BLOCK_SIZE + 2, 2);
+        let edid = Edid::parse(&data).unwrap();
+        assert!(edid.cea.is_empty());
+        assert_eq!(edid.name.as_deref(), Some("Redox TV"));
+
+        // A data block running into the detailed timings
+        let mut data = TV.to_vec();
+        edit(&mut data, BLOCK_SIZE + 2, 10);
+        assert!(Edid::parse(&data).unwrap().cea.is_empty());
+
+        // A bad checksum, or a missing block
+        let mut data = TV.to_vec();
+        data[BLOCK_SIZE + 4] ^= 1;
+        assert!(Edid::parse(&data).unwrap().cea.is_empty());
+        let edid = Edid::parse(&TV[..BLOCK_SIZE]).unwrap();
+        assert_eq!(edid.preferred_timing(), Some(&MODE_1080P));
+        assert!(edid.cea.is_empty());
+    }
+
+    /// Dumps taken from real displays, which are checked for what every
+    /// display reports
+    #[test]
+    fn real_dumps() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/edid/real");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("bin") {
+                continue;
+            }
+            let data = std::fs::read(&path).unwrap();
+            let edid = Edid::parse(&data).unwrap();
+            assert!(
+                edid.manufacturer.bytes().all(|b| b.is_ascii_uppercase()),
+                "{path:?}"
+            );
+            let (width, height) = edid.native_resolution().unwrap();
+            assert!(width > 0 && height > 0, "{path:?}");
+
+            // Every CEA-861 extension present is understood
+            let cea_blocks = data[BLOCK_SIZE..]
+                .chunks_exact(BLOCK_SIZE)
+                .filter(|block| block[0] == CEA_EXTENSION)
+                .count();
+            assert_eq!(edid.cea.len(), cea_blocks, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn bad_base_block() {
+        assert_eq!(
+            Edid::parse(&MONITOR[..BLOCK_SIZE - 1]),
+            Err(Status::INVALID_PARAMETER)
+        );
+
+        let mut data = MONITOR.to_vec();
+        data[0] = 0xFF;
+        assert_eq!(Edid::parse(&data), Err(Status::INVALID_PARAMETER));
+
+        let mut data = MONITOR.to_vec();
+        data[20] ^= 0x80;
+        assert_eq!(Edid::parse(&data), Err(Status::CRC_ERROR));
+    }
+}
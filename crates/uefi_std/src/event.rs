@@ -0,0 +1,86 @@
+use uefi::boot::TimerDelay;
+
+use crate::prelude::*;
+use crate::system_table;
+
+/// Notify function for events that have none, which firmware never calls
+extern "efiapi" fn no_notify(_event: Event, _context: usize) {}
+
+/// An event created by this image, closed on drop
+pub struct OwnedEvent(Event);
+
+impl OwnedEvent {
+    /// Create an event of `kind`, a combination of the `EVT_*` flags in
+    /// `uefi::boot`, without a notify function
+    pub fn new(kind: u32) -> Result<Self> {
+        unsafe { Self::with_notify(kind, Tpl::CALLBACK, no_notify, 0) }
+    }
+
+    /// Create an event that calls `notify` with `context` at `tpl`
+    ///
+    /// # Safety
+    ///
+    /// `notify` may be called at any point until the event is closed, and
+    /// must treat `context` accordingly.
+    pub unsafe fn with_notify(
+        kind: u32,
+        tpl: Tpl,
+        notify: extern "efiapi" fn(Event, usize),
+        context: usize,
+    ) -> Result<Self> {
+        let mut event = Event(0);
+        let status =
+            (system_table().BootServices.CreateEvent)(kind, tpl, notify, context, &mut event);
+
+        match status {
+            Status::SUCCESS => Ok(OwnedEvent(event)),
+            _ => Err(status),
+        }
+    }
+
+    pub fn event(&self) -> Event {
+        self.0
+    }
+
+    pub fn signal(&self) -> Result<()> {
+        (system_table().BootServices.SignalEvent)(self.0).into()
+    }
+
+    /// Returns true if the event was signaled, clearing it. This is not
+    /// valid for events with `EVT_NOTIFY_SIGNAL`.
+    pub fn check(&self) -> Result<bool> {
+        match (system_table().BootServices.CheckEvent)(self.0) {
+            Status::SUCCESS => Ok(true),
+            Status::NOT_READY => Ok(false),
+            status => Err(status),
+        }
+    }
+
+    /// Block until the event is signaled
+    pub fn wait(&self) -> Result<()> {
+        wait_for_event(&[self.0]).map(|_| ())
+    }
+
+    /// Arm or cancel a timer event, with `trigger_time` in 100ns units
+    pub fn set_timer(&self, delay: TimerDelay, trigger_time: u64) -> Result<()> {
+        (system_table().BootServices.SetTimer)(self.0, delay, trigger_time).into()
+    }
+}
+
+impl Drop for OwnedEvent {
+    fn drop(&mut self) {
+        let _ = (system_table().BootServices.CloseEvent)(self.0);
+    }
+}
+
+/// Block until one of `events` is signaled, returning its index
+pub fn wait_for_event(events: &[Event]) -> Result<usize> {
+    let mut index = 0;
+    let status =
+        (system_table().BootServices.WaitForEvent)(events.len(), events.as_ptr(), &mut index);
+
+    match status {
+        Status::SUCCESS => Ok(index),
+        _ => Err(status),
+    }
+}
@@ -0,0 +1,264 @@
+use core::char;
+use uefi::time::Time;
+
+use crate::bytes::le16;
+use crate::fs::FileAttributes;
+use crate::prelude::*;
+
+pub(super) const ENTRY_SIZE: usize = 32;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const LAST_LONG_ENTRY: u8 = 0x40;
+const DELETED: u8 = 0xE5;
+/// The byte stored in place of a leading 0xE5 in a short name
+const KANJI_E5: u8 = 0x05;
+
+/// Convert a FAT date and time to a UEFI time, with `tenths` of seconds
+/// counting in 10ms units
+fn fat_time(date: u16, time: u16, tenths: u8) -> Time {
+    let mut result = Time::default();
+    if date == 0 {
+        return result;
+    }
+
+    result.Year = 1980 + (date >> 9);
+    result.Month = ((date >> 5) & 0xF) as u8;
+    result.Day = (date & 0x1F) as u8;
+    result.Hour = (time >> 11) as u8;
+    result.Minute = ((time >> 5) & 0x3F) as u8;
+    result.Second = ((time & 0x1F) * 2) as u8 + tenths / 100;
+    result.Nanosecond = u32::from(tenths % 100) * 10_000_000;
+    result
+}
+
+/// The checksum of a short name stored in each of its long name entries
+fn short_name_checksum(name: &[u8]) -> u8 {
+    name.iter()
+        .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+/// Decode an 8.3 name, applying the lowercase flags set by Windows NT
+fn short_name(name: &[u8], flags: u8) -> String {
+    let decode = |bytes: &[u8], lower: bool| -> String {
+        let end = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        bytes[..end]
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| {
+                let b = if i == 0 && b == KANJI_E5 { DELETED } else { b };
+                let c = char::from(b);
+                if lower {
+                    c.to_ascii_lowercase()
+                } else {
+                    c
+                }
+            })
+            .collect()
+    };
+
+    let mut result = decode(&name[..8], flags & 0x08 != 0);
+    let ext = decode(&name[8..11], flags & 0x10 != 0);
+    if !ext.is_empty() {
+        result.push('.');
+        result.push_str(&ext);
+    }
+    result
+}
+
+/// A file or directory in a FAT directory
+#[derive(Clone, Debug)]
+pub struct FatDirEntry {
+    pub(super) name: String,
+    pub(super) short_name: String,
+    pub(super) attributes: u8,
+    pub(super) first_cluster: u32,
+    pub(super) size: u32,
+    pub(super) created: Time,
+    pub(super) accessed: Time,
+    pub(super) modified: Time,
+}
+
+impl FatDirEntry {
+    /// An entry for the root directory, which has none of its own
+    pub(super) fn root(first_cluster: u32) -> Self {
+        FatDirEntry {
+            name: String::new(),
+            short_name: String::new(),
+            attributes: ATTR_DIRECTORY,
+            first_cluster,
+            size: 0,
+            created: Time::default(),
+            accessed: Time::default(),
+            modified: Time::default(),
+        }
+    }
+
+    /// The long name if there is one, and the short name otherwise
+    pub fn file_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The 8.3 name
+    pub fn short_name(&self) -> &str {
+        &self.short_name
+    }
+
+    pub fn attributes(&self) -> FileAttributes {
+        FileAttributes(u64::from(self.attributes & 0x37))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// Returns true for the `.` and `..` entries
+    pub fn is_dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+
+    pub fn len(&self) -> u64 {
+        u64::from(self.size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// The first cluster of the data, or 0 for none
+    pub fn first_cluster(&self) -> u32 {
+        self.first_cluster
+    }
+
+    pub fn created(&self) -> Time {
+        self.created
+    }
+
+    pub fn accessed(&self) -> Time {
+        self.accessed
+    }
+
+    pub fn modified(&self) -> Time {
+        self.modified
+    }
+
+    /// Returns true if `name` matches the long or short name, ignoring case
+    pub(super) fn matches(&self, name: &str) -> bool {
+        let eq = |a: &str| {
+            a.len() == name.len()
+                && a.chars()
+                    .zip(name.chars())
+                    .all(|(a, b)| a == b || a.to_uppercase().eq(b.to_uppercase()))
+        };
+        eq(&self.name) || eq(&self.short_name)
+    }
+}
+
+/// The entries of a directory and its volume label, if it has one
+pub(super) struct DirContents {
+    pub entries: Vec<FatDirEntry>,
+    pub label: Option<String>,
+}
+
+/// Parse the raw entries of a directory, stopping at the end marker
+pub(super) fn parse_dir(data: &[u8]) -> DirContents {
+    let mut entries = Vec::new();
+    let mut label = None;
+
+    // Pieces of the long name preceding the next short entry, and the
+    // checksum they expect
+    let mut long_name: Vec<u16> = Vec::new();
+    let mut long_checksum = None;
+    let mut long_next = 0;
+
+    for entry in data.chunks_exact(ENTRY_SIZE) {
+        match entry[0] {
+            0 => break,
+            DELETED => {
+                long_checksum = None;
+                continue;
+            }
+            _ => (),
+        }
+
+        let attributes = entry[11];
+        if attributes & 0x3F == ATTR_LONG_NAME {
+            let order = entry[0];
+            let index = order & 0x1F;
+            if order & LAST_LONG_ENTRY != 0 {
+                long_name = vec![0xFFFF; usize::from(index) * 13];
+                long_checksum = Some(entry[13]);
+                long_next = index;
+            } else if long_checksum != Some(entry[13]) || index != long_next {
+                long_checksum = None;
+                continue;
+            }
+
+            if index == 0 {
+                long_checksum = None;
+                continue;
+            }
+
+            // Each long entry holds 13 UTF-16 units in three runs
+            let units = (1..11)
+                .step_by(2)
+                .chain((14..26).step_by(2))
+                .chain((28..32).step_by(2))
+                .map(|offset| le16(entry, offset));
+            let start = usize::from(index - 1) * 13;
+            for (slot, unit) in long_name[start..start + 13].iter_mut().zip(units) {
+                *slot = unit;
+            }
+            long_next = index - 1;
+            continue;
+        }
+
+        let name_bytes = &entry[..11];
+        if attributes & ATTR_VOLUME_ID != 0 {
+            // Labels are 11 characters without an extension dot
+            if attributes & ATTR_DIRECTORY == 0 {
+                let text: String = name_bytes.iter().map(|&b| char::from(b)).collect();
+                if !text.trim_end().is_empty() {
+                    label = Some(text.trim_end().to_string());
+                }
+            }
+            long_checksum = None;
+            continue;
+        }
+
+        let short = short_name(name_bytes, entry[12]);
+        let long = match long_checksum {
+            Some(checksum) if long_next == 0 && checksum == short_name_checksum(name_bytes) => {
+                let end = long_name
+                    .iter()
+                    .position(|&unit| unit == 0 || unit == 0xFFFF)
+                    .unwrap_or(long_name.len());
+                Some(
+                    char::decode_utf16(long_name[..end].iter().copied())
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect::<String>(),
+                )
+            }
+            _ => None,
+        };
+        long_checksum = None;
+
+        entries.push(FatDirEntry {
+            name: long.unwrap_or_else(|| short.clone()),
+            short_name: short,
+            attributes,
+            first_cluster: u32::from(le16(entry, 20)) << 16 | u32::from(le16(entry, 26)),
+            size: u32::from_le_bytes(entry[28..32].try_into().unwrap()),
+            created: fat_time(le16(entry, 16), le16(entry, 14), entry[13]),
+            accessed: fat_time(le16(entry, 18), 0, 0),
+            modified: fat_time(le16(entry, 24), le16(entry, 22), 0),
+        });
+    }
+
+    DirContents { entries, label }
+}
@@ -0,0 +1,621 @@
+//! A read-only FAT12, FAT16 and FAT32 driver with long filename support
+//!
+//! [`FatFs`] works over any [`Read`] + [`Seek`] source positioned at the
+//! start of the volume, such as a [`Region`](crate::io::Region) of a disk or a
+//! disk image file. It can also be installed as a `SimpleFileSystem` protocol
+//! with [`FatFs::install`].
+
+use core::cmp;
+
+use crate::bytes::{le16, le32};
+use crate::io::{self, Read, Seek, SeekFrom};
+use crate::path::{Component, Path};
+use crate::prelude::*;
+
+pub use self::dir::FatDirEntry;
+pub use self::sfs::FAT_VOLUME_VENDOR_GUID;
+
+mod dir;
+mod sfs;
+
+/// The size of the window of the FAT kept in memory
+const FAT_WINDOW: u64 = 4096;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// A cached part of the FAT, with a few bytes of overlap so that FAT12
+/// entries crossing the window end are complete
+struct FatCache {
+    offset: u64,
+    data: Vec<u8>,
+}
+
+/// A FAT volume
+pub struct FatFs<R> {
+    inner: R,
+    fat_type: FatType,
+    sector_size: u32,
+    cluster_size: u32,
+    /// Byte offset of the FAT in use
+    fat_offset: u64,
+    fat_len: u64,
+    /// Byte offset and length of the FAT12/16 root directory
+    root_offset: u64,
+    root_len: u64,
+    /// First cluster of the FAT32 root directory
+    root_cluster: u32,
+    data_offset: u64,
+    clusters: u32,
+    fs_info_free: Option<u32>,
+    serial: u32,
+    label: Option<String>,
+    fat_cache: Option<FatCache>,
+}
+
+impl<R: Read + Seek> FatFs<R> {
+    /// Check the boot sector of the volume in `inner` and prepare to read it
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut boot = [0; 512];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut boot)?;
+        if boot[510..] != [0x55, 0xAA] {
+            return Err(Status::UNSUPPORTED);
+        }
+
+        let sector_size = u32::from(le16(&boot, 11));
+        let sectors_per_cluster = u32::from(boot[13]);
+        let reserved = u64::from(le16(&boot, 14));
+        let fats = u64::from(boot[16]);
+        let root_entries = u64::from(le16(&boot, 17));
+        let total = match le16(&boot, 19) {
+            0 => u64::from(le32(&boot, 32)),
+            total => u64::from(total),
+        };
+        let fat_sectors = match le16(&boot, 22) {
+            0 => u64::from(le32(&boot, 36)),
+            size => u64::from(size),
+        };
+
+        if !sector_size.is_power_of_two()
+            || !(512..=4096).contains(&sector_size)
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved == 0
+            || fats == 0
+            || fat_sectors == 0
+        {
+            return Err(Status::UNSUPPORTED);
+        }
+
+        let sector = u64::from(sector_size);
+        let root_sectors = (root_entries * dir::ENTRY_SIZE as u64).div_ceil(sector);
+        let meta_sectors = reserved + fats * fat_sectors + root_sectors;
+        let clusters = total
+            .checked_sub(meta_sectors)
+            .ok_or(Status::VOLUME_CORRUPTED)?
+            / u64::from(sectors_per_cluster);
+
+        // The type is decided by the cluster count alone
+        let fat_type = match clusters {
+            0..=4084 => FatType::Fat12,
+            4085..=65524 => FatType::Fat16,
+            _ => FatType::Fat32,
+        };
+
+        let mut fat_index = 0;
+        let mut root_cluster = 0;
+        let mut fs_info_free = None;
+        let label_offset;
+        let serial_offset;
+        if fat_type == FatType::Fat32 {
+            // Mirroring may be disabled, leaving only one active FAT
+            let flags = le16(&boot, 40);
+            if flags & 0x80 != 0 {
+                fat_index = u64::from(flags & 0xF);
+            }
+            root_cluster = le32(&boot, 44);
+            fs_info_free = read_fs_info(&mut inner, u64::from(le16(&boot, 48)) * sector)
+                .filter(|&free| u64::from(free) <= clusters);
+            label_offset = 71;
+            serial_offset = 67;
+        } else {
+            label_offset = 43;
+            serial_offset = 39;
+        }
+
+        // The extended boot signature marks the serial and label as present
+        let extended = boot[serial_offset - 1] == 0x29;
+        let label = if extended {
+            let text: String = boot[label_offset..label_offset + 11]
+                .iter()
+                .map(|&b| char::from(b))
+                .collect();
+            match text.trim_end() {
+                "" | "NO NAME" => None,
+                text => Some(text.to_string()),
+            }
+        } else {
+            None
+        };
+
+        let mut fs = FatFs {
+            inner,
+            fat_type,
+            sector_size,
+            cluster_size: sector_size * sectors_per_cluster,
+            fat_offset: (reserved + fat_index.min(fats - 1) * fat_sectors) * sector,
+            fat_len: fat_sectors * sector,
+            root_offset: (reserved + fats * fat_sectors) * sector,
+            root_len: root_sectors * sector,
+            root_cluster,
+            data_offset: meta_sectors * sector,
+            clusters: clusters as u32,
+            fs_info_free,
+            serial: if extended {
+                le32(&boot, serial_offset)
+            } else {
+                0
+            },
+            label,
+            fat_cache: None,
+        };
+
+        // The label in the root directory takes precedence
+        let root = fs.root_data()?;
+        if let Some(label) = dir::parse_dir(&root).label {
+            fs.label = Some(label);
+        }
+
+        Ok(fs)
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    /// The volume label, if it has one
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    pub fn cluster_size(&self) -> u32 {
+        self.cluster_size
+    }
+
+    pub fn sector_size(&self) -> u32 {
+        self.sector_size
+    }
+
+    /// The size of the data area in bytes
+    pub fn volume_size(&self) -> u64 {
+        u64::from(self.clusters) * u64::from(self.cluster_size)
+    }
+
+    /// The free space in bytes, counting free clusters if FAT32 did not
+    /// record it
+    pub fn free_space(&mut self) -> Result<u64> {
+        let free = match self.fs_info_free {
+            Some(free) => free,
+            None => {
+                let mut free = 0;
+                for cluster in 2..self.clusters + 2 {
+                    if self.fat_entry(cluster)? == 0 {
+                        free += 1;
+                    }
+                }
+                self.fs_info_free = Some(free);
+                free
+            }
+        };
+        Ok(u64::from(free) * u64::from(self.cluster_size))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(buf)?;
+        Ok(())
+    }
+
+    /// The raw FAT entry of `cluster`
+    fn fat_entry(&mut self, cluster: u32) -> Result<u32> {
+        let (offset, width) = match self.fat_type {
+            FatType::Fat12 => (u64::from(cluster) * 3 / 2, 2),
+            FatType::Fat16 => (u64::from(cluster) * 2, 2),
+            FatType::Fat32 => (u64::from(cluster) * 4, 4),
+        };
+        if offset + width > self.fat_len {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        let cached = match &self.fat_cache {
+            Some(cache) => {
+                offset >= cache.offset && offset + width <= cache.offset + cache.data.len() as u64
+            }
+            None => false,
+        };
+        if !cached {
+            let start = offset - offset % FAT_WINDOW;
+            let mut data = vec![0; cmp::min(FAT_WINDOW + 4, self.fat_len - start) as usize];
+            self.read_at(self.fat_offset + start, &mut data)?;
+            self.fat_cache = Some(FatCache {
+                offset: start,
+                data,
+            });
+        }
+
+        let cache = self.fat_cache.as_ref().unwrap();
+        let data = &cache.data[(offset - cache.offset) as usize..];
+        Ok(match self.fat_type {
+            FatType::Fat12 if cluster & 1 == 1 => u32::from(le16(data, 0) >> 4),
+            FatType::Fat12 => u32::from(le16(data, 0) & 0xFFF),
+            FatType::Fat16 => u32::from(le16(data, 0)),
+            FatType::Fat32 => le32(data, 0) & 0x0FFF_FFFF,
+        })
+    }
+
+    /// The cluster after `cluster` in a chain, or `None` at the end
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>> {
+        self.check_cluster(cluster)?;
+        let next = self.fat_entry(cluster)?;
+        let end = match self.fat_type {
+            FatType::Fat12 => 0xFF8,
+            FatType::Fat16 => 0xFFF8,
+            FatType::Fat32 => 0x0FFF_FFF8,
+        };
+        if next >= end {
+            Ok(None)
+        } else {
+            self.check_cluster(next)?;
+            Ok(Some(next))
+        }
+    }
+
+    fn check_cluster(&self, cluster: u32) -> Result<()> {
+        if cluster >= 2 && cluster - 2 < self.clusters {
+            Ok(())
+        } else {
+            Err(Status::VOLUME_CORRUPTED)
+        }
+    }
+
+    fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_offset + u64::from(cluster - 2) * u64::from(self.cluster_size)
+    }
+
+    /// Read a whole cluster chain
+    fn chain_data(&mut self, first: u32) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut cluster = Some(first);
+        while let Some(current) = cluster {
+            // A chain longer than the volume must loop
+            if data.len() as u64 >= self.volume_size() {
+                return Err(Status::VOLUME_CORRUPTED);
+            }
+
+            self.check_cluster(current)?;
+            let start = data.len();
+            data.resize(start + self.cluster_size as usize, 0);
+            let offset = self.cluster_offset(current);
+            self.read_at(offset, &mut data[start..])?;
+            cluster = self.next_cluster(current)?;
+        }
+        Ok(data)
+    }
+
+    fn root_data(&mut self) -> Result<Vec<u8>> {
+        if self.fat_type == FatType::Fat32 {
+            self.chain_data(self.root_cluster)
+        } else {
+            let mut data = vec![0; self.root_len as usize];
+            self.read_at(self.root_offset, &mut data)?;
+            Ok(data)
+        }
+    }
+
+    fn root_entry(&self) -> FatDirEntry {
+        FatDirEntry::root(self.root_cluster)
+    }
+
+    /// Read the entries of the directory `dir`, including `.` and `..`
+    fn dir_entries(&mut self, dir: &FatDirEntry) -> Result<Vec<FatDirEntry>> {
+        if !dir.is_dir() {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        // Directories refer to the root as cluster 0, even on FAT32
+        let data = match dir.first_cluster {
+            0 => self.root_data()?,
+            cluster if cluster == self.root_cluster => self.root_data()?,
+            cluster => self.chain_data(cluster)?,
+        };
+        Ok(dir::parse_dir(&data).entries)
+    }
+
+    /// Look up `path`, relative to the root directory
+    pub fn entry<P: AsRef<Path>>(&mut self, path: P) -> Result<FatDirEntry> {
+        let path = path.as_ref().normalize();
+        let mut entry = self.root_entry();
+        for component in path.components() {
+            let name = match component {
+                Component::Normal(name) => name,
+                _ => continue,
+            };
+            if !entry.is_dir() {
+                return Err(Status::NOT_FOUND);
+            }
+
+            entry = self
+                .dir_entries(&entry)?
+                .into_iter()
+                .find(|child| !child.is_dot() && child.matches(name))
+                .ok_or(Status::NOT_FOUND)?;
+        }
+        Ok(entry)
+    }
+
+    /// List the directory at `path`, without `.` and `..`
+    pub fn read_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<FatDirEntry>> {
+        let dir = self.entry(path)?;
+        let mut entries = self.dir_entries(&dir)?;
+        entries.retain(|entry| !entry.is_dot());
+        Ok(entries)
+    }
+
+    /// Open the file at `path` for reading
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> Result<FatFile<'_, R>> {
+        let entry = self.entry(path)?;
+        if entry.is_dir() {
+            return Err(Status::ACCESS_DENIED);
+        }
+        Ok(FatFile::new(self, entry))
+    }
+
+    /// Read the whole file at `path`
+    pub fn read<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open(path)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Read from the file `entry` at `position`, following its chain from
+    /// `cursor`, the last cluster visited as an index and cluster number
+    fn read_file(
+        &mut self,
+        entry: &FatDirEntry,
+        position: u64,
+        cursor: &mut Option<(u64, u32)>,
+        buf: &mut [u8],
+    ) -> Result<usize> {
+        let remaining = entry.len().saturating_sub(position);
+        let count = cmp::min(buf.len() as u64, remaining) as usize;
+        let cluster_size = u64::from(self.cluster_size);
+
+        let mut done = 0;
+        while done < count {
+            let pos = position + done as u64;
+            let index = pos / cluster_size;
+
+            // Walk forward from the cursor if possible, or from the start
+            let (mut current_index, mut cluster) = match *cursor {
+                Some((i, cluster)) if i <= index => (i, cluster),
+                _ => (0, entry.first_cluster),
+            };
+            while current_index < index {
+                cluster = self
+                    .next_cluster(cluster)?
+                    .ok_or(Status::VOLUME_CORRUPTED)?;
+                current_index += 1;
+            }
+            self.check_cluster(cluster)?;
+            *cursor = Some((index, cluster));
+
+            let offset = pos % cluster_size;
+            let chunk = cmp::min((count - done) as u64, cluster_size - offset) as usize;
+            let at = self.cluster_offset(cluster) + offset;
+            self.read_at(at, &mut buf[done..done + chunk])?;
+            done += chunk;
+        }
+        Ok(count)
+    }
+}
+
+/// Read the free cluster count from the FAT32 FSInfo sector
+fn read_fs_info<R: Read + Seek>(inner: &mut R, offset: u64) -> Option<u32> {
+    let mut sector = [0; 512];
+    inner.seek(SeekFrom::Start(offset)).ok()?;
+    inner.read_exact(&mut sector).ok()?;
+    if le32(&sector, 0) != 0x41615252 || le32(&sector, 484) != 0x61417272 {
+        return None;
+    }
+    match le32(&sector, 488) {
+        0xFFFF_FFFF => None,
+        free => Some(free),
+    }
+}
+
+/// A file opened for reading
+pub struct FatFile<'a, R> {
+    fs: &'a mut FatFs<R>,
+    entry: FatDirEntry,
+    position: u64,
+    cursor: Option<(u64, u32)>,
+}
+
+impl<'a, R: Read + Seek> FatFile<'a, R> {
+    fn new(fs: &'a mut FatFs<R>, entry: FatDirEntry) -> Self {
+        FatFile {
+            fs,
+            entry,
+            position: 0,
+            cursor: None,
+        }
+    }
+
+    pub fn entry(&self) -> &FatDirEntry {
+        &self.entry
+    }
+
+    pub fn len(&self) -> u64 {
+        self.entry.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry.is_empty()
+    }
+}
+
+impl<'a, R: Read + Seek> Read for FatFile<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self
+            .fs
+            .read_file(&self.entry, self.position, &mut self.cursor, buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for FatFile<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.len(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        self.position = base
+            .checked_add_signed(offset)
+            .ok_or(io::Error::INVALID_INPUT)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::inflate::zlib_decompress;
+    use crate::io::Cursor;
+
+    pub(crate) const FAT12: &[u8] = include_bytes!("../../testdata/fat/fat12.img.z");
+    pub(crate) const FAT16: &[u8] = include_bytes!("../../testdata/fat/fat16.img.z");
+    pub(crate) const FAT32: &[u8] = include_bytes!("../../testdata/fat/fat32.img.z");
+
+    pub(crate) const LINE: &[u8] = b"Rust is cool!\n";
+
+    /// Open a compressed image from `testdata/fat`
+    pub(crate) fn volume(image: &[u8]) -> FatFs<Cursor<Vec<u8>>> {
+        FatFs::new(Cursor::new(zlib_decompress(image).unwrap())).unwrap()
+    }
+
+    fn volumes() -> [(FatType, FatFs<Cursor<Vec<u8>>>); 3] {
+        [
+            (FatType::Fat12, volume(FAT12)),
+            (FatType::Fat16, volume(FAT16)),
+            (FatType::Fat32, volume(FAT32)),
+        ]
+    }
+
+    #[test]
+    fn volume_info() {
+        for (fat_type, fs) in volumes() {
+            assert_eq!(fs.fat_type(), fat_type);
+            assert_eq!(fs.label(), Some("Test!"));
+            assert_eq!(fs.serial(), 0x12345678);
+            assert_eq!(fs.cluster_size(), 512);
+        }
+    }
+
+    #[test]
+    fn read_files() {
+        for (fat_type, mut fs) in volumes() {
+            // The long file spans many clusters
+            assert_eq!(
+                fs.read("long.txt").unwrap(),
+                LINE.repeat(1000),
+                "{fat_type:?}"
+            );
+            assert_eq!(fs.read("\\short.txt").unwrap(), LINE);
+            assert_eq!(fs.read("very/long/path/test.txt").unwrap(), LINE);
+            assert_eq!(
+                fs.read("very-long-dir-name/very-long-file-name.txt")
+                    .unwrap(),
+                LINE
+            );
+        }
+    }
+
+    #[test]
+    fn long_names() {
+        for (fat_type, mut fs) in volumes() {
+            let mut names = fs
+                .read_dir("\\")
+                .unwrap()
+                .iter()
+                .map(|entry| entry.file_name().to_string())
+                .collect::<Vec<_>>();
+            names.sort();
+            // The deleted file is not listed
+            assert_eq!(
+                names,
+                ["long.txt", "short.txt", "very", "very-long-dir-name"],
+                "{fat_type:?}"
+            );
+
+            // Names match ignoring case, by their long or short name
+            let entry = fs
+                .entry("VERY-LONG-DIR-NAME/Very-Long-File-Name.TXT")
+                .unwrap();
+            assert_eq!(entry.file_name(), "very-long-file-name.txt");
+            assert_eq!(entry.short_name(), "VERY-L~1.TXT");
+            assert_eq!(entry.len(), LINE.len() as u64);
+            let entry = fs.entry("VERY-L~1/very-l~1.txt").unwrap();
+            assert_eq!(entry.file_name(), "very-long-file-name.txt");
+            assert_eq!(fs.entry("deleted file.txt").err(), Some(Status::NOT_FOUND));
+        }
+    }
+
+    #[test]
+    fn open_errors() {
+        let mut fs = volume(FAT16);
+        assert_eq!(fs.open("missing.txt").err(), Some(Status::NOT_FOUND));
+        assert_eq!(fs.open("short.txt/x").err(), Some(Status::NOT_FOUND));
+        assert_eq!(fs.open("very").err(), Some(Status::ACCESS_DENIED));
+    }
+
+    #[test]
+    fn seek_and_read() {
+        let mut fs = volume(FAT32);
+        let mut file = fs.open("long.txt").unwrap();
+        assert_eq!(file.len(), 14000);
+
+        // Read across the boundary of the first two clusters
+        let mut buf = [0; 14];
+        file.seek(SeekFrom::Start(504)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"Rust is cool!\n");
+
+        // Seeking back restarts the walk of the cluster chain
+        file.seek(SeekFrom::End(-14)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, LINE);
+        file.seek(SeekFrom::Start(14)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, LINE);
+        file.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+    }
+}
@@ -0,0 +1,616 @@
+//! A `SimpleFileSystem` protocol backed by a [`FatFs`]
+
+use alloc_crate::rc::Rc;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::{mem, ptr, slice};
+use uefi::boot::InterfaceType;
+use uefi::device::{
+    DevicePath as UefiDevicePath, DevicePathEndType, DevicePathHardwareType, DevicePathType,
+};
+use uefi::fs::{
+    File as InnerFile, FileInfo as InnerFileInfo, FileSystemInfo as InnerFileSystemInfo,
+    FileSystemVolumeLabel, SimpleFileSystem, FILE_MODE_CREATE, FILE_MODE_READ, FILE_MODE_WRITE,
+};
+use uefi::guid;
+
+use super::{FatDirEntry, FatFs};
+use crate::device_path::DevicePath;
+use crate::io::{Read, Seek};
+use crate::path::{Path, PathBuf};
+use crate::prelude::*;
+use crate::system_table;
+
+const REVISION: u64 = 0x00010000;
+
+/// The GUID of the vendor device path node of installed volumes
+pub const FAT_VOLUME_VENDOR_GUID: Guid = guid!("b12f50a5-13f8-4f1e-9ba0-6cda62099f32");
+
+/// The number of volumes installed, which tells their device paths apart
+static INSTALLED: AtomicU32 = AtomicU32::new(0);
+
+type Shared<R> = Rc<RefCell<FatFs<R>>>;
+
+/// The installed protocol, which firmware sees as its first field
+#[repr(C)]
+struct Volume<R> {
+    raw: SimpleFileSystem,
+    fs: Shared<R>,
+}
+
+enum OpenKind {
+    Dir {
+        entries: Vec<FatDirEntry>,
+        index: usize,
+    },
+    File {
+        position: u64,
+        cursor: Option<(u64, u32)>,
+    },
+}
+
+/// An open file or directory, which firmware sees as its first field
+#[repr(C)]
+struct OpenFile<R> {
+    raw: InnerFile,
+    fs: Shared<R>,
+    /// The absolute, normalized path of the entry
+    path: PathBuf,
+    entry: FatDirEntry,
+    kind: OpenKind,
+}
+
+impl<R: Read + Seek> OpenFile<R> {
+    fn open(fs: &Shared<R>, path: PathBuf) -> Result<Box<Self>> {
+        let entry = fs.borrow_mut().entry(&path)?;
+        let kind = if entry.is_dir() {
+            let mut entries = fs.borrow_mut().dir_entries(&entry)?;
+            // Only subdirectories list `.` and `..`
+            if path.parent().is_none() {
+                entries.retain(|entry| !entry.is_dot());
+            }
+            OpenKind::Dir { entries, index: 0 }
+        } else {
+            OpenKind::File {
+                position: 0,
+                cursor: None,
+            }
+        };
+
+        Ok(Box::new(OpenFile {
+            raw: InnerFile {
+                Revision: REVISION,
+                Open: open::<R>,
+                Close: close::<R>,
+                Delete: delete::<R>,
+                Read: read::<R>,
+                Write: write,
+                SetPosition: set_position::<R>,
+                GetPosition: get_position::<R>,
+                GetInfo: get_info::<R>,
+                SetInfo: set_info,
+                Flush: flush,
+            },
+            fs: fs.clone(),
+            path,
+            entry,
+            kind,
+        }))
+    }
+
+    fn from_raw(raw: &mut InnerFile) -> &mut Self {
+        unsafe { &mut *(raw as *mut InnerFile as *mut Self) }
+    }
+}
+
+/// Decode a null-terminated UCS-2 name passed by a caller, which may hold
+/// unpaired surrogates
+fn decode_name(name: *const u16) -> Result<String> {
+    let mut units = Vec::new();
+    loop {
+        let unit = unsafe { *name.add(units.len()) };
+        if unit == 0 {
+            break;
+        }
+        units.push(unit);
+    }
+
+    char::decode_utf16(units)
+        .collect::<core::result::Result<String, _>>()
+        .map_err(|_| Status::INVALID_PARAMETER)
+}
+
+/// Copy `data` to a firmware buffer of `*size` bytes, or report the size needed
+fn copy_out(data: &[u8], size: &mut usize, buffer: *mut u8) -> Status {
+    if *size < data.len() {
+        *size = data.len();
+        return Status::BUFFER_TOO_SMALL;
+    }
+    if buffer.is_null() {
+        return Status::INVALID_PARAMETER;
+    }
+
+    unsafe { ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len()) };
+    *size = data.len();
+    Status::SUCCESS
+}
+
+/// The bytes of `value`, followed by the null-terminated `name` in UCS-2
+fn with_name<T>(value: &T, header: usize, name: &str) -> Vec<u8> {
+    let mut data =
+        unsafe { slice::from_raw_parts(value as *const T as *const u8, header) }.to_vec();
+    for unit in name.encode_utf16().chain(Some(0)) {
+        data.extend_from_slice(&unit.to_ne_bytes());
+    }
+    data
+}
+
+fn file_info(entry: &FatDirEntry, cluster_size: u64) -> Vec<u8> {
+    let header = mem::offset_of!(InnerFileInfo, FileName);
+    let name = entry.file_name();
+    let info = InnerFileInfo {
+        Size: (header + (name.encode_utf16().count() + 1) * 2) as u64,
+        FileSize: entry.len(),
+        PhysicalSize: entry.len().div_ceil(cluster_size) * cluster_size,
+        CreateTime: entry.created(),
+        LastAccessTime: entry.accessed(),
+        ModificationTime: entry.modified(),
+        Attribute: entry.attributes().0,
+        ..Default::default()
+    };
+    with_name(&info, header, name)
+}
+
+extern "efiapi" fn open_volume<R: Read + Seek>(
+    this: &mut SimpleFileSystem,
+    root: &mut *mut InnerFile,
+) -> Status {
+    let volume = unsafe { &mut *(this as *mut SimpleFileSystem as *mut Volume<R>) };
+    match OpenFile::open(&volume.fs, PathBuf::from("\\")) {
+        Ok(file) => {
+            *root = Box::into_raw(file) as *mut InnerFile;
+            Status::SUCCESS
+        }
+        Err(err) => err,
+    }
+}
+
+extern "efiapi" fn open<R: Read + Seek>(
+    this: &mut InnerFile,
+    new: &mut *mut InnerFile,
+    name: *const u16,
+    mode: u64,
+    _attributes: u64,
+) -> Status {
+    if name.is_null() || mode & FILE_MODE_READ == 0 {
+        return Status::INVALID_PARAMETER;
+    }
+    if mode & (FILE_MODE_WRITE | FILE_MODE_CREATE) != 0 {
+        return Status::WRITE_PROTECTED;
+    }
+
+    // Relative names start from the directory of this handle
+    let this = OpenFile::<R>::from_raw(this);
+    let name = match decode_name(name) {
+        Ok(name) => PathBuf::from(name),
+        Err(err) => return err,
+    };
+    let path = if name.is_absolute() {
+        name.normalize()
+    } else {
+        let base = match this.kind {
+            OpenKind::Dir { .. } => this.path.as_path(),
+            OpenKind::File { .. } => this.path.parent().unwrap_or(Path::new("\\")),
+        };
+        base.join(&name).normalize()
+    };
+
+    match OpenFile::open(&this.fs, path) {
+        Ok(file) => {
+            *new = Box::into_raw(file) as *mut InnerFile;
+            Status::SUCCESS
+        }
+        Err(err) => err,
+    }
+}
+
+extern "efiapi" fn close<R: Read + Seek>(this: &mut InnerFile) -> Status {
+    drop(unsafe { Box::from_raw(OpenFile::<R>::from_raw(this)) });
+    Status::SUCCESS
+}
+
+extern "efiapi" fn delete<R: Read + Seek>(this: &mut InnerFile) -> Status {
+    let _ = close::<R>(this);
+    Status::WARN_DELETE_FAILURE
+}
+
+extern "efiapi" fn read<R: Read + Seek>(
+    this: &mut InnerFile,
+    size: &mut usize,
+    buffer: *mut u8,
+) -> Status {
+    let this = OpenFile::<R>::from_raw(this);
+    let mut fs = this.fs.borrow_mut();
+    match &mut this.kind {
+        OpenKind::Dir { entries, index } => {
+            // Each read returns one entry, and an empty read ends the list
+            let entry = match entries.get(*index) {
+                Some(entry) => entry,
+                None => {
+                    *size = 0;
+                    return Status::SUCCESS;
+                }
+            };
+            let status = copy_out(
+                &file_info(entry, u64::from(fs.cluster_size())),
+                size,
+                buffer,
+            );
+            if status == Status::SUCCESS {
+                *index += 1;
+            }
+            status
+        }
+        OpenKind::File { position, cursor } => {
+            if *position > this.entry.len() {
+                return Status::DEVICE_ERROR;
+            }
+
+            if *size == 0 {
+                return Status::SUCCESS;
+            }
+            if buffer.is_null() {
+                return Status::INVALID_PARAMETER;
+            }
+
+            let buf = unsafe { slice::from_raw_parts_mut(buffer, *size) };
+            match fs.read_file(&this.entry, *position, cursor, buf) {
+                Ok(count) => {
+                    *position += count as u64;
+                    *size = count;
+                    Status::SUCCESS
+                }
+                Err(err) => err,
+            }
+        }
+    }
+}
+
+extern "efiapi" fn write(_this: &mut InnerFile, _size: &mut usize, _buffer: *const u8) -> Status {
+    Status::WRITE_PROTECTED
+}
+
+extern "efiapi" fn set_position<R: Read + Seek>(this: &mut InnerFile, position: u64) -> Status {
+    let this = OpenFile::<R>::from_raw(this);
+    match &mut this.kind {
+        // Directories can only be rewound
+        OpenKind::Dir { index, .. } if position == 0 => {
+            *index = 0;
+            Status::SUCCESS
+        }
+        OpenKind::Dir { .. } => Status::UNSUPPORTED,
+        OpenKind::File {
+            position: current, ..
+        } => {
+            *current = if position == u64::MAX {
+                this.entry.len()
+            } else {
+                position
+            };
+            Status::SUCCESS
+        }
+    }
+}
+
+extern "efiapi" fn get_position<R: Read + Seek>(
+    this: &mut InnerFile,
+    position: &mut u64,
+) -> Status {
+    match OpenFile::<R>::from_raw(this).kind {
+        OpenKind::Dir { .. } => Status::UNSUPPORTED,
+        OpenKind::File {
+            position: current, ..
+        } => {
+            *position = current;
+            Status::SUCCESS
+        }
+    }
+}
+
+extern "efiapi" fn get_info<R: Read + Seek>(
+    this: &mut InnerFile,
+    kind: &Guid,
+    size: &mut usize,
+    buffer: *mut u8,
+) -> Status {
+    let this = OpenFile::<R>::from_raw(this);
+    let mut fs = this.fs.borrow_mut();
+    let label = fs.label().unwrap_or("").to_string();
+
+    let data = if *kind == InnerFileInfo::ID {
+        file_info(&this.entry, u64::from(fs.cluster_size()))
+    } else if *kind == InnerFileSystemInfo::ID {
+        let header = mem::offset_of!(InnerFileSystemInfo, VolumeLabel);
+        let free_space = match fs.free_space() {
+            Ok(free_space) => free_space,
+            Err(err) => return err,
+        };
+        let info = InnerFileSystemInfo {
+            Size: (header + (label.encode_utf16().count() + 1) * 2) as u64,
+            ReadOnly: true,
+            VolumeSize: fs.volume_size(),
+            FreeSpace: free_space,
+            BlockSize: fs.cluster_size(),
+            VolumeLabel: [],
+        };
+        with_name(&info, header, &label)
+    } else if *kind == FileSystemVolumeLabel::ID {
+        with_name(&FileSystemVolumeLabel { VolumeLabel: [] }, 0, &label)
+    } else {
+        return Status::UNSUPPORTED;
+    };
+
+    copy_out(&data, size, buffer)
+}
+
+extern "efiapi" fn set_info(
+    _this: &mut InnerFile,
+    _kind: &Guid,
+    _size: &mut usize,
+    _buffer: *const u8,
+) -> Status {
+    Status::WRITE_PROTECTED
+}
+
+extern "efiapi" fn flush(_this: &mut InnerFile) -> Status {
+    Status::ACCESS_DENIED
+}
+
+/// The device path of an installed volume: the nodes of `parent`, if any,
+/// then a hardware vendor node holding `instance`
+fn volume_path(parent: Option<&DevicePath>, instance: u32) -> Vec<u8> {
+    let mut path = parent.map_or(Vec::new(), |parent| parent.as_bytes().to_vec());
+    let Guid(data1, data2, data3, data4) = FAT_VOLUME_VENDOR_GUID;
+    path.extend_from_slice(&[
+        DevicePathType::Hardware as u8,
+        DevicePathHardwareType::Vendor as u8,
+        24,
+        0,
+    ]);
+    path.extend_from_slice(&data1.to_le_bytes());
+    path.extend_from_slice(&data2.to_le_bytes());
+    path.extend_from_slice(&data3.to_le_bytes());
+    path.extend_from_slice(&data4);
+    path.extend_from_slice(&instance.to_le_bytes());
+    path.extend_from_slice(&[
+        DevicePathType::End as u8,
+        DevicePathEndType::Entire as u8,
+        4,
+        0,
+    ]);
+    path
+}
+
+impl<R: Read + Seek + 'static> FatFs<R> {
+    /// Install the volume as a `SimpleFileSystem` protocol on a new handle.
+    /// The volume stays in memory for as long as the firmware runs.
+    ///
+    /// The handle also gets a device path, so that images can be loaded from
+    /// the volume. It is `parent`, such as the path of the disk or file
+    /// holding the volume, followed by a vendor node with
+    /// [`FAT_VOLUME_VENDOR_GUID`] and a number unique to the volume.
+    pub fn install(self, parent: Option<&DevicePath>) -> Result<Handle> {
+        let instance = INSTALLED.fetch_add(1, Ordering::Relaxed);
+        let path = Box::into_raw(volume_path(parent, instance).into_boxed_slice());
+        let mut handle = Handle(0);
+        let status = (system_table().BootServices.InstallProtocolInterface)(
+            &mut handle,
+            &UefiDevicePath::GUID,
+            InterfaceType::Native,
+            path as *mut u8 as usize,
+        );
+        if status != Status::SUCCESS {
+            drop(unsafe { Box::from_raw(path) });
+            return Err(status);
+        }
+
+        let volume = Box::new(Volume {
+            raw: SimpleFileSystem {
+                Revision: REVISION,
+                OpenVolume: open_volume::<R>,
+            },
+            fs: Rc::new(RefCell::new(self)),
+        });
+        let interface = Box::into_raw(volume);
+        let status = (system_table().BootServices.InstallProtocolInterface)(
+            &mut handle,
+            &SimpleFileSystem::GUID,
+            InterfaceType::Native,
+            interface as usize,
+        );
+        if status != Status::SUCCESS {
+            // The path can only be freed once nothing refers to it
+            let uninstalled = (system_table().BootServices.UninstallProtocolInterface)(
+                handle,
+                &UefiDevicePath::GUID,
+                path as *mut u8 as usize,
+            );
+            if uninstalled == Status::SUCCESS {
+                drop(unsafe { Box::from_raw(path) });
+            }
+            drop(unsafe { Box::from_raw(interface) });
+            return Err(status);
+        }
+        Ok(handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fat;
+    use crate::ffi::wstr;
+
+    fn leak(path: Vec<u8>) -> DevicePath {
+        let path = Box::leak(path.into_boxed_slice());
+        DevicePath(unsafe { &mut *(path.as_mut_ptr() as *mut UefiDevicePath) })
+    }
+
+    type Image = crate::io::Cursor<Vec<u8>>;
+
+    fn open_volume_root(fs: FatFs<Image>) -> &'static mut InnerFile {
+        let volume = Box::leak(Box::new(Volume {
+            raw: SimpleFileSystem {
+                Revision: REVISION,
+                OpenVolume: open_volume::<Image>,
+            },
+            fs: Rc::new(RefCell::new(fs)),
+        }));
+        let mut root = ptr::null_mut();
+        assert_eq!(
+            (volume.raw.OpenVolume)(&mut volume.raw, &mut root),
+            Status::SUCCESS
+        );
+        unsafe { &mut *root }
+    }
+
+    fn open_file(dir: &mut InnerFile, name: &str, mode: u64) -> Result<&'static mut InnerFile> {
+        let mut file = ptr::null_mut();
+        match (dir.Open)(dir, &mut file, wstr(name).as_ptr(), mode, 0) {
+            Status::SUCCESS => Ok(unsafe { &mut *file }),
+            err => Err(err),
+        }
+    }
+
+    /// Read from `file` into a buffer of `len` bytes
+    fn read_file(file: &mut InnerFile, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        let mut size = len;
+        assert_eq!(
+            (file.Read)(file, &mut size, buf.as_mut_ptr()),
+            Status::SUCCESS
+        );
+        buf.truncate(size);
+        buf
+    }
+
+    fn info_of(data: &[u8]) -> (u64, String) {
+        let mut buf = vec![0u8; mem::size_of::<InnerFileInfo>()];
+        buf[..data.len()].copy_from_slice(data);
+        let info = unsafe { ptr::read_unaligned(buf.as_ptr() as *const InnerFileInfo) };
+        (info.FileSize, decode_name(info.FileName.as_ptr()).unwrap())
+    }
+
+    #[test]
+    fn read_through_protocol() {
+        let root = open_volume_root(fat::tests::volume(fat::tests::FAT12));
+        let dir = open_file(root, "very-long-dir-name", FILE_MODE_READ).unwrap();
+        let file = open_file(dir, "very-long-file-name.txt", FILE_MODE_READ).unwrap();
+        assert_eq!(read_file(file, 100), fat::tests::LINE);
+        assert_eq!(read_file(file, 100), b"");
+
+        let mut size = 0;
+        let status = (file.GetInfo)(file, &InnerFileInfo::ID, &mut size, ptr::null_mut());
+        assert_eq!(status, Status::BUFFER_TOO_SMALL);
+        let mut info = vec![0; size];
+        let status = (file.GetInfo)(file, &InnerFileInfo::ID, &mut size, info.as_mut_ptr());
+        assert_eq!(status, Status::SUCCESS);
+        assert_eq!(info_of(&info), (14, "very-long-file-name.txt".to_string()));
+        assert_eq!((file.Close)(file), Status::SUCCESS);
+
+        // A subdirectory lists `.` and `..`, and relative names start from it
+        let mut names = Vec::new();
+        loop {
+            let entry = read_file(dir, 1024);
+            if entry.is_empty() {
+                break;
+            }
+            names.push(info_of(&entry).1);
+        }
+        assert_eq!(names, [".", "..", "very-long-file-name.txt"]);
+        let file = open_file(dir, "..\\short.txt", FILE_MODE_READ).unwrap();
+        assert_eq!(read_file(file, 100), fat::tests::LINE);
+        assert_eq!((file.Close)(file), Status::SUCCESS);
+        assert_eq!((dir.Close)(dir), Status::SUCCESS);
+        assert_eq!((root.Close)(root), Status::SUCCESS);
+    }
+
+    #[test]
+    fn changes_are_refused() {
+        let root = open_volume_root(fat::tests::volume(fat::tests::FAT16));
+        let mode = FILE_MODE_READ | FILE_MODE_WRITE | FILE_MODE_CREATE;
+        assert_eq!(
+            open_file(root, "new.txt", mode).err(),
+            Some(Status::WRITE_PROTECTED)
+        );
+        assert_eq!(
+            open_file(root, "short.txt", FILE_MODE_READ | FILE_MODE_WRITE).err(),
+            Some(Status::WRITE_PROTECTED)
+        );
+
+        let file = open_file(root, "short.txt", FILE_MODE_READ).unwrap();
+        let mut size = 4;
+        assert_eq!(
+            (file.Write)(file, &mut size, b"data".as_ptr()),
+            Status::WRITE_PROTECTED
+        );
+        // Deleting closes the handle, and the file remains
+        assert_eq!((file.Delete)(file), Status::WARN_DELETE_FAILURE);
+        let file = open_file(root, "short.txt", FILE_MODE_READ).unwrap();
+        assert_eq!(read_file(file, 100), fat::tests::LINE);
+        assert_eq!((file.Close)(file), Status::SUCCESS);
+        assert_eq!((root.Close)(root), Status::SUCCESS);
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        let root = open_volume_root(fat::tests::volume(fat::tests::FAT32));
+
+        // An unpaired surrogate cannot be decoded
+        let mut file = ptr::null_mut();
+        let name = [0xD800, b'a' as u16, 0];
+        assert_eq!(
+            (root.Open)(root, &mut file, name.as_ptr(), FILE_MODE_READ, 0),
+            Status::INVALID_PARAMETER
+        );
+
+        let file = open_file(root, "short.txt", FILE_MODE_READ).unwrap();
+        let mut size = 0;
+        assert_eq!(
+            (file.Read)(file, &mut size, ptr::null_mut()),
+            Status::SUCCESS
+        );
+        assert_eq!(size, 0);
+        size = 4;
+        assert_eq!(
+            (file.Read)(file, &mut size, ptr::null_mut()),
+            Status::INVALID_PARAMETER
+        );
+        assert_eq!(read_file(file, 100), fat::tests::LINE);
+        assert_eq!((file.Close)(file), Status::SUCCESS);
+        assert_eq!((root.Close)(root), Status::SUCCESS);
+    }
+
+    #[test]
+    fn volume_path_nodes() {
+        let parent = leak(volume_path(None, 3));
+        let nodes = parent.nodes().collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].kind, DevicePathType::Hardware as u8);
+        assert_eq!(nodes[0].sub_type, DevicePathHardwareType::Vendor as u8);
+        assert_eq!(
+            nodes[0].data[..16],
+            [
+                0xA5, 0x50, 0x2F, 0xB1, 0xF8, 0x13, 0x1E, 0x4F, 0x9B, 0xA0, 0x6C, 0xDA, 0x62, 0x09,
+                0x9F, 0x32
+            ]
+        );
+        assert_eq!(nodes[0].data[16..], 3u32.to_le_bytes());
+
+        let child = leak(volume_path(Some(&parent), 4));
+        assert!(child.starts_with(&parent));
+        let nodes = child.nodes().collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].data[16..], 4u32.to_le_bytes());
+    }
+}
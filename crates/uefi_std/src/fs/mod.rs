@@ -0,0 +1,792 @@
+use core::default::Default;
+use core::ops::BitOr;
+use core::{char, mem, ptr, slice};
+
+use uefi::fs::{
+    File as InnerFile, FileInfo as InnerFileInfo, FileSystemInfo as InnerFileSystemInfo,
+    FileSystemVolumeLabel, SimpleFileSystem, FILE_ARCHIVE, FILE_DIRECTORY, FILE_HIDDEN,
+    FILE_MODE_CREATE, FILE_MODE_READ, FILE_MODE_WRITE, FILE_READ_ONLY, FILE_SYSTEM,
+};
+use uefi::time::Time;
+
+use crate::ffi::wstr;
+use crate::io::{self, Read, Seek, SeekFrom, Write};
+use crate::path::{Component, Path, PathBuf};
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+pub use self::walk::{
+    copy_tree, glob, glob_match, remove_tree, walk_dir, ErrorPolicy, Progress, TreeOptions,
+    TreeSummary, Visit, WalkDir, WalkEntry,
+};
+
+mod walk;
+
+pub struct FileSystem(pub &'static mut SimpleFileSystem);
+
+impl Protocol<SimpleFileSystem> for FileSystem {
+    fn guid() -> Guid {
+        SimpleFileSystem::GUID
+    }
+
+    fn new(inner: &'static mut SimpleFileSystem) -> Self {
+        FileSystem(inner)
+    }
+}
+
+impl FileSystem {
+    pub fn root(&mut self) -> Result<Dir> {
+        let mut interface = ptr::null_mut::<InnerFile>();
+        let status = (self.0.OpenVolume)(self.0, &mut interface);
+
+        match status {
+            Status::SUCCESS => Ok(Dir(File(unsafe { &mut *interface }))),
+            _ => Err(status),
+        }
+    }
+
+    pub fn info(&mut self) -> Result<FileSystemInfo> {
+        let buf = self.root()?.0.get_info(&InnerFileSystemInfo::ID)?;
+        FileSystemInfo::from_bytes(buf.bytes())
+    }
+
+    /// The size of the volume in bytes
+    pub fn volume_size(&mut self) -> Result<u64> {
+        Ok(self.info()?.volume_size)
+    }
+
+    /// The number of bytes available on the volume
+    pub fn free_space(&mut self) -> Result<u64> {
+        Ok(self.info()?.free_space)
+    }
+
+    pub fn block_size(&mut self) -> Result<u32> {
+        Ok(self.info()?.block_size)
+    }
+
+    pub fn read_only(&mut self) -> Result<bool> {
+        Ok(self.info()?.read_only)
+    }
+
+    pub fn label(&mut self) -> Result<String> {
+        let buf = self.root()?.0.get_info(&FileSystemVolumeLabel::ID)?;
+        Ok(decode_name(buf.bytes()))
+    }
+
+    pub fn set_label(&mut self, label: &str) -> Result<()> {
+        let wlabel = wstr(label);
+        let data = unsafe {
+            slice::from_raw_parts(
+                wlabel.as_ptr() as *const u8,
+                wlabel.len() * mem::size_of::<u16>(),
+            )
+        };
+        self.root()?.0.set_info(&FileSystemVolumeLabel::ID, data)
+    }
+}
+
+/// A variable-length [`InnerFileSystemInfo`] with its volume label decoded
+#[derive(Clone, Debug)]
+pub struct FileSystemInfo {
+    read_only: bool,
+    volume_size: u64,
+    free_space: u64,
+    block_size: u32,
+    label: String,
+}
+
+impl FileSystemInfo {
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The size of the volume in bytes
+    pub fn volume_size(&self) -> u64 {
+        self.volume_size
+    }
+
+    /// The number of bytes available on the volume
+    pub fn free_space(&self) -> u64 {
+        self.free_space
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        const LABEL_OFFSET: usize = mem::offset_of!(InnerFileSystemInfo, VolumeLabel);
+        if data.len() < LABEL_OFFSET {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        let raw = unsafe {
+            let mut raw = mem::zeroed::<InnerFileSystemInfo>();
+            ptr::copy_nonoverlapping(data.as_ptr(), &mut raw as *mut _ as *mut u8, LABEL_OFFSET);
+            raw
+        };
+        let end = (raw.Size as usize).clamp(LABEL_OFFSET, data.len());
+        Ok(FileSystemInfo {
+            read_only: raw.ReadOnly,
+            volume_size: raw.VolumeSize,
+            free_space: raw.FreeSpace,
+            block_size: raw.BlockSize,
+            label: decode_name(&data[LABEL_OFFSET..end]),
+        })
+    }
+}
+
+/// Typed `FILE_*` attribute bits
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct FileAttributes(pub u64);
+
+impl FileAttributes {
+    pub const READ_ONLY: Self = Self(FILE_READ_ONLY);
+    pub const HIDDEN: Self = Self(FILE_HIDDEN);
+    pub const SYSTEM: Self = Self(FILE_SYSTEM);
+    pub const DIRECTORY: Self = Self(FILE_DIRECTORY);
+    pub const ARCHIVE: Self = Self(FILE_ARCHIVE);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for FileAttributes {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// Information about a file, decoded from [`InnerFileInfo`]
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    len: u64,
+    physical_len: u64,
+    attributes: FileAttributes,
+    created: Time,
+    accessed: Time,
+    modified: Time,
+}
+
+impl Metadata {
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The amount of space the file uses on the volume
+    pub fn physical_len(&self) -> u64 {
+        self.physical_len
+    }
+
+    pub fn attributes(&self) -> FileAttributes {
+        self.attributes
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes.contains(FileAttributes::DIRECTORY)
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.attributes.contains(FileAttributes::READ_ONLY)
+    }
+
+    pub fn created(&self) -> Time {
+        self.created
+    }
+
+    pub fn accessed(&self) -> Time {
+        self.accessed
+    }
+
+    pub fn modified(&self) -> Time {
+        self.modified
+    }
+}
+
+/// A buffer for variable-length data returned by `GetInfo` and `Read`
+struct InfoBuffer {
+    // Stored as u64 to satisfy the alignment of the info structures
+    data: Vec<u64>,
+    len: usize,
+}
+
+impl InfoBuffer {
+    fn bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data.as_ptr() as *const u8, self.len) }
+    }
+
+    /// Call `f` with a buffer, growing it for as long as the firmware reports
+    /// BUFFER_TOO_SMALL along with the required size
+    fn read<F: FnMut(&mut usize, *mut u8) -> Status>(mut f: F) -> Result<Self> {
+        let mut len = mem::size_of::<InnerFileInfo>();
+        loop {
+            let mut data = vec![0u64; len.div_ceil(mem::size_of::<u64>())];
+            let mut size = data.len() * mem::size_of::<u64>();
+            match f(&mut size, data.as_mut_ptr() as *mut u8) {
+                Status::SUCCESS => return Ok(InfoBuffer { data, len: size }),
+                Status::BUFFER_TOO_SMALL if size > len => len = size,
+                status => return Err(status),
+            }
+        }
+    }
+}
+
+/// Decode a null-terminated UCS-2 string from native endian bytes
+fn decode_name(data: &[u8]) -> String {
+    let wide = data
+        .chunks_exact(2)
+        .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+        .take_while(|&w| w != 0);
+    char::decode_utf16(wide)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+const FILE_NAME_OFFSET: usize = mem::offset_of!(InnerFileInfo, FileName);
+
+/// A variable-length [`InnerFileInfo`] with its file name decoded
+#[derive(Clone, Debug)]
+pub struct FileInfo {
+    name: String,
+    metadata: Metadata,
+}
+
+/// An entry returned while reading a [`Dir`]
+pub type DirEntry = FileInfo;
+
+impl FileInfo {
+    pub fn file_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.metadata.is_file()
+    }
+
+    /// Returns true for the `.` and `..` entries of a directory
+    pub fn is_dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() < FILE_NAME_OFFSET {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        let mut raw = InnerFileInfo::default();
+        unsafe {
+            ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                &mut raw as *mut _ as *mut u8,
+                FILE_NAME_OFFSET,
+            );
+        }
+
+        let end = (raw.Size as usize).clamp(FILE_NAME_OFFSET, data.len());
+        Ok(FileInfo {
+            name: decode_name(&data[FILE_NAME_OFFSET..end]),
+            metadata: Metadata {
+                len: raw.FileSize,
+                physical_len: raw.PhysicalSize,
+                attributes: FileAttributes(raw.Attribute),
+                created: raw.CreateTime,
+                accessed: raw.LastAccessTime,
+                modified: raw.ModificationTime,
+            },
+        })
+    }
+
+    fn to_buffer(&self) -> InfoBuffer {
+        let name = wstr(&self.name);
+        let len = FILE_NAME_OFFSET + name.len() * mem::size_of::<u16>();
+
+        let raw = InnerFileInfo {
+            Size: len as u64,
+            FileSize: self.metadata.len,
+            PhysicalSize: self.metadata.physical_len,
+            CreateTime: self.metadata.created,
+            LastAccessTime: self.metadata.accessed,
+            ModificationTime: self.metadata.modified,
+            Attribute: self.metadata.attributes.0,
+            ..Default::default()
+        };
+
+        let mut data = vec![0u64; len.div_ceil(mem::size_of::<u64>())];
+        unsafe {
+            let ptr = data.as_mut_ptr() as *mut u8;
+            ptr::copy_nonoverlapping(&raw as *const _ as *const u8, ptr, FILE_NAME_OFFSET);
+            ptr::copy_nonoverlapping(
+                name.as_ptr(),
+                ptr.add(FILE_NAME_OFFSET) as *mut u16,
+                name.len(),
+            );
+        }
+        InfoBuffer { data, len }
+    }
+}
+
+pub struct File(pub &'static mut InnerFile);
+
+impl File {
+    fn get_info(&mut self, id: &Guid) -> Result<InfoBuffer> {
+        InfoBuffer::read(|len, buf| (self.0.GetInfo)(self.0, id, len, buf))
+    }
+
+    fn set_info(&mut self, id: &Guid, data: &[u8]) -> Result<()> {
+        let mut len = data.len();
+        (self.0.SetInfo)(self.0, id, &mut len, data.as_ptr()).into()
+    }
+
+    pub fn info(&mut self) -> Result<FileInfo> {
+        FileInfo::from_bytes(self.get_info(&InnerFileInfo::ID)?.bytes())
+    }
+
+    fn set_file_info(&mut self, info: &FileInfo) -> Result<()> {
+        self.set_info(&InnerFileInfo::ID, info.to_buffer().bytes())
+    }
+
+    pub fn metadata(&mut self) -> Result<Metadata> {
+        Ok(self.info()?.metadata)
+    }
+
+    fn position(&mut self) -> Result<u64> {
+        let mut position = 0;
+        let status = (self.0.GetPosition)(self.0, &mut position);
+
+        match status {
+            Status::SUCCESS => Ok(position),
+            _ => Err(status),
+        }
+    }
+
+    fn set_position(&mut self, position: u64) -> Result<()> {
+        (self.0.SetPosition)(self.0, position).into()
+    }
+
+    /// Truncate or extend the file to `size` bytes
+    pub fn set_len(&mut self, size: u64) -> Result<()> {
+        let mut info = self.info()?;
+        info.metadata.len = size;
+        self.set_file_info(&info)
+    }
+
+    /// Close and delete the file
+    pub fn delete(self) -> Result<()> {
+        // Delete closes the handle, even on failure
+        let mut file = mem::ManuallyDrop::new(self);
+        (file.0.Delete)(file.0).into()
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = buf.len();
+        let status = (self.0.Read)(self.0, &mut len, buf.as_mut_ptr());
+
+        match status {
+            Status::SUCCESS => Ok(len),
+            _ => Err(status.into()),
+        }
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        // Reserve the rest of the file up front to avoid regrowing
+        if let (Ok(metadata), Ok(position)) = (self.metadata(), self.position()) {
+            let remaining = metadata.len().saturating_sub(position);
+            buf.reserve(usize::try_from(remaining).unwrap_or(0));
+        }
+
+        io::read_to_end(self, buf)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut len = buf.len();
+        let status = (self.0.Write)(self.0, &mut len, buf.as_ptr());
+
+        match status {
+            Status::SUCCESS => Ok(len),
+            _ => Err(status.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match (self.0.Flush)(self.0) {
+            Status::SUCCESS => Ok(()),
+            status => Err(status.into()),
+        }
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.set_position(position)?;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => {
+                // A position of all ones moves to the end of the file
+                self.set_position(u64::MAX)?;
+                (self.position()?, offset)
+            }
+            SeekFrom::Current(offset) => (self.position()?, offset),
+        };
+
+        let position = base
+            .checked_add_signed(offset)
+            .ok_or(io::Error::INVALID_INPUT)?;
+        self.set_position(position)?;
+        Ok(position)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.position()?)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = (self.0.Close)(self.0);
+    }
+}
+
+/// Options for opening a file relative to a [`Dir`]
+///
+/// Files are always opened for reading, as the firmware does not support
+/// write-only access.
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Position the file at its end after opening, implies `write`
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Truncate the file to zero length after opening, requires `write`
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Create the file if it does not exist, requires `write`
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    fn mode(&self) -> Result<u64> {
+        let write = self.write || self.append;
+        if !self.read && !write {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        if (self.truncate || self.create) && !write {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let mut mode = FILE_MODE_READ;
+        if write {
+            mode |= FILE_MODE_WRITE;
+        }
+        if self.create {
+            mode |= FILE_MODE_CREATE;
+        }
+        Ok(mode)
+    }
+
+    fn prepare(&self, mut file: File) -> Result<File> {
+        if self.truncate {
+            file.set_len(0)?;
+        }
+        if self.append {
+            file.seek(SeekFrom::End(0))?;
+        }
+        Ok(file)
+    }
+
+    pub fn open(&self, dir: &mut Dir, filename: &[u16]) -> Result<File> {
+        self.prepare(dir.open_mode(filename, self.mode()?, 0)?)
+    }
+}
+
+pub struct Dir(pub File);
+
+impl Dir {
+    fn open_mode(&mut self, filename: &[u16], mode: u64, attributes: u64) -> Result<File> {
+        let mut interface = ptr::null_mut::<InnerFile>();
+        let status = ((self.0).0.Open)(
+            (self.0).0,
+            &mut interface,
+            filename.as_ptr(),
+            mode,
+            attributes,
+        );
+
+        match status {
+            Status::SUCCESS => Ok(File(unsafe { &mut *interface })),
+            _ => Err(status),
+        }
+    }
+
+    pub fn open(&mut self, filename: &[u16]) -> Result<File> {
+        self.open_mode(filename, FILE_MODE_READ, 0)
+    }
+
+    pub fn open_dir(&mut self, filename: &[u16]) -> Result<Dir> {
+        let file = self.open(filename)?;
+        Ok(Dir(file))
+    }
+
+    /// Open a directory, creating it if it does not exist
+    pub fn create_dir(&mut self, filename: &[u16]) -> Result<Dir> {
+        let mut file = self.open_mode(
+            filename,
+            FILE_MODE_READ | FILE_MODE_WRITE | FILE_MODE_CREATE,
+            FILE_DIRECTORY,
+        )?;
+        if file.metadata()?.is_dir() {
+            Ok(Dir(file))
+        } else {
+            Err(Status::ACCESS_DENIED)
+        }
+    }
+
+    pub fn read(&mut self) -> Result<Option<FileInfo>> {
+        let file = &mut self.0;
+        let buf = InfoBuffer::read(|len, buf| (file.0.Read)(file.0, len, buf))?;
+        match buf.len {
+            0 => Ok(None),
+            _ => FileInfo::from_bytes(buf.bytes()).map(Some),
+        }
+    }
+
+    /// Move back to the first entry of the directory
+    pub fn rewind(&mut self) -> Result<()> {
+        self.0.set_position(0)
+    }
+
+    /// Iterate over the remaining entries, skipping `.` and `..`
+    pub fn entries(&mut self) -> Entries {
+        Entries {
+            dir: self,
+            dots: false,
+            done: false,
+        }
+    }
+}
+
+/// Iterator over the entries of a [`Dir`]
+pub struct Entries<'a> {
+    dir: &'a mut Dir,
+    dots: bool,
+    done: bool,
+}
+
+impl<'a> Entries<'a> {
+    /// Also yield the `.` and `..` entries
+    pub fn with_dots(mut self, dots: bool) -> Self {
+        self.dots = dots;
+        self
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.dir.read() {
+                Ok(Some(entry)) if !self.dots && entry.is_dot() => (),
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => self.done = true,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+}
+
+pub fn find<P: AsRef<Path>>(path: P) -> Result<(usize, File)> {
+    let wpath = path.as_ref().to_wstr();
+
+    for (i, fs) in FileSystem::all().iter_mut().enumerate() {
+        // Errors are ignored as they may not be related to opening the matching file
+        if let Ok(mut root) = fs.root() {
+            if let Ok(file) = root.open(&wpath) {
+                return Ok((i, file));
+            }
+        }
+    }
+
+    // If no matching file found (or it failed to open), return NOT_FOUND
+    Err(Status::NOT_FOUND)
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let (_i, mut file) = find(path)?;
+
+    let mut data = vec![];
+    let _count = file.read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+/// Resolve `path` against the root directory of a volume
+fn absolute(path: &Path) -> PathBuf {
+    Path::new("\\").join(path).normalize()
+}
+
+/// Open `path` relative to the root of `fs`
+fn open_path(fs: &mut FileSystem, path: &Path, mode: u64, attributes: u64) -> Result<File> {
+    fs.root()?
+        .open_mode(&absolute(path).to_wstr(), mode, attributes)
+}
+
+/// Open the file at `path` on `fs` using `options`
+pub fn open<P: AsRef<Path>>(fs: &mut FileSystem, path: P, options: &OpenOptions) -> Result<File> {
+    open_path(fs, path.as_ref(), options.mode()?, 0).and_then(|file| options.prepare(file))
+}
+
+pub fn metadata<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> Result<Metadata> {
+    open_path(fs, path.as_ref(), FILE_MODE_READ, 0)?.metadata()
+}
+
+pub fn exists<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> bool {
+    open_path(fs, path.as_ref(), FILE_MODE_READ, 0).is_ok()
+}
+
+pub fn read<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> Result<Vec<u8>> {
+    let mut file = open_path(fs, path.as_ref(), FILE_MODE_READ, 0)?;
+
+    let mut data = vec![];
+    let _count = file.read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+pub fn read_to_string<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> Result<String> {
+    String::from_utf8(read(fs, path)?).map_err(|_| Status::VOLUME_CORRUPTED)
+}
+
+/// Write `contents` to `path`, creating or truncating the file
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(
+    fs: &mut FileSystem,
+    path: P,
+    contents: C,
+) -> Result<()> {
+    let mut file = open(
+        fs,
+        path,
+        OpenOptions::new().write(true).create(true).truncate(true),
+    )?;
+    file.write_all(contents.as_ref())?;
+    Ok(file.flush()?)
+}
+
+/// Copy the contents of `from` to `to`, returning the number of bytes copied
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(fs: &mut FileSystem, from: P, to: Q) -> Result<u64> {
+    let mut src = open_path(fs, from.as_ref(), FILE_MODE_READ, 0)?;
+    let mut dst = open(
+        fs,
+        to,
+        OpenOptions::new().write(true).create(true).truncate(true),
+    )?;
+
+    let total = io::copy(&mut src, &mut dst)?;
+    dst.flush()?;
+
+    Ok(total)
+}
+
+/// Rename `from` to `to`, which may be in another directory on the same volume
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(fs: &mut FileSystem, from: P, to: Q) -> Result<()> {
+    let mut file = open_path(fs, from.as_ref(), FILE_MODE_READ | FILE_MODE_WRITE, 0)?;
+    let mut info = file.info()?;
+
+    // An absolute name moves the file relative to the root of the volume
+    info.name = absolute(to.as_ref()).into_string();
+    file.set_file_info(&info)
+}
+
+pub fn create_dir<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> Result<()> {
+    fs.root()?
+        .create_dir(&absolute(path.as_ref()).to_wstr())
+        .map(|_| ())
+}
+
+/// Create a directory and all of its missing parents
+pub fn create_dir_all<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> Result<()> {
+    let mut dir = fs.root()?;
+    for component in absolute(path.as_ref()).components() {
+        match component {
+            Component::RootDir => (),
+            Component::Normal(name) => dir = dir.create_dir(&wstr(name))?,
+            // Normalized absolute paths have no other components
+            Component::CurDir | Component::ParentDir => return Err(Status::INVALID_PARAMETER),
+        }
+    }
+    Ok(())
+}
+
+pub fn remove_file<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> Result<()> {
+    let mut file = open_path(fs, path.as_ref(), FILE_MODE_READ | FILE_MODE_WRITE, 0)?;
+    if file.metadata()?.is_dir() {
+        return Err(Status::ACCESS_DENIED);
+    }
+    file.delete()
+}
+
+/// Remove a directory after removing all of its contents
+pub fn remove_dir_all<P: AsRef<Path>>(fs: &mut FileSystem, path: P) -> Result<()> {
+    remove_tree(fs, path, &mut TreeOptions::new()).map(|_| ())
+}
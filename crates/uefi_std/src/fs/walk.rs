@@ -0,0 +1,464 @@
+//! Recursive directory traversal and tree operations
+
+use uefi::fs::{FILE_MODE_READ, FILE_MODE_WRITE};
+
+use super::{absolute, open_path, Dir, DirEntry, File, FileSystem, OpenOptions};
+use crate::ffi::wstr;
+use crate::io::{Read, Write};
+use crate::path::{Component, Path, PathBuf};
+use crate::prelude::*;
+
+/// An entry yielded by [`WalkDir`]
+#[derive(Clone, Debug)]
+pub struct WalkEntry {
+    path: PathBuf,
+    depth: usize,
+    entry: DirEntry,
+}
+
+impl WalkEntry {
+    /// The path of the entry, relative to the directory being walked
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The depth of the entry, where children of the walked directory are at depth 1
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn entry(&self) -> &DirEntry {
+        &self.entry
+    }
+
+    pub fn file_name(&self) -> &str {
+        self.entry.file_name()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.entry.is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.entry.is_file()
+    }
+}
+
+/// The action to take after visiting an entry with [`WalkDir::visit`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Visit {
+    /// Continue walking, descending into the entry if it is a directory
+    Continue,
+    /// Do not descend into the entry
+    Prune,
+    /// Stop walking
+    Stop,
+}
+
+/// Depth-first iterator over a directory tree, parents before children
+pub struct WalkDir {
+    stack: Vec<(Dir, PathBuf)>,
+    pending: Option<PathBuf>,
+    failed: PathBuf,
+    max_depth: usize,
+}
+
+/// Walk the tree under `dir`, not including `dir` itself
+pub fn walk_dir(dir: Dir) -> WalkDir {
+    WalkDir::new(dir)
+}
+
+impl WalkDir {
+    pub fn new(dir: Dir) -> Self {
+        WalkDir {
+            stack: vec![(dir, PathBuf::new())],
+            pending: None,
+            failed: PathBuf::new(),
+            max_depth: usize::MAX,
+        }
+    }
+
+    /// Do not yield entries deeper than `depth`
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Do not descend into the directory that was yielded last
+    pub fn prune(&mut self) {
+        self.pending = None;
+    }
+
+    /// The directory that could not be opened or read when the last error was yielded
+    pub fn failed_path(&self) -> &Path {
+        &self.failed
+    }
+
+    /// Call `f` for every entry, stopping at the first error
+    pub fn visit<F: FnMut(&WalkEntry) -> Visit>(mut self, mut f: F) -> Result<()> {
+        while let Some(entry) = self.next() {
+            match f(&entry?) {
+                Visit::Continue => (),
+                Visit::Prune => self.prune(),
+                Visit::Stop => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn descend(&mut self, path: PathBuf) -> Result<()> {
+        let (parent, _) = self.stack.last_mut().ok_or(Status::NOT_FOUND)?;
+        let name = path.file_name().ok_or(Status::INVALID_PARAMETER)?;
+        let dir = parent.open_dir(&wstr(name))?;
+        self.stack.push((dir, path));
+        Ok(())
+    }
+}
+
+impl Iterator for WalkDir {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(path) = self.pending.take() {
+            if let Err(err) = self.descend(path.clone()) {
+                self.failed = path;
+                return Some(Err(err));
+            }
+        }
+
+        loop {
+            let depth = self.stack.len();
+            let (dir, path) = self.stack.last_mut()?;
+            match dir.read() {
+                Ok(Some(entry)) if entry.is_dot() => (),
+                Ok(Some(entry)) => {
+                    let entry = WalkEntry {
+                        path: path.join(entry.file_name()),
+                        depth,
+                        entry,
+                    };
+                    if entry.is_dir() && depth < self.max_depth {
+                        self.pending = Some(entry.path.clone());
+                    }
+                    return Some(Ok(entry));
+                }
+                Ok(None) => {
+                    self.stack.pop();
+                }
+                Err(err) => {
+                    self.failed = path.clone();
+                    // Abandon the directory, as reading it again may fail forever
+                    self.stack.pop();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+fn glob_match_name(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| glob_match_name(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && glob_match_name(rest, &name[1..]),
+        Some((c, rest)) => match name.split_first() {
+            Some((n, name)) => c.eq_ignore_ascii_case(n) && glob_match_name(rest, name),
+            None => false,
+        },
+    }
+}
+
+fn glob_match_components(pattern: &[&str], names: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => names.is_empty(),
+        Some((&"**", rest)) => (0..=names.len()).any(|i| glob_match_components(rest, &names[i..])),
+        Some((part, rest)) => match names.split_first() {
+            Some((name, names)) => {
+                glob_match_name(part.as_bytes(), name.as_bytes())
+                    && glob_match_components(rest, names)
+            }
+            None => false,
+        },
+    }
+}
+
+fn normal_components(path: &Path) -> Vec<&str> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Match a relative path against a glob pattern, ignoring ASCII case like FAT
+///
+/// `?` matches one character and `*` matches any characters within a single
+/// component, while a `**` component matches any number of components.
+pub fn glob_match<P: AsRef<Path>>(pattern: &str, path: P) -> bool {
+    glob_match_components(
+        &normal_components(Path::new(pattern)),
+        &normal_components(path.as_ref()),
+    )
+}
+
+/// Find the entries under `dir` whose relative paths match `pattern`
+pub fn glob(dir: Dir, pattern: &str) -> impl Iterator<Item = Result<WalkEntry>> + '_ {
+    let parts = normal_components(Path::new(pattern));
+    let mut walk = walk_dir(dir);
+    if !parts.contains(&"**") {
+        walk = walk.max_depth(parts.len());
+    }
+    walk.filter(move |entry| match entry {
+        Ok(entry) => glob_match(pattern, entry.path()),
+        Err(_) => true,
+    })
+}
+
+/// What to do when an entry fails during [`copy_tree`] or [`remove_tree`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    /// Return the first error
+    #[default]
+    Abort,
+    /// Record the error in the [`TreeSummary`] and continue with the next entry
+    Continue,
+}
+
+/// Progress reported during [`copy_tree`] and [`remove_tree`]
+#[derive(Clone, Copy, Debug)]
+pub struct Progress<'a> {
+    /// The path currently being processed, relative to the tree
+    pub path: &'a Path,
+    /// Bytes processed of the current file
+    pub file_bytes: u64,
+    /// Size of the current file
+    pub file_len: u64,
+    /// Files completed so far
+    pub files: usize,
+    /// Directories completed so far
+    pub dirs: usize,
+    /// Bytes processed so far
+    pub bytes: u64,
+}
+
+type ProgressFn<'a> = dyn FnMut(&Progress) + 'a;
+
+/// Options for [`copy_tree`] and [`remove_tree`]
+#[derive(Default)]
+pub struct TreeOptions<'a> {
+    on_error: ErrorPolicy,
+    progress: Option<Box<ProgressFn<'a>>>,
+}
+
+impl<'a> TreeOptions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.on_error = policy;
+        self
+    }
+
+    pub fn progress<F: FnMut(&Progress) + 'a>(mut self, f: F) -> Self {
+        self.progress = Some(Box::new(f));
+        self
+    }
+}
+
+/// The outcome of [`copy_tree`] or [`remove_tree`]
+#[derive(Clone, Debug, Default)]
+pub struct TreeSummary {
+    pub files: usize,
+    pub dirs: usize,
+    pub bytes: u64,
+    /// Entries that failed, when using [`ErrorPolicy::Continue`]
+    pub errors: Vec<(PathBuf, Status)>,
+}
+
+struct TreeState<'o, 'a> {
+    options: &'o mut TreeOptions<'a>,
+    summary: TreeSummary,
+}
+
+impl<'o, 'a> TreeState<'o, 'a> {
+    fn report(&mut self, path: &Path, file_bytes: u64, file_len: u64) {
+        if let Some(progress) = &mut self.options.progress {
+            progress(&Progress {
+                path,
+                file_bytes,
+                file_len,
+                files: self.summary.files,
+                dirs: self.summary.dirs,
+                bytes: self.summary.bytes,
+            });
+        }
+    }
+
+    /// Apply the error policy to the result of processing `path`
+    fn check(&mut self, path: &Path, res: Result<()>) -> Result<()> {
+        match res {
+            Err(err) if self.options.on_error == ErrorPolicy::Continue => {
+                self.summary.errors.push((path.to_path_buf(), err));
+                Ok(())
+            }
+            res => res,
+        }
+    }
+}
+
+fn open_dir_path(fs: &mut FileSystem, path: &Path, mode: u64) -> Result<Dir> {
+    let mut file = open_path(fs, path, mode, 0)?;
+    if file.metadata()?.is_dir() {
+        Ok(Dir(file))
+    } else {
+        Err(Status::NOT_FOUND)
+    }
+}
+
+fn copy_file(state: &mut TreeState, path: &Path, src: &mut File, dst: &mut File) -> Result<()> {
+    let len = src.metadata()?.len();
+    let mut done = 0;
+    let mut buf = vec![0; 65536];
+    loop {
+        let count = src.read(&mut buf)?;
+        if count == 0 {
+            break;
+        }
+
+        dst.write_all(&buf[..count])?;
+        done += count as u64;
+        state.summary.bytes += count as u64;
+        state.report(path, done, len);
+    }
+    Ok(dst.flush()?)
+}
+
+/// Copy the tree at `from` on `src` into `to` on `dst`, creating directories
+/// as needed and replacing existing files
+pub fn copy_tree<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: &mut FileSystem,
+    from: P,
+    dst: &mut FileSystem,
+    to: Q,
+    options: &mut TreeOptions,
+) -> Result<TreeSummary> {
+    let from = absolute(from.as_ref());
+    let to = absolute(to.as_ref());
+
+    super::create_dir_all(dst, &to)?;
+    let mut src_root = open_dir_path(src, &from, FILE_MODE_READ)?;
+    let mut dst_root = open_dir_path(dst, &to, FILE_MODE_READ | FILE_MODE_WRITE)?;
+    let mut walk = walk_dir(open_dir_path(src, &from, FILE_MODE_READ)?);
+
+    let mut state = TreeState {
+        options,
+        summary: TreeSummary::default(),
+    };
+    let create = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .clone();
+    while let Some(entry) = walk.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                state.check(walk.failed_path(), Err(err))?;
+                continue;
+            }
+        };
+        let wpath = entry.path().to_wstr();
+        let res = if entry.is_dir() {
+            dst_root.create_dir(&wpath).map(|_| {
+                state.summary.dirs += 1;
+            })
+        } else {
+            src_root.open(&wpath).and_then(|mut src_file| {
+                let mut dst_file = create.open(&mut dst_root, &wpath)?;
+                copy_file(&mut state, entry.path(), &mut src_file, &mut dst_file)?;
+                state.summary.files += 1;
+                Ok(())
+            })
+        };
+        state.check(entry.path(), res)?;
+        state.report(entry.path(), 0, 0);
+    }
+
+    Ok(state.summary)
+}
+
+fn remove_contents(state: &mut TreeState, dir: &mut Dir, path: &Path) -> Result<()> {
+    // Collect entries first, as deleting while reading a directory is not reliable
+    let mut entries = Vec::new();
+    for entry in dir.entries() {
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(err) => {
+                // The rest of the directory cannot be read
+                state.check(path, Err(err))?;
+                break;
+            }
+        }
+    }
+
+    for entry in entries {
+        let child_path = path.join(entry.file_name());
+        let res = dir
+            .open_mode(
+                &wstr(entry.file_name()),
+                FILE_MODE_READ | FILE_MODE_WRITE,
+                0,
+            )
+            .and_then(|file| {
+                if entry.is_dir() {
+                    let mut child = Dir(file);
+                    let res = remove_contents(state, &mut child, &child_path);
+                    // Try to delete the directory even if some contents remain
+                    let deleted = child.0.delete();
+                    res?;
+                    deleted?;
+                    state.summary.dirs += 1;
+                } else {
+                    file.delete()?;
+                    state.summary.files += 1;
+                    state.summary.bytes += entry.metadata().len();
+                }
+                Ok(())
+            });
+        state.check(&child_path, res)?;
+        state.report(&child_path, 0, 0);
+    }
+
+    Ok(())
+}
+
+/// Remove the tree at `path`, including the directory itself. With
+/// [`ErrorPolicy::Continue`], failing to delete the directory is recorded
+/// under the empty path like any other failure.
+pub fn remove_tree<P: AsRef<Path>>(
+    fs: &mut FileSystem,
+    path: P,
+    options: &mut TreeOptions,
+) -> Result<TreeSummary> {
+    let path = absolute(path.as_ref());
+    if path.parent().is_none() {
+        // Refuse to delete the root directory
+        return Err(Status::ACCESS_DENIED);
+    }
+
+    let mut dir = open_dir_path(fs, &path, FILE_MODE_READ | FILE_MODE_WRITE)?;
+    let mut state = TreeState {
+        options,
+        summary: TreeSummary::default(),
+    };
+    remove_contents(&mut state, &mut dir, Path::new(""))?;
+    // The directory remains if any of its contents do
+    let res = dir.0.delete().map(|()| {
+        state.summary.dirs += 1;
+    });
+    state.check(Path::new(""), res)?;
+
+    Ok(state.summary)
+}
@@ -0,0 +1,164 @@
+use core::{mem, ptr};
+use uefi::graphics::{GraphicsBltOp, GraphicsBltPixel};
+
+use super::{Gop, Point, Rect};
+use crate::prelude::*;
+
+/// Check that `rect` lies within a buffer of `len` pixels with lines of
+/// `stride` pixels
+fn check_buffer(len: usize, rect: Rect, stride: usize) -> Result<()> {
+    if rect.right() > stride as u64 {
+        return Err(Status::INVALID_PARAMETER);
+    }
+    let end = (rect.bottom() - 1)
+        .checked_mul(stride as u64)
+        .and_then(|offset| offset.checked_add(rect.right()))
+        .ok_or(Status::INVALID_PARAMETER)?;
+    if end > len as u64 {
+        return Err(Status::BAD_BUFFER_SIZE);
+    }
+    Ok(())
+}
+
+/// Drawing through Blt, which works in every mode including those without a
+/// framebuffer. Rectangles must lie within the current mode and empty
+/// rectangles do nothing.
+impl Gop {
+    /// Check that `rect` lies on screen
+    fn check_rect(&self, rect: Rect) -> Result<()> {
+        let info = &self.0.Mode.Info;
+        if rect.fits(info.HorizontalResolution, info.VerticalResolution) {
+            Ok(())
+        } else {
+            Err(Status::INVALID_PARAMETER)
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn blt(
+        &mut self,
+        buffer: *mut GraphicsBltPixel,
+        op: GraphicsBltOp,
+        src: Point,
+        dst: Point,
+        width: u32,
+        height: u32,
+        stride: usize,
+    ) -> Result<()> {
+        (self.0.Blt)(
+            self.0,
+            buffer,
+            op,
+            src.x as usize,
+            src.y as usize,
+            dst.x as usize,
+            dst.y as usize,
+            width as usize,
+            height as usize,
+            stride * mem::size_of::<GraphicsBltPixel>(),
+        )
+        .into()
+    }
+
+    /// Fill `rect` with `pixel`
+    pub fn fill(&mut self, rect: Rect, pixel: GraphicsBltPixel) -> Result<()> {
+        if rect.is_empty() {
+            return Ok(());
+        }
+        self.check_rect(rect)?;
+
+        let mut pixel = pixel;
+        self.blt(
+            &mut pixel,
+            GraphicsBltOp::VideoFill,
+            Point::default(),
+            rect.origin(),
+            rect.width,
+            rect.height,
+            0,
+        )
+    }
+
+    /// Fill the whole screen with `pixel`
+    pub fn clear(&mut self, pixel: GraphicsBltPixel) -> Result<()> {
+        let info = &self.0.Mode.Info;
+        let rect = Rect::new(0, 0, info.HorizontalResolution, info.VerticalResolution);
+        self.fill(rect, pixel)
+    }
+
+    /// Draw the `src` area of `buffer`, which has lines of `stride` pixels, at
+    /// `dst` on screen
+    pub fn blit_from_buffer(
+        &mut self,
+        buffer: &[GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()> {
+        if src.is_empty() {
+            return Ok(());
+        }
+        check_buffer(buffer.len(), src, stride)?;
+        self.check_rect(Rect::new(dst.x, dst.y, src.width, src.height))?;
+
+        // Firmware only reads from the buffer for this operation
+        self.blt(
+            buffer.as_ptr() as *mut GraphicsBltPixel,
+            GraphicsBltOp::BufferToVideo,
+            src.origin(),
+            dst,
+            src.width,
+            src.height,
+            stride,
+        )
+    }
+
+    /// Copy the `src` area of the screen to `dst` in `buffer`, which has lines
+    /// of `stride` pixels
+    pub fn read_to_buffer(
+        &mut self,
+        buffer: &mut [GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()> {
+        if src.is_empty() {
+            return Ok(());
+        }
+        self.check_rect(src)?;
+        check_buffer(
+            buffer.len(),
+            Rect::new(dst.x, dst.y, src.width, src.height),
+            stride,
+        )?;
+
+        self.blt(
+            buffer.as_mut_ptr(),
+            GraphicsBltOp::VideoToBuffer,
+            src.origin(),
+            dst,
+            src.width,
+            src.height,
+            stride,
+        )
+    }
+
+    /// Copy the `src` area of the screen to `dst`. The areas may overlap.
+    pub fn copy_within(&mut self, src: Rect, dst: Point) -> Result<()> {
+        if src.is_empty() {
+            return Ok(());
+        }
+        self.check_rect(src)?;
+        self.check_rect(Rect::new(dst.x, dst.y, src.width, src.height))?;
+
+        self.blt(
+            ptr::null_mut(),
+            GraphicsBltOp::VideoToVideo,
+            src.origin(),
+            dst,
+            src.width,
+            src.height,
+            0,
+        )
+    }
+}
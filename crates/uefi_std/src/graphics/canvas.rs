@@ -0,0 +1,276 @@
+use uefi::graphics::GraphicsBltPixel;
+
+use super::{Framebuffer, Gop, Point, Rect};
+use crate::image::{self, Image};
+use crate::prelude::*;
+
+/// Beyond this many separate dirty rectangles they are merged into one
+const MAX_DIRTY: usize = 16;
+
+/// An off-screen image which tracks the areas changed since it was last
+/// flushed, so that only those are copied to the display
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<GraphicsBltPixel>,
+    dirty: Vec<Rect>,
+}
+
+impl Canvas {
+    /// A black canvas of `width` by `height` pixels, all of which are dirty
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut canvas = Canvas {
+            width,
+            height,
+            pixels: vec![GraphicsBltPixel::default(); width as usize * height as usize],
+            dirty: Vec::new(),
+        };
+        canvas.mark_all_dirty();
+        canvas
+    }
+
+    /// A canvas the size of the current mode of `gop`
+    pub fn for_gop(gop: &Gop) -> Self {
+        let info = gop.current_mode();
+        Self::new(info.width, info.height)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// The pixels, in lines of [`width`](Self::width) pixels
+    pub fn pixels(&self) -> &[GraphicsBltPixel] {
+        &self.pixels
+    }
+
+    fn index(&self, point: Point) -> usize {
+        point.y as usize * self.width as usize + point.x as usize
+    }
+
+    pub fn pixel(&self, point: Point) -> Option<GraphicsBltPixel> {
+        if self.bounds().contains(point) {
+            Some(self.pixels[self.index(point)])
+        } else {
+            None
+        }
+    }
+
+    /// Set a pixel, ignoring points outside the canvas
+    pub fn set_pixel(&mut self, point: Point, pixel: GraphicsBltPixel) {
+        if self.bounds().contains(point) {
+            let index = self.index(point);
+            self.pixels[index] = pixel;
+            self.mark_dirty(Rect::new(point.x, point.y, 1, 1));
+        }
+    }
+
+    /// The pixels of `rect` clipped to the canvas, a line at a time. The area
+    /// is marked dirty.
+    pub fn lines_mut(&mut self, rect: Rect) -> impl Iterator<Item = &mut [GraphicsBltPixel]> {
+        let rect = rect.intersection(&self.bounds());
+        self.mark_dirty(rect);
+
+        let width = self.width as usize;
+        let (x, columns) = (rect.x as usize, rect.width as usize);
+        self.pixels
+            .chunks_exact_mut(width.max(1))
+            .skip(rect.y as usize)
+            .take(rect.height as usize)
+            .map(move |line| &mut line[x..x + columns])
+    }
+
+    /// Fill `rect` with `pixel`, clipped to the canvas
+    pub fn fill(&mut self, rect: Rect, pixel: GraphicsBltPixel) {
+        for line in self.lines_mut(rect) {
+            line.fill(pixel);
+        }
+    }
+
+    pub fn clear(&mut self, pixel: GraphicsBltPixel) {
+        self.fill(self.bounds(), pixel);
+    }
+
+    /// Clip the `src` area of a buffer of `len` pixels with lines of `stride`
+    /// pixels, drawn at `dst`, to the canvas. Returns the area covered on the
+    /// canvas and the offset of its first pixel in the buffer.
+    fn clip(&self, len: usize, src: Rect, dst: Point, stride: usize) -> Result<(Rect, usize)> {
+        if src.right() > stride as u64 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        let area = Rect::new(dst.x, dst.y, src.width, src.height).intersection(&self.bounds());
+        if area.is_empty() {
+            return Ok((area, 0));
+        }
+
+        let x = (src.x + area.x - dst.x) as usize;
+        let y = (src.y + area.y - dst.y) as usize;
+        let end = (y + area.height as usize - 1) * stride + x + area.width as usize;
+        if end > len {
+            return Err(Status::BAD_BUFFER_SIZE);
+        }
+        Ok((area, y * stride + x))
+    }
+
+    /// Copy the `src` area of `buffer`, which has lines of `stride` pixels, to
+    /// `dst`, clipped to the canvas
+    pub fn draw(
+        &mut self,
+        buffer: &[GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()> {
+        let (area, start) = self.clip(buffer.len(), src, dst, stride)?;
+        let width = area.width as usize;
+        for (row, line) in self.lines_mut(area).enumerate() {
+            line.copy_from_slice(&buffer[start + row * stride..][..width]);
+        }
+        Ok(())
+    }
+
+    /// Draw the `src` area of `buffer` at `dst` like [`draw`](Self::draw), but
+    /// blend each pixel using the alpha in its `Reserved` byte
+    pub fn blend(
+        &mut self,
+        buffer: &[GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()> {
+        let (area, start) = self.clip(buffer.len(), src, dst, stride)?;
+        let width = area.width as usize;
+        for (row, line) in self.lines_mut(area).enumerate() {
+            let source = &buffer[start + row * stride..][..width];
+            for (pixel, &over) in line.iter_mut().zip(source) {
+                *pixel = image::blend(*pixel, over);
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw `image` with its top left corner at `dst`, blending any
+    /// transparent pixels
+    pub fn draw_image(&mut self, image: &Image, dst: Point) -> Result<()> {
+        let stride = image.width() as usize;
+        if image.is_opaque() {
+            self.draw(image.pixels(), image.bounds(), dst, stride)
+        } else {
+            self.blend(image.pixels(), image.bounds(), dst, stride)
+        }
+    }
+
+    /// Move the contents of `rect` by `dx` and `dy` pixels, clipped to the
+    /// canvas. The area left behind keeps its old contents.
+    pub fn copy_within(&mut self, rect: Rect, dx: i32, dy: i32) {
+        let rect = rect.intersection(&self.bounds());
+        let moved = |start: u32, offset: i32| i64::from(start) + i64::from(offset);
+        let (x, y) = (moved(rect.x, dx), moved(rect.y, dy));
+
+        // Clip the destination, then shrink the source to match
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + i64::from(rect.width)).min(i64::from(self.width));
+        let bottom = (y + i64::from(rect.height)).min(i64::from(self.height));
+        if right <= left || bottom <= top {
+            return;
+        }
+
+        let dst = Rect::new(
+            left as u32,
+            top as u32,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        );
+        let src = Point::new((left - i64::from(dx)) as u32, (top - i64::from(dy)) as u32);
+        let (width, stride) = (dst.width as usize, self.width as usize);
+        let pixels = &mut self.pixels;
+        let mut copy_line = |row: u32| {
+            let start = (src.y + row) as usize * stride + src.x as usize;
+            let to = (dst.y + row) as usize * stride + dst.x as usize;
+            pixels.copy_within(start..start + width, to);
+        };
+        // Copy lines in an order that does not overwrite lines yet to be read
+        if dy > 0 {
+            (0..dst.height).rev().for_each(&mut copy_line);
+        } else {
+            (0..dst.height).for_each(&mut copy_line);
+        }
+        self.mark_dirty(dst);
+    }
+
+    /// Record that `rect` must be copied on the next flush
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        let mut rect = rect.intersection(&self.bounds());
+        if rect.is_empty() {
+            return;
+        }
+
+        // Absorb every rectangle that overlaps or touches the new one
+        loop {
+            let grown = Rect::new(
+                rect.x.saturating_sub(1),
+                rect.y.saturating_sub(1),
+                rect.width.saturating_add(2),
+                rect.height.saturating_add(2),
+            );
+            let Some(index) = self
+                .dirty
+                .iter()
+                .position(|dirty| !dirty.intersection(&grown).is_empty())
+            else {
+                break;
+            };
+            rect = rect.union(&self.dirty.swap_remove(index));
+        }
+
+        self.dirty.push(rect);
+        if self.dirty.len() > MAX_DIRTY {
+            let all = self
+                .dirty
+                .drain(..)
+                .fold(Rect::default(), |all, dirty| all.union(&dirty));
+            self.dirty.push(all);
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.clear();
+        self.mark_dirty(self.bounds());
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// The areas changed since the last flush, which do not overlap
+    pub fn dirty_rects(&self) -> &[Rect] {
+        &self.dirty
+    }
+
+    /// Copy the changed areas to the top left of the display with Blt
+    pub fn flush(&mut self, gop: &mut Gop) -> Result<()> {
+        while let Some(rect) = self.dirty.last() {
+            gop.blit_from_buffer(&self.pixels, *rect, rect.origin(), self.width as usize)?;
+            self.dirty.pop();
+        }
+        Ok(())
+    }
+
+    /// Copy the changed areas to the top left of `framebuffer`
+    pub fn flush_framebuffer(&mut self, framebuffer: &mut Framebuffer) -> Result<()> {
+        while let Some(rect) = self.dirty.last() {
+            framebuffer.draw(&self.pixels, *rect, rect.origin(), self.width as usize)?;
+            self.dirty.pop();
+        }
+        Ok(())
+    }
+}
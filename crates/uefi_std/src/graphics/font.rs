@@ -0,0 +1,323 @@
+use alloc_crate::collections::BTreeMap;
+use core::str;
+use uefi::graphics::GraphicsBltPixel;
+
+use super::{Point, Rect, Surface};
+use crate::bytes::le32;
+use crate::path::Path;
+use crate::prelude::*;
+
+/// The built-in 8x16 font, Sony Fixed from X.Org, under the licence in
+/// `fonts/LICENSE`
+static DEFAULT_FONT: &[u8] = include_bytes!("../../fonts/default8x16.psf");
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TABLE: u8 = 0x02;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_START_SEQUENCE: u16 = 0xFFFE;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_START_SEQUENCE: u8 = 0xFE;
+
+/// The character drawn in place of those a font lacks
+const REPLACEMENT: char = '\u{FFFD}';
+
+/// A bitmap font in PC Screen Font format
+#[derive(Clone, Debug)]
+pub struct Font {
+    width: u32,
+    height: u32,
+    /// The bytes in a row of a glyph
+    pitch: usize,
+    glyph_count: usize,
+    glyphs: Vec<u8>,
+    /// The glyph of each character, or `None` if glyphs are indexed by
+    /// character
+    unicode: Option<BTreeMap<char, usize>>,
+}
+
+impl Font {
+    /// The built-in 8x16 font, which covers Latin-1 and single line box
+    /// drawing
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT_FONT).expect("built-in font is invalid")
+    }
+
+    /// Parse a PSF1 or PSF2 font
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.starts_with(&PSF2_MAGIC) {
+            Self::parse_psf2(data)
+        } else if data.starts_with(&PSF1_MAGIC) {
+            Self::parse_psf1(data)
+        } else {
+            Err(Status::UNSUPPORTED)
+        }
+    }
+
+    /// Load a PSF1 or PSF2 font, such as one from `/usr/share/consolefonts`
+    /// after decompression
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&crate::fs::load(path)?)
+    }
+
+    fn parse_psf1(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        let (mode, height) = (data[2], usize::from(data[3]));
+        let glyph_count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let end = 4 + glyph_count * height;
+        let glyphs = data.get(4..end).ok_or(Status::INVALID_PARAMETER)?;
+
+        let unicode = if mode & PSF1_MODE_HAS_TABLE != 0 {
+            let mut map = BTreeMap::new();
+            let mut entries = data[end..]
+                .chunks_exact(2)
+                .map(|entry| u16::from_le_bytes([entry[0], entry[1]]));
+            for glyph in 0..glyph_count {
+                let mut sequence = false;
+                for value in entries.by_ref() {
+                    match value {
+                        PSF1_SEPARATOR => break,
+                        PSF1_START_SEQUENCE => sequence = true,
+                        // Sequences map combined characters, which are not
+                        // supported
+                        _ if sequence => (),
+                        _ => {
+                            if let Some(c) = char::from_u32(u32::from(value)) {
+                                map.entry(c).or_insert(glyph);
+                            }
+                        }
+                    }
+                }
+            }
+            Some(map)
+        } else {
+            None
+        };
+
+        Self::new(8, height as u32, glyph_count, glyphs, unicode)
+    }
+
+    fn parse_psf2(data: &[u8]) -> Result<Self> {
+        if data.len() < 32 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        let header_size = le32(data, 8) as usize;
+        let flags = le32(data, 12);
+        let glyph_count = le32(data, 16) as usize;
+        let glyph_size = le32(data, 20) as usize;
+        let height = le32(data, 24);
+        let width = le32(data, 28);
+        if glyph_size != (width as usize).div_ceil(8) * height as usize {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let end = glyph_count
+            .checked_mul(glyph_size)
+            .and_then(|len| len.checked_add(header_size))
+            .ok_or(Status::INVALID_PARAMETER)?;
+        let glyphs = data
+            .get(header_size..end)
+            .ok_or(Status::INVALID_PARAMETER)?;
+
+        let unicode = if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+            let mut map = BTreeMap::new();
+            let mut entries = data[end..].split(|&byte| byte == PSF2_SEPARATOR);
+            for glyph in 0..glyph_count {
+                let entry = entries.next().unwrap_or_default();
+                // Sequences of combined characters follow the single characters
+                let singles = entry
+                    .split(|&byte| byte == PSF2_START_SEQUENCE)
+                    .next()
+                    .unwrap_or_default();
+                for c in str::from_utf8(singles).unwrap_or_default().chars() {
+                    map.entry(c).or_insert(glyph);
+                }
+            }
+            Some(map)
+        } else {
+            None
+        };
+
+        Self::new(width, height, glyph_count, glyphs, unicode)
+    }
+
+    fn new(
+        width: u32,
+        height: u32,
+        glyph_count: usize,
+        glyphs: &[u8],
+        unicode: Option<BTreeMap<char, usize>>,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 || glyph_count == 0 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        Ok(Font {
+            width,
+            height,
+            pitch: (width as usize).div_ceil(8),
+            glyph_count,
+            glyphs: glyphs.to_vec(),
+            unicode,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.glyph_count
+    }
+
+    /// The glyph index of `c`, if the font has one
+    pub fn index(&self, c: char) -> Option<usize> {
+        match &self.unicode {
+            Some(map) => map.get(&c).copied(),
+            None => Some(c as usize).filter(|&index| index < self.glyph_count),
+        }
+    }
+
+    /// Returns true if the font has a glyph for `c`
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.index(c).is_some()
+    }
+
+    /// The glyph of `c`, or of a replacement character if the font lacks it
+    pub fn glyph(&self, c: char) -> Glyph<'_> {
+        let index = self
+            .index(c)
+            .or_else(|| self.index(REPLACEMENT))
+            .or_else(|| self.index('?'))
+            .unwrap_or(0);
+        let size = self.pitch * self.height as usize;
+        Glyph {
+            font: self,
+            data: &self.glyphs[index * size..][..size],
+        }
+    }
+
+    /// Draw `c` with its top left corner at `point`, leaving the background
+    /// unchanged if `bg` is `None`
+    pub fn draw_char<S: Surface + ?Sized>(
+        &self,
+        surface: &mut S,
+        point: Point,
+        c: char,
+        fg: GraphicsBltPixel,
+        bg: Option<GraphicsBltPixel>,
+    ) {
+        let glyph = self.glyph(c);
+        let Some(bg) = bg else {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    if glyph.pixel(x, y) {
+                        surface.set_pixel(Point::new(point.x + x, point.y + y), fg);
+                    }
+                }
+            }
+            return;
+        };
+
+        let pixels: Vec<GraphicsBltPixel> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| if glyph.pixel(x, y) { fg } else { bg })
+            .collect();
+        let rect = Rect::new(0, 0, self.width, self.height);
+        let _ = surface.draw(&pixels, rect, point, self.width as usize);
+    }
+
+    /// Draw `text` on a single line starting at `point`, returning the point
+    /// after the last character
+    pub fn draw_str<S: Surface + ?Sized>(
+        &self,
+        surface: &mut S,
+        point: Point,
+        text: &str,
+        fg: GraphicsBltPixel,
+        bg: Option<GraphicsBltPixel>,
+    ) -> Point {
+        let mut point = point;
+        for c in text.chars() {
+            self.draw_char(surface, point, c, fg, bg);
+            point.x = point.x.saturating_add(self.width);
+        }
+        point
+    }
+}
+
+/// The bitmap of a character
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph<'a> {
+    font: &'a Font,
+    data: &'a [u8],
+}
+
+impl<'a> Glyph<'a> {
+    /// Returns true if the pixel at `x`, `y` is set
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        if x >= self.font.width || y >= self.font.height {
+            return false;
+        }
+        let byte = self.data[y as usize * self.font.pitch + x as usize / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    /// The rows of the bitmap, each `(width + 7) / 8` bytes with the leftmost
+    /// pixel in the high bit
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_covers_latin1_and_box_drawing() {
+        let font = Font::builtin();
+        assert_eq!((font.width(), font.height()), (8, 16));
+        for c in (' '..='~').chain('\u{A0}'..='\u{FF}') {
+            assert!(font.has_glyph(c), "{:?}", c);
+        }
+        for c in "─│┌┐└┘├┤┬┴┼▒◆≤≥π≠".chars() {
+            assert!(font.has_glyph(c), "{:?}", c);
+        }
+        assert_eq!(font.index('\u{FFFD}'), Some(0));
+        assert!(!font.has_glyph('\u{2603}'));
+    }
+
+    #[test]
+    fn missing_characters_use_replacement() {
+        let font = Font::builtin();
+        assert_eq!(font.glyph('\u{2603}').data(), font.glyph('\u{FFFD}').data());
+    }
+
+    #[test]
+    fn glyph_bitmap() {
+        let font = Font::builtin();
+        // The horizontal line is a single row through the middle of the cell
+        let line = font.glyph('─');
+        for y in 0..16 {
+            for x in 0..8 {
+                assert_eq!(line.pixel(x, y), y == 8, "{}, {}", x, y);
+            }
+        }
+        assert!(!line.pixel(8, 8));
+    }
+}
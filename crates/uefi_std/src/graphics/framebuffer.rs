@@ -0,0 +1,475 @@
+use core::ptr;
+use uefi::graphics::GraphicsBltPixel;
+
+use super::{FramebufferInfo, Gop, PixelFormat, Point, Rect};
+use crate::prelude::*;
+
+/// The position and width of a color channel in a pixel
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Channel {
+    shift: u32,
+    bits: u32,
+}
+
+impl Channel {
+    fn new(mask: u32) -> Self {
+        if mask == 0 {
+            return Channel { shift: 0, bits: 0 };
+        }
+        let shift = mask.trailing_zeros();
+        Channel {
+            shift,
+            bits: (mask >> shift).trailing_ones(),
+        }
+    }
+
+    fn max(&self) -> u64 {
+        (1u64 << self.bits) - 1
+    }
+
+    /// Scale an 8 bit intensity to the channel
+    fn encode(&self, value: u8) -> u32 {
+        let value = (u64::from(value) * self.max() + 127) / 255;
+        (value as u32) << self.shift
+    }
+
+    /// Scale the channel of `pixel` to an 8 bit intensity
+    fn decode(&self, pixel: u32) -> u8 {
+        if self.bits == 0 {
+            return 0;
+        }
+        let value = u64::from(pixel >> self.shift) & self.max();
+        ((value * 255 + self.max() / 2) / self.max()) as u8
+    }
+}
+
+/// A linear framebuffer of 8, 16, 24 or 32 bit pixels
+///
+/// No boot services are used, so a framebuffer obtained before
+/// ExitBootServices can still be drawn on afterwards. Drawing is clipped to
+/// the screen.
+pub struct Framebuffer {
+    base: *mut u8,
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: PixelFormat,
+    bytes_per_pixel: usize,
+    red: Channel,
+    green: Channel,
+    blue: Channel,
+}
+
+impl Framebuffer {
+    /// Use the framebuffer described by `info`
+    ///
+    /// # Safety
+    ///
+    /// The framebuffer must stay mapped at `info.base` for the lifetime of the
+    /// returned value.
+    pub unsafe fn new(info: FramebufferInfo) -> Result<Self> {
+        let bytes_per_pixel = info.format.bytes_per_pixel().ok_or(Status::UNSUPPORTED)?;
+        let needed = u64::from(info.stride) * u64::from(info.height) * bytes_per_pixel as u64;
+        if (info.size as u64) < needed {
+            return Err(Status::BAD_BUFFER_SIZE);
+        }
+        Self::from_raw_parts(
+            info.base as *mut u8,
+            info.width,
+            info.height,
+            info.stride,
+            info.format,
+        )
+    }
+
+    /// Use `stride * height` pixels at `base`, which may be ordinary memory
+    ///
+    /// # Safety
+    ///
+    /// `base` must be valid for reads and writes of `stride * height` pixels
+    /// of [`PixelFormat::bytes_per_pixel`] for the lifetime of the returned
+    /// value, and aligned to the size of a pixel if that is 2 or 4 bytes.
+    pub unsafe fn from_raw_parts(
+        base: *mut u8,
+        width: u32,
+        height: u32,
+        stride: u32,
+        format: PixelFormat,
+    ) -> Result<Self> {
+        if base.is_null() || stride < width {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let (red, green, blue) = match format {
+            PixelFormat::Rgb => (0xFF, 0xFF00, 0xFF0000),
+            PixelFormat::Bgr => (0xFF0000, 0xFF00, 0xFF),
+            PixelFormat::Bitmask {
+                red, green, blue, ..
+            } => (red, green, blue),
+            PixelFormat::BltOnly => return Err(Status::UNSUPPORTED),
+        };
+        let bytes_per_pixel = format.bytes_per_pixel().ok_or(Status::UNSUPPORTED)?;
+
+        Ok(Framebuffer {
+            base,
+            width,
+            height,
+            stride,
+            format,
+            bytes_per_pixel,
+            red: Channel::new(red),
+            green: Channel::new(green),
+            blue: Channel::new(blue),
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of pixels in a line
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// Convert a color to the pixel format of the framebuffer
+    pub fn encode(&self, pixel: GraphicsBltPixel) -> u32 {
+        match self.format {
+            PixelFormat::Bgr => u32::from_le_bytes([pixel.Blue, pixel.Green, pixel.Red, 0]),
+            PixelFormat::Rgb => u32::from_le_bytes([pixel.Red, pixel.Green, pixel.Blue, 0]),
+            _ => {
+                self.red.encode(pixel.Red)
+                    | self.green.encode(pixel.Green)
+                    | self.blue.encode(pixel.Blue)
+            }
+        }
+    }
+
+    /// Convert a pixel from the framebuffer to a color
+    pub fn decode(&self, value: u32) -> GraphicsBltPixel {
+        GraphicsBltPixel::rgb(
+            self.red.decode(value),
+            self.green.decode(value),
+            self.blue.decode(value),
+        )
+    }
+
+    /// The address of `point`, which must be on screen
+    fn offset(&self, point: Point) -> *mut u8 {
+        let offset = point.y as usize * self.stride as usize + point.x as usize;
+        unsafe { self.base.add(offset * self.bytes_per_pixel) }
+    }
+
+    /// Read the pixel at `pixel`, which must be on screen
+    unsafe fn read(&self, pixel: *mut u8) -> u32 {
+        match self.bytes_per_pixel {
+            4 => ptr::read_volatile(pixel as *mut u32),
+            2 => u32::from(ptr::read_volatile(pixel as *mut u16)),
+            len => {
+                let mut bytes = [0; 4];
+                for (index, byte) in bytes[..len].iter_mut().enumerate() {
+                    *byte = ptr::read_volatile(pixel.add(index));
+                }
+                u32::from_le_bytes(bytes)
+            }
+        }
+    }
+
+    /// Write `value` to the pixel at `pixel`, which must be on screen
+    unsafe fn write(&self, pixel: *mut u8, value: u32) {
+        match self.bytes_per_pixel {
+            4 => ptr::write_volatile(pixel as *mut u32, value),
+            2 => ptr::write_volatile(pixel as *mut u16, value as u16),
+            len => {
+                for (index, byte) in value.to_le_bytes()[..len].iter().enumerate() {
+                    ptr::write_volatile(pixel.add(index), *byte);
+                }
+            }
+        }
+    }
+
+    pub fn pixel(&self, point: Point) -> Option<GraphicsBltPixel> {
+        if !self.bounds().contains(point) {
+            return None;
+        }
+        let value = unsafe { self.read(self.offset(point)) };
+        Some(self.decode(value))
+    }
+
+    pub fn set_pixel(&mut self, point: Point, pixel: GraphicsBltPixel) {
+        if self.bounds().contains(point) {
+            unsafe { self.write(self.offset(point), self.encode(pixel)) };
+        }
+    }
+
+    /// Fill `rect` with `pixel`
+    pub fn fill(&mut self, rect: Rect, pixel: GraphicsBltPixel) {
+        let rect = rect.intersection(&self.bounds());
+        if rect.is_empty() {
+            return;
+        }
+
+        let value = self.encode(pixel);
+        for y in rect.y..rect.bottom() as u32 {
+            let line = self.offset(Point::new(rect.x, y));
+            for column in 0..rect.width as usize {
+                unsafe { self.write(line.add(column * self.bytes_per_pixel), value) };
+            }
+        }
+    }
+
+    pub fn clear(&mut self, pixel: GraphicsBltPixel) {
+        self.fill(self.bounds(), pixel);
+    }
+
+    /// Draw the `src` area of `buffer`, which has lines of `stride` pixels, at
+    /// `dst`
+    pub fn draw(
+        &mut self,
+        buffer: &[GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()> {
+        if src.right() > stride as u64 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        let area = Rect::new(dst.x, dst.y, src.width, src.height).intersection(&self.bounds());
+        if area.is_empty() {
+            return Ok(());
+        }
+
+        let x = (src.x + area.x - dst.x) as usize;
+        let y = (src.y + area.y - dst.y) as usize;
+        let width = area.width as usize;
+        let end = (y + area.height as usize - 1) * stride + x + width;
+        if end > buffer.len() {
+            return Err(Status::BAD_BUFFER_SIZE);
+        }
+
+        for row in 0..area.height as usize {
+            let source = &buffer[(y + row) * stride + x..][..width];
+            let line = self.offset(Point::new(area.x, area.y + row as u32));
+            for (column, pixel) in source.iter().enumerate() {
+                let offset = column * self.bytes_per_pixel;
+                unsafe { self.write(line.add(offset), self.encode(*pixel)) };
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the contents of `rect` by `dx` and `dy` pixels, clipped to the
+    /// screen. The area left behind keeps its old contents.
+    pub fn copy_within(&mut self, rect: Rect, dx: i32, dy: i32) {
+        let rect = rect.intersection(&self.bounds());
+        let moved = |start: u32, offset: i32| i64::from(start) + i64::from(offset);
+        let (x, y) = (moved(rect.x, dx), moved(rect.y, dy));
+
+        // Clip the destination, then shrink the source to match
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + i64::from(rect.width)).min(i64::from(self.width));
+        let bottom = (y + i64::from(rect.height)).min(i64::from(self.height));
+        if right <= left || bottom <= top {
+            return;
+        }
+
+        let width = (right - left) as usize;
+        let height = (bottom - top) as u32;
+        let src_x = (left - i64::from(dx)) as u32;
+        let src_y = (top - i64::from(dy)) as u32;
+        let dst_x = left as u32;
+        let dst_y = top as u32;
+
+        let copy_line = |row: u32| unsafe {
+            let src = self.offset(Point::new(src_x, src_y + row));
+            let dst = self.offset(Point::new(dst_x, dst_y + row));
+            ptr::copy(src, dst, width * self.bytes_per_pixel);
+        };
+        // Copy lines in an order that does not overwrite lines yet to be read
+        if dy > 0 {
+            (0..height).rev().for_each(copy_line);
+        } else {
+            (0..height).for_each(copy_line);
+        }
+    }
+
+    /// Scroll the screen up by `lines`, filling the lines uncovered at the
+    /// bottom with `pixel`
+    pub fn scroll_up(&mut self, lines: u32, pixel: GraphicsBltPixel) {
+        let lines = lines.min(self.height);
+        self.copy_within(self.bounds(), 0, -(lines as i32));
+        self.fill(Rect::new(0, self.height - lines, self.width, lines), pixel);
+    }
+
+    /// Scroll the screen down by `lines`, filling the lines uncovered at the
+    /// top with `pixel`
+    pub fn scroll_down(&mut self, lines: u32, pixel: GraphicsBltPixel) {
+        let lines = lines.min(self.height);
+        self.copy_within(self.bounds(), 0, lines as i32);
+        self.fill(Rect::new(0, 0, self.width, lines), pixel);
+    }
+}
+
+impl Gop {
+    /// The framebuffer of the current mode, which remains usable after
+    /// ExitBootServices until the mode is changed
+    pub fn framebuffer(&self) -> Result<Framebuffer> {
+        let info = self.framebuffer_info().ok_or(Status::UNSUPPORTED)?;
+        unsafe { Framebuffer::new(info) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: GraphicsBltPixel = GraphicsBltPixel::rgb(255, 255, 255);
+    const RED: GraphicsBltPixel = GraphicsBltPixel::rgb(255, 0, 0);
+
+    /// A 4 by 3 framebuffer with lines of 5 pixels in `memory`
+    fn framebuffer(memory: &mut [u32], format: PixelFormat) -> Framebuffer {
+        assert!(memory.len() * 4 >= 5 * 3 * format.bytes_per_pixel().unwrap());
+        unsafe { Framebuffer::from_raw_parts(memory.as_mut_ptr().cast(), 4, 3, 5, format).unwrap() }
+    }
+
+    /// The pixels of `framebuffer` as `+` for white and `.` otherwise
+    fn picture(framebuffer: &Framebuffer) -> String {
+        let mut picture = String::new();
+        for y in 0..framebuffer.height() {
+            for x in 0..framebuffer.width() {
+                let white = framebuffer.pixel(Point::new(x, y)) == Some(WHITE);
+                picture.push(if white { '+' } else { '.' });
+            }
+            picture.push('\n');
+        }
+        picture
+    }
+
+    #[test]
+    fn encode_rgb_and_bgr() {
+        let mut memory = [0; 15];
+        let rgb = framebuffer(&mut memory, PixelFormat::Rgb);
+        assert_eq!(rgb.encode(GraphicsBltPixel::rgb(1, 2, 3)), 0x030201);
+        assert_eq!(rgb.decode(0x030201), GraphicsBltPixel::rgb(1, 2, 3));
+
+        let bgr = framebuffer(&mut memory, PixelFormat::Bgr);
+        assert_eq!(bgr.encode(GraphicsBltPixel::rgb(1, 2, 3)), 0x010203);
+        assert_eq!(bgr.decode(0x010203), GraphicsBltPixel::rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn encode_bitmask() {
+        let rgb565 = PixelFormat::Bitmask {
+            red: 0xF800,
+            green: 0x07E0,
+            blue: 0x001F,
+            reserved: 0,
+        };
+        assert_eq!(rgb565.bytes_per_pixel(), Some(2));
+        let mut memory = [0; 15];
+        let mut framebuffer = framebuffer(&mut memory, rgb565);
+        assert_eq!(framebuffer.encode(RED), 0xF800);
+        assert_eq!(
+            framebuffer.encode(GraphicsBltPixel::rgb(0, 128, 255)),
+            0x041F
+        );
+        assert_eq!(framebuffer.decode(0x07E0), GraphicsBltPixel::rgb(0, 255, 0));
+
+        // Pixels are two bytes apart
+        framebuffer.set_pixel(Point::new(1, 0), RED);
+        framebuffer.set_pixel(Point::new(0, 1), WHITE);
+        assert_eq!(framebuffer.pixel(Point::new(1, 0)), Some(RED));
+        assert_eq!(memory[0], 0xF800_0000);
+        // The second line starts 5 pixels, or 10 bytes, in
+        assert_eq!(memory[2], 0xFFFF_0000);
+    }
+
+    #[test]
+    fn bitmask_24_bit() {
+        let format = PixelFormat::Bitmask {
+            red: 0xFF0000,
+            green: 0xFF00,
+            blue: 0xFF,
+            reserved: 0,
+        };
+        assert_eq!(format.bytes_per_pixel(), Some(3));
+        let mut memory = [0; 12];
+        let mut framebuffer = framebuffer(&mut memory, format);
+        framebuffer.fill(Rect::new(0, 0, 2, 1), GraphicsBltPixel::rgb(1, 2, 3));
+        assert_eq!(
+            framebuffer.pixel(Point::new(1, 0)),
+            Some(GraphicsBltPixel::rgb(1, 2, 3))
+        );
+        assert_eq!(memory[0], 0x03_010203);
+        assert_eq!(memory[1], 0x0102);
+    }
+
+    #[test]
+    fn fill_is_clipped() {
+        let mut memory = [0; 15];
+        let mut framebuffer = framebuffer(&mut memory, PixelFormat::Bgr);
+        framebuffer.fill(Rect::new(2, 1, 10, 10), WHITE);
+        framebuffer.fill(Rect::new(4, 0, 1, 1), WHITE);
+        assert_eq!(picture(&framebuffer), "....\n..++\n..++\n");
+        // The padding at the end of each line is untouched
+        assert_eq!(memory[9], 0);
+        assert_eq!(memory[4], 0);
+    }
+
+    #[test]
+    fn draw_is_clipped() {
+        let mut memory = [0; 15];
+        let mut framebuffer = framebuffer(&mut memory, PixelFormat::Rgb);
+        let buffer = [WHITE, RED, RED, WHITE];
+        framebuffer
+            .draw(&buffer, Rect::new(0, 0, 2, 2), Point::new(3, 2), 2)
+            .unwrap();
+        assert_eq!(picture(&framebuffer), "....\n....\n...+\n");
+        framebuffer
+            .draw(&buffer, Rect::new(1, 0, 1, 2), Point::new(0, 0), 2)
+            .unwrap();
+        assert_eq!(framebuffer.pixel(Point::new(0, 0)), Some(RED));
+        assert_eq!(picture(&framebuffer), "....\n+...\n...+\n");
+
+        assert_eq!(
+            framebuffer.draw(&buffer, Rect::new(1, 0, 2, 1), Point::new(0, 0), 2),
+            Err(Status::INVALID_PARAMETER)
+        );
+        assert_eq!(
+            framebuffer.draw(&buffer, Rect::new(0, 0, 2, 3), Point::new(0, 0), 2),
+            Err(Status::BAD_BUFFER_SIZE)
+        );
+    }
+
+    #[test]
+    fn copy_within_is_clipped() {
+        let mut memory = [0; 15];
+        let mut framebuffer = framebuffer(&mut memory, PixelFormat::Bgr);
+        framebuffer.fill(Rect::new(0, 0, 2, 1), WHITE);
+        framebuffer.set_pixel(Point::new(0, 1), WHITE);
+        assert_eq!(picture(&framebuffer), "++..\n+...\n....\n");
+
+        framebuffer.copy_within(Rect::new(0, 0, 2, 2), 3, 1);
+        assert_eq!(picture(&framebuffer), "++..\n+..+\n...+\n");
+
+        framebuffer.copy_within(framebuffer.bounds(), -1, -1);
+        assert_eq!(picture(&framebuffer), "..+.\n..++\n...+\n");
+
+        framebuffer.scroll_down(1, RED);
+        assert_eq!(picture(&framebuffer), "....\n..+.\n..++\n");
+        assert_eq!(framebuffer.pixel(Point::new(0, 0)), Some(RED));
+    }
+}
@@ -0,0 +1,238 @@
+use core::ptr;
+use uefi::graphics::{GraphicsOutput, GraphicsOutputModeInfo};
+
+use super::PixelFormat;
+use crate::edid::{Edid, EdidActive, EdidDiscovered};
+use crate::prelude::*;
+use crate::proto::Protocol;
+use crate::system_table;
+
+/// A display mode
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ModeInfo {
+    /// The number passed to [`Gop::set_mode`]
+    pub mode: u32,
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    /// The number of pixels in a line of the framebuffer, which may be more
+    /// than `width`
+    pub stride: u32,
+}
+
+impl ModeInfo {
+    fn new(mode: u32, info: &GraphicsOutputModeInfo) -> Self {
+        ModeInfo {
+            mode,
+            width: info.HorizontalResolution,
+            height: info.VerticalResolution,
+            format: PixelFormat::new(info),
+            stride: info.PixelsPerScanLine,
+        }
+    }
+
+    pub fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The number of pixels on screen
+    pub fn area(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+}
+
+/// The linear framebuffer of the current mode
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FramebufferInfo {
+    pub base: usize,
+    /// The size in bytes
+    pub size: usize,
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    /// The number of pixels in a line
+    pub stride: u32,
+}
+
+/// How [`Gop::best_mode`] chooses a mode
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ModePolicy {
+    /// The preferred resolution of the display from its EDID, or the largest
+    /// mode if that is unknown or unavailable
+    Native,
+    /// The mode with the most pixels
+    Largest,
+    /// The given resolution, or the largest mode that fits within it, or
+    /// failing that the smallest mode
+    Closest(u32, u32),
+}
+
+pub struct Gop(pub &'static mut GraphicsOutput);
+
+impl Protocol<GraphicsOutput> for Gop {
+    fn guid() -> Guid {
+        GraphicsOutput::GUID
+    }
+
+    fn new(inner: &'static mut GraphicsOutput) -> Self {
+        Gop(inner)
+    }
+}
+
+impl Gop {
+    pub fn mode_count(&self) -> u32 {
+        self.0.Mode.MaxMode
+    }
+
+    pub fn current_mode(&self) -> ModeInfo {
+        ModeInfo::new(self.0.Mode.Mode, self.0.Mode.Info)
+    }
+
+    pub fn query_mode(&mut self, mode: u32) -> Result<ModeInfo> {
+        let mut size = 0;
+        let mut info = ptr::null_mut();
+        let status = (self.0.QueryMode)(self.0, mode, &mut size, &mut info);
+        if status != Status::SUCCESS {
+            return Err(status);
+        }
+
+        let mode_info = ModeInfo::new(mode, unsafe { &*info });
+        let _ = (system_table().BootServices.FreePool)(info as usize);
+        Ok(mode_info)
+    }
+
+    /// Every mode the display supports. Modes that firmware fails to describe
+    /// are skipped.
+    pub fn modes(&mut self) -> Modes<'_> {
+        Modes { gop: self, mode: 0 }
+    }
+
+    pub fn set_mode(&mut self, mode: u32) -> Result<()> {
+        (self.0.SetMode)(self.0, mode).into()
+    }
+
+    /// Switch to the first mode with the resolution `width` by `height`
+    pub fn set_resolution(&mut self, width: u32, height: u32) -> Result<ModeInfo> {
+        let info = self
+            .modes()
+            .find(|info| info.resolution() == (width, height))
+            .ok_or(Status::UNSUPPORTED)?;
+        self.set_mode(info.mode)?;
+        Ok(info)
+    }
+
+    /// Choose a mode according to `policy`
+    pub fn best_mode(&mut self, policy: ModePolicy) -> Option<ModeInfo> {
+        let modes: Vec<ModeInfo> = self.modes().collect();
+        let largest = || {
+            modes
+                .iter()
+                .max_by_key(|info| (info.area(), info.width))
+                .copied()
+        };
+
+        match policy {
+            ModePolicy::Native => self
+                .native_resolution()
+                .and_then(|resolution| {
+                    modes
+                        .iter()
+                        .find(|info| info.resolution() == resolution)
+                        .copied()
+                })
+                .or_else(largest),
+            ModePolicy::Largest => largest(),
+            ModePolicy::Closest(width, height) => {
+                if let Some(info) = modes
+                    .iter()
+                    .find(|info| info.resolution() == (width, height))
+                {
+                    return Some(*info);
+                }
+                modes
+                    .iter()
+                    .filter(|info| info.width <= width && info.height <= height)
+                    .max_by_key(|info| (info.area(), info.width))
+                    .or_else(|| modes.iter().min_by_key(|info| (info.area(), info.width)))
+                    .copied()
+            }
+        }
+    }
+
+    /// Switch to the mode chosen by `policy`, unless it is already active
+    pub fn set_best_mode(&mut self, policy: ModePolicy) -> Result<ModeInfo> {
+        let info = self.best_mode(policy).ok_or(Status::NOT_FOUND)?;
+        if info.mode != self.0.Mode.Mode {
+            self.set_mode(info.mode)?;
+        }
+        Ok(self.current_mode())
+    }
+
+    /// The EDID of the attached display, preferring the one the driver uses
+    /// over the one read from the display
+    pub fn edid(&self) -> Result<Edid> {
+        let handle = self.handle().ok_or(Status::NOT_FOUND)?;
+        match EdidActive::handle_protocol(handle) {
+            Ok(active) => active.parse(),
+            Err(_) => EdidDiscovered::handle_protocol(handle)?.parse(),
+        }
+    }
+
+    /// The preferred resolution from the EDID of the attached display
+    pub fn native_resolution(&self) -> Option<(u32, u32)> {
+        self.edid().ok()?.native_resolution()
+    }
+
+    /// The framebuffer of the current mode, if it has one
+    pub fn framebuffer_info(&self) -> Option<FramebufferInfo> {
+        let mode = &self.0.Mode;
+        let info = self.current_mode();
+        if info.format == PixelFormat::BltOnly || mode.FrameBufferBase == 0 {
+            return None;
+        }
+
+        Some(FramebufferInfo {
+            base: mode.FrameBufferBase,
+            size: mode.FrameBufferSize,
+            width: info.width,
+            height: info.height,
+            format: info.format,
+            stride: info.stride,
+        })
+    }
+
+    /// The handle this instance is installed on
+    fn handle(&self) -> Option<Handle> {
+        let this = &*self.0 as *const GraphicsOutput as usize;
+        Self::locate_handle().ok()?.into_iter().find(|&handle| {
+            let mut interface = 0;
+            let status = (system_table().BootServices.HandleProtocol)(
+                handle,
+                &GraphicsOutput::GUID,
+                &mut interface,
+            );
+            status == Status::SUCCESS && interface == this
+        })
+    }
+}
+
+/// An iterator over the modes of a display
+pub struct Modes<'a> {
+    gop: &'a mut Gop,
+    mode: u32,
+}
+
+impl<'a> Iterator for Modes<'a> {
+    type Item = ModeInfo;
+
+    fn next(&mut self) -> Option<ModeInfo> {
+        while self.mode < self.gop.mode_count() {
+            let mode = self.mode;
+            self.mode += 1;
+            if let Ok(info) = self.gop.query_mode(mode) {
+                return Some(info);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,253 @@
+//! Graphics output: display modes and drawing
+
+use uefi::graphics::{GraphicsOutputModeInfo, GraphicsPixelFormat};
+
+pub use self::canvas::Canvas;
+pub use self::font::{Font, Glyph};
+pub use self::framebuffer::Framebuffer;
+pub use self::gop::{FramebufferInfo, Gop, ModeInfo, ModePolicy, Modes};
+pub use self::terminal::Terminal;
+pub use uefi::graphics::GraphicsBltPixel;
+
+use crate::prelude::*;
+
+mod blt;
+mod canvas;
+mod font;
+mod framebuffer;
+mod gop;
+mod terminal;
+
+/// Something that can be drawn on, such as a [`Canvas`] or a [`Framebuffer`]
+///
+/// Drawing is clipped to the bounds of the surface.
+pub trait Surface {
+    fn bounds(&self) -> Rect;
+
+    fn set_pixel(&mut self, point: Point, pixel: GraphicsBltPixel);
+
+    fn fill(&mut self, rect: Rect, pixel: GraphicsBltPixel);
+
+    /// Draw the `src` area of `buffer`, which has lines of `stride` pixels, at
+    /// `dst`
+    fn draw(
+        &mut self,
+        buffer: &[GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()>;
+
+    /// Move the contents of `rect` by `dx` and `dy` pixels. The area left
+    /// behind keeps its old contents.
+    fn copy_within(&mut self, rect: Rect, dx: i32, dy: i32);
+}
+
+impl Surface for Canvas {
+    fn bounds(&self) -> Rect {
+        Canvas::bounds(self)
+    }
+
+    fn set_pixel(&mut self, point: Point, pixel: GraphicsBltPixel) {
+        Canvas::set_pixel(self, point, pixel)
+    }
+
+    fn fill(&mut self, rect: Rect, pixel: GraphicsBltPixel) {
+        Canvas::fill(self, rect, pixel)
+    }
+
+    fn draw(
+        &mut self,
+        buffer: &[GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()> {
+        Canvas::draw(self, buffer, src, dst, stride)
+    }
+
+    fn copy_within(&mut self, rect: Rect, dx: i32, dy: i32) {
+        Canvas::copy_within(self, rect, dx, dy)
+    }
+}
+
+impl Surface for Framebuffer {
+    fn bounds(&self) -> Rect {
+        Framebuffer::bounds(self)
+    }
+
+    fn set_pixel(&mut self, point: Point, pixel: GraphicsBltPixel) {
+        Framebuffer::set_pixel(self, point, pixel)
+    }
+
+    fn fill(&mut self, rect: Rect, pixel: GraphicsBltPixel) {
+        Framebuffer::fill(self, rect, pixel)
+    }
+
+    fn draw(
+        &mut self,
+        buffer: &[GraphicsBltPixel],
+        src: Rect,
+        dst: Point,
+        stride: usize,
+    ) -> Result<()> {
+        Framebuffer::draw(self, buffer, src, dst, stride)
+    }
+
+    fn copy_within(&mut self, rect: Rect, dx: i32, dy: i32) {
+        Framebuffer::copy_within(self, rect, dx, dy)
+    }
+}
+
+/// A position on the display or in a buffer, in pixels from the top left
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Point {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Point {
+    pub const fn new(x: u32, y: u32) -> Self {
+        Point { x, y }
+    }
+}
+
+/// A rectangle of pixels
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn origin(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The column after the last, which may overflow a `u32`
+    pub fn right(&self) -> u64 {
+        u64::from(self.x) + u64::from(self.width)
+    }
+
+    /// The row after the last, which may overflow a `u32`
+    pub fn bottom(&self) -> u64 {
+        u64::from(self.y) + u64::from(self.height)
+    }
+
+    /// Returns true if the rectangle lies within a `width` by `height` area
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        self.right() <= u64::from(width) && self.bottom() <= u64::from(height)
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.x
+            && point.y >= self.y
+            && u64::from(point.x) < self.right()
+            && u64::from(point.y) < self.bottom()
+    }
+
+    /// The overlap of two rectangles, which is empty if they do not overlap
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= u64::from(x) || bottom <= u64::from(y) {
+            return Rect::new(x, y, 0, 0);
+        }
+        Rect::new(
+            x,
+            y,
+            (right - u64::from(x)) as u32,
+            (bottom - u64::from(y)) as u32,
+        )
+    }
+
+    /// The smallest rectangle covering both, ignoring empty rectangles
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Rect::new(
+            x,
+            y,
+            (right - u64::from(x)).min(u64::from(u32::MAX)) as u32,
+            (bottom - u64::from(y)).min(u64::from(u32::MAX)) as u32,
+        )
+    }
+}
+
+/// The layout of a pixel in the framebuffer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PixelFormat {
+    /// 32 bits with red in byte zero
+    Rgb,
+    /// 32 bits with blue in byte zero
+    Bgr,
+    /// Each channel given by a mask, in pixels of as many bytes as the
+    /// highest bit of the masks needs
+    Bitmask {
+        red: u32,
+        green: u32,
+        blue: u32,
+        reserved: u32,
+    },
+    /// No framebuffer, so drawing is only possible with Blt
+    BltOnly,
+}
+
+impl PixelFormat {
+    fn new(info: &GraphicsOutputModeInfo) -> Self {
+        let masks = &info.PixelInformation;
+        match info.PixelFormat {
+            GraphicsPixelFormat::PixelRedGreenBlueReserved8BitPerColor => PixelFormat::Rgb,
+            GraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor => PixelFormat::Bgr,
+            GraphicsPixelFormat::PixelBitMask => PixelFormat::Bitmask {
+                red: masks.RedMask,
+                green: masks.GreenMask,
+                blue: masks.BlueMask,
+                reserved: masks.ReservedMask,
+            },
+            _ => PixelFormat::BltOnly,
+        }
+    }
+
+    /// The size of a pixel in the framebuffer, or `None` without one
+    pub fn bytes_per_pixel(&self) -> Option<usize> {
+        match *self {
+            PixelFormat::Rgb | PixelFormat::Bgr => Some(4),
+            PixelFormat::Bitmask {
+                red,
+                green,
+                blue,
+                reserved,
+            } => {
+                let bits = 32 - (red | green | blue | reserved).leading_zeros();
+                Some((bits as usize).div_ceil(8).max(1))
+            }
+            PixelFormat::BltOnly => None,
+        }
+    }
+}
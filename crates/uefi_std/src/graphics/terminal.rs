@@ -0,0 +1,193 @@
+use core::fmt;
+use uefi::graphics::GraphicsBltPixel;
+
+use super::{Font, Rect, Surface};
+
+/// Tab stops are this many columns apart
+const TAB_WIDTH: u32 = 8;
+
+/// A grid of character cells drawn with a bitmap font, which scrolls when
+/// text runs past the last row
+///
+/// Output goes straight to the surface. Draw on a [`Canvas`](super::Canvas)
+/// and flush it to avoid the cost of writing to video memory a glyph at a
+/// time.
+pub struct Terminal<S: Surface> {
+    surface: S,
+    font: Font,
+    /// The area covered by the grid
+    area: Rect,
+    columns: u32,
+    rows: u32,
+    column: u32,
+    row: u32,
+    fg: GraphicsBltPixel,
+    bg: GraphicsBltPixel,
+}
+
+impl<S: Surface> Terminal<S> {
+    /// A terminal filling `surface`, with light gray text on black
+    pub fn new(surface: S, font: Font) -> Self {
+        let area = surface.bounds();
+        Self::with_area(surface, font, area)
+    }
+
+    /// A terminal covering `area` of `surface`
+    pub fn with_area(surface: S, font: Font, area: Rect) -> Self {
+        let area = area.intersection(&surface.bounds());
+        let columns = area.width / font.width();
+        let rows = area.height / font.height();
+        Terminal {
+            surface,
+            area: Rect::new(area.x, area.y, columns * font.width(), rows * font.height()),
+            font,
+            columns,
+            rows,
+            column: 0,
+            row: 0,
+            fg: GraphicsBltPixel::rgb(0xAA, 0xAA, 0xAA),
+            bg: GraphicsBltPixel::rgb(0, 0, 0),
+        }
+    }
+
+    pub fn surface(&self) -> &S {
+        &self.surface
+    }
+
+    pub fn surface_mut(&mut self) -> &mut S {
+        &mut self.surface
+    }
+
+    pub fn into_inner(self) -> S {
+        self.surface
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// The column and row of the cursor
+    pub fn cursor(&self) -> (u32, u32) {
+        (self.column, self.row)
+    }
+
+    /// Move the cursor, clamping it to the grid
+    pub fn set_cursor(&mut self, column: u32, row: u32) {
+        self.column = column.min(self.columns.saturating_sub(1));
+        self.row = row.min(self.rows.saturating_sub(1));
+    }
+
+    pub fn colors(&self) -> (GraphicsBltPixel, GraphicsBltPixel) {
+        (self.fg, self.bg)
+    }
+
+    /// Set the colors of text written from now on
+    pub fn set_colors(&mut self, fg: GraphicsBltPixel, bg: GraphicsBltPixel) {
+        self.fg = fg;
+        self.bg = bg;
+    }
+
+    /// Fill the grid with the background color and move the cursor home
+    pub fn clear(&mut self) {
+        self.surface.fill(self.area, self.bg);
+        self.column = 0;
+        self.row = 0;
+    }
+
+    /// The pixel area of a cell
+    fn cell(&self, column: u32, row: u32) -> Rect {
+        Rect::new(
+            self.area.x + column * self.font.width(),
+            self.area.y + row * self.font.height(),
+            self.font.width(),
+            self.font.height(),
+        )
+    }
+
+    /// Draw `c` in a cell without moving the cursor
+    pub fn put_char(&mut self, column: u32, row: u32, c: char) {
+        if column < self.columns && row < self.rows {
+            let point = self.cell(column, row).origin();
+            self.font
+                .draw_char(&mut self.surface, point, c, self.fg, Some(self.bg));
+        }
+    }
+
+    /// Scroll the grid up a row, clearing the last
+    pub fn scroll(&mut self) {
+        let height = self.font.height();
+        self.surface.copy_within(
+            Rect::new(
+                self.area.x,
+                self.area.y + height,
+                self.area.width,
+                self.area.height - height,
+            ),
+            0,
+            -(height as i32),
+        );
+        let last = self.cell(0, self.rows - 1);
+        self.surface
+            .fill(Rect::new(last.x, last.y, self.area.width, height), self.bg);
+    }
+
+    fn newline(&mut self) {
+        self.column = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            self.scroll();
+        }
+    }
+
+    /// Write a character at the cursor, handling newline, carriage return,
+    /// tab and backspace
+    pub fn print_char(&mut self, c: char) {
+        if self.columns == 0 || self.rows == 0 {
+            return;
+        }
+
+        match c {
+            '\n' => self.newline(),
+            '\r' => self.column = 0,
+            '\t' => {
+                let next = (self.column / TAB_WIDTH + 1) * TAB_WIDTH;
+                if next >= self.columns {
+                    self.newline();
+                } else {
+                    self.column = next;
+                }
+            }
+            '\x08' => self.column = self.column.saturating_sub(1),
+            _ if c.is_control() => (),
+            _ => {
+                if self.column >= self.columns {
+                    self.newline();
+                }
+                self.put_char(self.column, self.row, c);
+                self.column += 1;
+            }
+        }
+    }
+
+    pub fn print(&mut self, text: &str) {
+        for c in text.chars() {
+            self.print_char(c);
+        }
+    }
+}
+
+impl<S: Surface> fmt::Write for Terminal<S> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.print(text);
+        Ok(())
+    }
+}
@@ -0,0 +1,522 @@
+use uefi::graphics::GraphicsBltPixel;
+
+use super::{rgba, Image};
+use crate::bytes::{le16, le32};
+use crate::prelude::*;
+
+const FILE_HEADER_SIZE: usize = 14;
+const CORE_HEADER_SIZE: usize = 12;
+const INFO_HEADER_SIZE: usize = 40;
+
+// Compression methods
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// A channel given by a bit mask
+#[derive(Clone, Copy)]
+struct Mask {
+    shift: u32,
+    max: u32,
+}
+
+impl Mask {
+    fn new(mask: u32) -> Self {
+        if mask == 0 {
+            return Mask { shift: 0, max: 0 };
+        }
+        let shift = mask.trailing_zeros();
+        let bits = (mask >> shift).trailing_ones();
+        Mask {
+            shift,
+            max: ((1u64 << bits) - 1) as u32,
+        }
+    }
+
+    fn get(&self, value: u32) -> Option<u8> {
+        if self.max == 0 {
+            return None;
+        }
+        let value = (value >> self.shift) & self.max;
+        Some(((u64::from(value) * 255 + u64::from(self.max) / 2) / u64::from(self.max)) as u8)
+    }
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    top_down: bool,
+    bpp: u16,
+    compression: u32,
+    /// Red, green, blue and alpha masks
+    masks: [u32; 4],
+    palette: Vec<[u8; 4]>,
+}
+
+fn parse_header(data: &[u8]) -> Result<Header> {
+    if data.len() < FILE_HEADER_SIZE + CORE_HEADER_SIZE {
+        return Err(Status::INVALID_PARAMETER);
+    }
+    let header_size = le32(data, FILE_HEADER_SIZE) as usize;
+    let header = data
+        .get(FILE_HEADER_SIZE..FILE_HEADER_SIZE.saturating_add(header_size))
+        .ok_or(Status::INVALID_PARAMETER)?;
+
+    let (width, height, bpp, compression, colors) = match header_size {
+        CORE_HEADER_SIZE => (
+            i32::from(le16(header, 4)),
+            i32::from(le16(header, 6)),
+            le16(header, 10),
+            BI_RGB,
+            0,
+        ),
+        size if size >= INFO_HEADER_SIZE => (
+            le32(header, 4) as i32,
+            le32(header, 8) as i32,
+            le16(header, 14),
+            le32(header, 16),
+            le32(header, 32) as usize,
+        ),
+        _ => return Err(Status::UNSUPPORTED),
+    };
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err(Status::INVALID_PARAMETER);
+    }
+
+    // Masks follow the header unless it is large enough to hold them
+    let mut offset = FILE_HEADER_SIZE + header_size;
+    let mut masks = match bpp {
+        16 => [0x7C00, 0x03E0, 0x001F, 0],
+        _ => [0xFF0000, 0xFF00, 0xFF, 0],
+    };
+    match compression {
+        BI_RGB | BI_RLE8 | BI_RLE4 => (),
+        BI_BITFIELDS | BI_ALPHABITFIELDS => {
+            let count = if compression == BI_BITFIELDS { 3 } else { 4 };
+            let (fields, start) = if header_size >= INFO_HEADER_SIZE + 4 * count {
+                (header, INFO_HEADER_SIZE)
+            } else {
+                offset += 4 * count;
+                (data, FILE_HEADER_SIZE + header_size)
+            };
+            let fields = fields
+                .get(start..start + 4 * count)
+                .ok_or(Status::INVALID_PARAMETER)?;
+            for (index, mask) in masks.iter_mut().take(count).enumerate() {
+                *mask = le32(fields, index * 4);
+            }
+        }
+        _ => return Err(Status::UNSUPPORTED),
+    }
+    // Version 4 and later headers always carry an alpha mask
+    if header_size >= 56 && compression == BI_BITFIELDS {
+        masks[3] = le32(header, 52);
+    }
+
+    let mut palette = Vec::new();
+    if bpp <= 8 {
+        let entry_size = if header_size == CORE_HEADER_SIZE {
+            3
+        } else {
+            4
+        };
+        let count = if colors == 0 {
+            1 << bpp
+        } else {
+            colors.min(256)
+        };
+        let entries = data
+            .get(offset..offset + count * entry_size)
+            .ok_or(Status::INVALID_PARAMETER)?;
+        palette = entries
+            .chunks_exact(entry_size)
+            .map(|entry| [entry[2], entry[1], entry[0], 255])
+            .collect();
+    }
+
+    Ok(Header {
+        width: width as u32,
+        height: height.unsigned_abs(),
+        top_down: height < 0,
+        bpp,
+        compression,
+        masks,
+        palette,
+    })
+}
+
+fn palette_pixel(palette: &[[u8; 4]], index: usize) -> Result<GraphicsBltPixel> {
+    let [red, green, blue, alpha] = *palette.get(index).ok_or(Status::INVALID_PARAMETER)?;
+    Ok(rgba(red, green, blue, alpha))
+}
+
+fn decode_rows(header: &Header, pixels: &[u8], image: &mut Image) -> Result<()> {
+    let bpp = usize::from(header.bpp);
+    let stride = (header.width as usize * bpp).div_ceil(32) * 4;
+    let masks = header.masks.map(Mask::new);
+
+    for row in 0..header.height {
+        let start = row as usize * stride;
+        let line = pixels
+            .get(start..start + stride)
+            .ok_or(Status::INVALID_PARAMETER)?;
+        let y = if header.top_down {
+            row
+        } else {
+            header.height - 1 - row
+        };
+
+        for x in 0..header.width {
+            let x_bits = x as usize * bpp;
+            let pixel = match bpp {
+                1 | 2 | 4 | 8 => {
+                    let byte = line[x_bits / 8];
+                    let shift = 8 - bpp - x_bits % 8;
+                    let index = (byte >> shift) & ((1u16 << bpp) - 1) as u8;
+                    palette_pixel(&header.palette, usize::from(index))?
+                }
+                16 | 24 | 32 => {
+                    let bytes = &line[x_bits / 8..x_bits / 8 + bpp / 8];
+                    let mut value = [0; 4];
+                    value[..bytes.len()].copy_from_slice(bytes);
+                    let value = u32::from_le_bytes(value);
+                    rgba(
+                        masks[0].get(value).unwrap_or(0),
+                        masks[1].get(value).unwrap_or(0),
+                        masks[2].get(value).unwrap_or(0),
+                        masks[3].get(value).unwrap_or(255),
+                    )
+                }
+                _ => return Err(Status::UNSUPPORTED),
+            };
+            image.set(x, y, pixel);
+        }
+    }
+    Ok(())
+}
+
+/// Decode run length encoded data with 4 or 8 bit indices. Pixels the data
+/// skips are left transparent.
+fn decode_rle(header: &Header, data: &[u8], image: &mut Image) -> Result<()> {
+    let four = header.compression == BI_RLE4;
+    let (mut x, mut row) = (0u32, 0u32);
+    let mut put = |x: u32, row: u32, index: u8| -> Result<()> {
+        if x < header.width && row < header.height {
+            let y = if header.top_down {
+                row
+            } else {
+                header.height - 1 - row
+            };
+            image.set(x, y, palette_pixel(&header.palette, usize::from(index))?);
+        }
+        Ok(())
+    };
+
+    let mut position = 0;
+    let mut next = || -> Result<u8> {
+        let byte = *data.get(position).ok_or(Status::INVALID_PARAMETER)?;
+        position += 1;
+        Ok(byte)
+    };
+
+    loop {
+        let (count, value) = (next()?, next()?);
+        if count > 0 {
+            for index in 0..count {
+                let index = match four {
+                    true if index % 2 == 0 => value >> 4,
+                    true => value & 0xF,
+                    false => value,
+                };
+                put(x, row, index)?;
+                x += 1;
+            }
+            continue;
+        }
+
+        match value {
+            // End of line
+            0 => {
+                x = 0;
+                row += 1;
+            }
+            // End of bitmap
+            1 => return Ok(()),
+            // Move right and down
+            2 => {
+                x += u32::from(next()?);
+                row += u32::from(next()?);
+            }
+            // A run of literal indices, padded to a 16 bit boundary
+            count => {
+                let bytes = if four {
+                    usize::from(count).div_ceil(2)
+                } else {
+                    usize::from(count)
+                };
+                let mut literal = [0; 255];
+                for byte in &mut literal[..bytes] {
+                    *byte = next()?;
+                }
+                if bytes % 2 != 0 {
+                    next()?;
+                }
+                for index in 0..usize::from(count) {
+                    let index = if four {
+                        (literal[index / 2] >> (4 - index % 2 * 4)) & 0xF
+                    } else {
+                        literal[index]
+                    };
+                    put(x, row, index)?;
+                    x += 1;
+                }
+            }
+        }
+        if row >= header.height {
+            return Ok(());
+        }
+    }
+}
+
+/// Decode a BMP file with 1, 2, 4, 8, 16, 24 or 32 bit pixels, which may be
+/// RLE compressed or described by bit masks
+pub fn decode_bmp(data: &[u8]) -> Result<Image> {
+    if !data.starts_with(b"BM") {
+        return Err(Status::INVALID_PARAMETER);
+    }
+    let header = parse_header(data)?;
+    let pixels = data
+        .get(le32(data, 10) as usize..)
+        .ok_or(Status::INVALID_PARAMETER)?;
+
+    // Check the data can describe every pixel before allocating the image.
+    // RLE data draws at most 255 pixels for every two bytes. Pixels skipped by
+    // escapes are not counted, so that a few bytes cannot ask for a huge image.
+    let len = if matches!(header.compression, BI_RLE8 | BI_RLE4) {
+        (header.width as usize)
+            .checked_mul(header.height as usize)
+            .map(|count| count.div_ceil(255) * 2)
+    } else {
+        let stride = (header.width as usize)
+            .checked_mul(usize::from(header.bpp))
+            .map(|bits| bits.div_ceil(32) * 4);
+        stride.and_then(|stride| stride.checked_mul(header.height as usize))
+    };
+    if !len.is_some_and(|len| pixels.len() >= len) {
+        return Err(Status::INVALID_PARAMETER);
+    }
+
+    let mut image = Image::new(header.width, header.height)?;
+    match header.compression {
+        BI_RLE8 if header.bpp == 8 => decode_rle(&header, pixels, &mut image)?,
+        BI_RLE4 if header.bpp == 4 => decode_rle(&header, pixels, &mut image)?,
+        BI_RLE8 | BI_RLE4 => return Err(Status::INVALID_PARAMETER),
+        _ => {
+            image.pixels.fill(rgba(0, 0, 0, 255));
+            decode_rows(&header, pixels, &mut image)?
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_bytes!(concat!("../../testdata/image/", $name))
+        };
+    }
+
+    const K: GraphicsBltPixel = rgba(0, 0, 0, 255);
+    const W: GraphicsBltPixel = rgba(255, 255, 255, 255);
+
+    /// The colors of the `colors_*` fixtures
+    const COLORS: [GraphicsBltPixel; 8] = [
+        K,
+        rgba(255, 0, 0, 255),
+        rgba(0, 255, 0, 255),
+        rgba(255, 255, 0, 255),
+        rgba(0, 0, 255, 255),
+        rgba(255, 0, 255, 255),
+        rgba(0, 255, 255, 255),
+        W,
+    ];
+
+    #[test]
+    fn one_bit() {
+        let image = decode_bmp(fixture!("chessboard-8px-1bit.bmp")).unwrap();
+        assert_eq!((image.width(), image.height()), (8, 8));
+        for (index, pixel) in image.pixels().iter().enumerate() {
+            let (x, y) = (index % 8, index / 8);
+            let white = (x / 2 + y / 2) % 2 == 0;
+            assert_eq!(*pixel, if white { W } else { K }, "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn bit_masks() {
+        for data in [
+            &fixture!("colors_rgb555.bmp")[..],
+            fixture!("colors_rgb565.bmp"),
+            fixture!("colors_rgb888_24bit.bmp"),
+            fixture!("colors_rgb888_32bit.bmp"),
+        ] {
+            let image = decode_bmp(data).unwrap();
+            assert_eq!((image.width(), image.height()), (4, 2));
+            assert_eq!(image.pixels(), COLORS);
+        }
+    }
+
+    #[test]
+    fn eight_bit_palette() {
+        let indexed = decode_bmp(fixture!("colors_8bpp_indexed.bmp")).unwrap();
+        let rgb = decode_bmp(fixture!("colors_8bpp_non_indexed.bmp")).unwrap();
+        assert_eq!((indexed.width(), indexed.height()), (4, 6));
+        assert_eq!(indexed, rgb);
+    }
+
+    #[test]
+    fn top_down() {
+        let bottom_up = decode_bmp(fixture!("issue_8-image_bottom_up.bmp")).unwrap();
+        let top_down = decode_bmp(fixture!("issue_8-image_top_down.bmp")).unwrap();
+        assert_eq!(bottom_up, top_down);
+    }
+
+    /// A BMP file with an info header and a gray palette
+    fn with_header(width: i32, height: i32, bpp: u16, compression: u32, pixels: &[u8]) -> Vec<u8> {
+        let colors = 1usize << bpp;
+        let offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE + colors * 4;
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BM");
+        data.extend_from_slice(&((offset + pixels.len()) as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(offset as u32).to_le_bytes());
+        data.extend_from_slice(&(INFO_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&bpp.to_le_bytes());
+        data.extend_from_slice(&compression.to_le_bytes());
+        data.extend_from_slice(&[0; 20]);
+        for index in 0..colors {
+            data.extend_from_slice(&[index as u8, index as u8, index as u8, 0]);
+        }
+        data.extend_from_slice(pixels);
+        data
+    }
+
+    /// Check the pixels in `runs` of `(x, y, indices)` have the gray of
+    /// their index, and that the rest are transparent
+    fn assert_runs(image: &Image, runs: &[(u32, u32, &[u8])]) {
+        let mut expected = Image::new(image.width(), image.height()).unwrap();
+        for &(x, y, indices) in runs {
+            for (offset, &index) in indices.iter().enumerate() {
+                expected.set(x + offset as u32, y, rgba(index, index, index, 255));
+            }
+        }
+        assert_eq!(*image, expected);
+    }
+
+    #[test]
+    fn four_bit() {
+        let pixels = [0x12, 0x30, 0, 0, 0xAB, 0xC0, 0, 0];
+        let image = decode_bmp(&with_header(3, 2, 4, BI_RGB, &pixels)).unwrap();
+        assert_runs(&image, &[(0, 0, &[0xA, 0xB, 0xC]), (0, 1, &[1, 2, 3])]);
+    }
+
+    #[test]
+    fn rle8() {
+        // The example from the BITMAPINFOHEADER documentation
+        let rle = [
+            0x03, 0x04, 0x05, 0x06, 0x00, 0x03, 0x45, 0x56, 0x67, 0x00, 0x02, 0x78, 0x00, 0x02,
+            0x05, 0x01, 0x02, 0x78, 0x00, 0x00, 0x09, 0x1E, 0x00, 0x01,
+        ];
+        let image = decode_bmp(&with_header(32, -3, 8, BI_RLE8, &rle)).unwrap();
+        assert_runs(
+            &image,
+            &[
+                (
+                    0,
+                    0,
+                    &[4, 4, 4, 6, 6, 6, 6, 6, 0x45, 0x56, 0x67, 0x78, 0x78],
+                ),
+                (18, 1, &[0x78, 0x78]),
+                (0, 2, &[0x1E; 9]),
+            ],
+        );
+
+        // Bottom up images start at the last line
+        let image = decode_bmp(&with_header(32, 3, 8, BI_RLE8, &rle)).unwrap();
+        assert_eq!(image.pixels()[2 * 32], rgba(4, 4, 4, 255));
+        assert_eq!(image.pixels()[8], rgba(0x1E, 0x1E, 0x1E, 255));
+    }
+
+    #[test]
+    fn rle4() {
+        // The example from the BITMAPINFOHEADER documentation
+        let rle = [
+            0x03, 0x04, 0x05, 0x06, 0x00, 0x06, 0x45, 0x56, 0x67, 0x00, 0x04, 0x78, 0x00, 0x02,
+            0x05, 0x01, 0x04, 0x78, 0x00, 0x00, 0x09, 0x1E, 0x00, 0x01,
+        ];
+        let image = decode_bmp(&with_header(32, -3, 4, BI_RLE4, &rle)).unwrap();
+        assert_runs(
+            &image,
+            &[
+                (
+                    0,
+                    0,
+                    &[0, 4, 0, 0, 6, 0, 6, 0, 4, 5, 5, 6, 6, 7, 7, 8, 7, 8],
+                ),
+                (23, 1, &[7, 8, 7, 8]),
+                (0, 2, &[1, 0xE, 1, 0xE, 1, 0xE, 1, 0xE, 1]),
+            ],
+        );
+
+        let image = decode_bmp(fixture!("pr_50_rle4_padding.bmp")).unwrap();
+        let raw = fixture!("pr_50_rle4_padding.raw");
+        let expected: Vec<_> = raw
+            .chunks_exact(3)
+            .map(|rgb| rgba(rgb[0], rgb[1], rgb[2], 255))
+            .collect();
+        assert_eq!((image.width(), image.height()), (20, 20));
+        assert_eq!(image.pixels(), expected);
+    }
+
+    #[test]
+    fn truncated_pixels() {
+        let data = fixture!("colors_rgb888_32bit.bmp");
+        assert_eq!(
+            decode_bmp(&data[..data.len() - 1]),
+            Err(Status::INVALID_PARAMETER)
+        );
+
+        // A huge image must be rejected before allocating for it
+        let mut data = data.to_vec();
+        data[18..22].copy_from_slice(&0x7FFF_FFFFu32.to_le_bytes());
+        data[22..26].copy_from_slice(&0x7FFF_FFFFu32.to_le_bytes());
+        assert_eq!(decode_bmp(&data), Err(Status::INVALID_PARAMETER));
+
+        // RLE data too short to draw every pixel, ending the bitmap early
+        let mut rle = [0; 60];
+        rle[..2].copy_from_slice(&[0x00, 0x01]);
+        for (width, height, bpp, compression) in [
+            (65535, 65535, 8, BI_RLE8),
+            (65535, 65535, 4, BI_RLE4),
+            (256, 30, 8, BI_RLE8),
+        ] {
+            assert_eq!(
+                decode_bmp(&with_header(width, height, bpp, compression, &rle)),
+                Err(Status::INVALID_PARAMETER)
+            );
+        }
+        let image = decode_bmp(&with_header(255, 30, 8, BI_RLE8, &rle)).unwrap();
+        assert!(image
+            .pixels()
+            .iter()
+            .all(|&pixel| pixel == rgba(0, 0, 0, 0)));
+    }
+}
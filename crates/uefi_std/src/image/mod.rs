@@ -0,0 +1,155 @@
+//! BMP and PNG decoding
+//!
+//! Images decode to [`GraphicsBltPixel`]s with the alpha channel in the
+//! `Reserved` byte, where 255 is opaque, so they can be drawn directly with
+//! Blt or blended onto a [`Canvas`](crate::graphics::Canvas).
+
+use uefi::graphics::GraphicsBltPixel;
+
+use crate::graphics::Rect;
+use crate::path::Path;
+use crate::prelude::*;
+
+pub use self::bmp::decode_bmp;
+pub use self::png::decode_png;
+
+mod bmp;
+mod png;
+
+/// A decoded image
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<GraphicsBltPixel>,
+}
+
+impl Image {
+    /// An image of `width` by `height` transparent pixels
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .filter(|&len| len <= isize::MAX as usize / 4)
+            .ok_or(Status::OUT_OF_RESOURCES)?;
+
+        Ok(Image {
+            width,
+            height,
+            pixels: vec![GraphicsBltPixel::default(); len],
+        })
+    }
+
+    /// Decode a BMP or PNG file, detected from its signature
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.starts_with(png::SIGNATURE) {
+            decode_png(data)
+        } else if data.starts_with(b"BM") {
+            decode_bmp(data)
+        } else {
+            Err(Status::UNSUPPORTED)
+        }
+    }
+
+    /// Load and decode a BMP or PNG file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::decode(&crate::fs::load(path)?)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// The pixels, in lines of [`width`](Self::width) pixels from the top
+    pub fn pixels(&self) -> &[GraphicsBltPixel] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [GraphicsBltPixel] {
+        &mut self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<GraphicsBltPixel> {
+        self.pixels
+    }
+
+    /// Returns true if no pixel is even partly transparent
+    pub fn is_opaque(&self) -> bool {
+        self.pixels.iter().all(|pixel| pixel.Reserved == 255)
+    }
+
+    fn set(&mut self, x: u32, y: u32, pixel: GraphicsBltPixel) {
+        let index = y as usize * self.width as usize + x as usize;
+        self.pixels[index] = pixel;
+    }
+}
+
+/// A pixel with an alpha value
+const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> GraphicsBltPixel {
+    GraphicsBltPixel {
+        Blue: blue,
+        Green: green,
+        Red: red,
+        Reserved: alpha,
+    }
+}
+
+/// Draw `src` over `dst` using the alpha of `src`. The result is opaque if
+/// `dst` is.
+pub fn blend(dst: GraphicsBltPixel, src: GraphicsBltPixel) -> GraphicsBltPixel {
+    let alpha = u32::from(src.Reserved);
+    match alpha {
+        0 => dst,
+        255 => src,
+        _ => {
+            let mix = |dst: u8, src: u8| {
+                let value = u32::from(src) * alpha + u32::from(dst) * (255 - alpha);
+                ((value + 127) / 255) as u8
+            };
+            let dst_alpha = u32::from(dst.Reserved);
+            let out_alpha = alpha + (dst_alpha * (255 - alpha) + 127) / 255;
+            rgba(
+                mix(dst.Red, src.Red),
+                mix(dst.Green, src.Green),
+                mix(dst.Blue, src.Blue),
+                out_alpha as u8,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_alpha() {
+        let black = rgba(0, 0, 0, 255);
+        let red = rgba(200, 0, 0, 255);
+        assert_eq!(blend(black, red), red);
+        assert_eq!(blend(black, rgba(200, 0, 0, 0)), black);
+        assert_eq!(
+            blend(black, rgba(255, 255, 255, 128)),
+            rgba(128, 128, 128, 255)
+        );
+        assert_eq!(blend(red, rgba(0, 0, 255, 64)), rgba(150, 0, 64, 255));
+
+        // Blending onto a transparent pixel gives the alpha of the source
+        let clear = rgba(0, 0, 0, 0);
+        assert_eq!(blend(clear, rgba(0, 255, 0, 100)).Reserved, 100);
+    }
+
+    #[test]
+    fn decode_detects_format() {
+        assert_eq!(Image::decode(b"GIF89a"), Err(Status::UNSUPPORTED));
+        let image = Image::decode(include_bytes!("../../testdata/image/colors_rgb565.bmp"));
+        assert_eq!(image.map(|image| image.bounds()), Ok(Rect::new(0, 0, 4, 2)));
+    }
+}
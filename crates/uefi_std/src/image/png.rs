@@ -0,0 +1,431 @@
+use uefi::graphics::GraphicsBltPixel;
+
+use super::{rgba, Image};
+use crate::bytes::be32;
+use crate::crc32::crc32;
+use crate::inflate::zlib_decompress_limit;
+use crate::prelude::*;
+
+pub(super) const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Color types
+const GRAY: u8 = 0;
+const RGB: u8 = 2;
+const INDEXED: u8 = 3;
+const GRAY_ALPHA: u8 = 4;
+const RGB_ALPHA: u8 = 6;
+
+/// The origin and spacing of the pixels in each Adam7 pass
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct Header {
+    width: u32,
+    height: u32,
+    depth: u8,
+    color: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() != 13 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let header = Header {
+            width: be32(data, 0),
+            height: be32(data, 4),
+            depth: data[8],
+            color: data[9],
+            interlaced: data[12] == 1,
+        };
+        let valid_depth = match header.color {
+            GRAY => matches!(header.depth, 1 | 2 | 4 | 8 | 16),
+            INDEXED => matches!(header.depth, 1 | 2 | 4 | 8),
+            RGB | GRAY_ALPHA | RGB_ALPHA => matches!(header.depth, 8 | 16),
+            _ => false,
+        };
+        if !valid_depth || header.width == 0 || header.height == 0 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+            return Err(Status::UNSUPPORTED);
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color {
+            RGB => 3,
+            GRAY_ALPHA => 2,
+            RGB_ALPHA => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * usize::from(self.depth)
+    }
+
+    /// The bytes in a line of `width` pixels, excluding the filter type
+    fn line_len(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// The origin, spacing and size in pixels of each pass
+    fn passes(&self) -> impl Iterator<Item = ((u32, u32, u32, u32), u32, u32)> + '_ {
+        let passes: &[(u32, u32, u32, u32)] = if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+        passes
+            .iter()
+            .filter(|&&(x0, y0, _, _)| x0 < self.width && y0 < self.height)
+            .map(|&(x0, y0, dx, dy)| {
+                let width = (self.width - x0).div_ceil(dx);
+                let height = (self.height - y0).div_ceil(dy);
+                ((x0, y0, dx, dy), width, height)
+            })
+    }
+
+    /// The size of the decompressed image data, including filter types
+    fn raw_len(&self) -> Option<usize> {
+        self.passes().try_fold(0usize, |total, (_, width, height)| {
+            (width as usize)
+                .checked_mul(self.bits_per_pixel())?
+                .div_ceil(8)
+                .checked_add(1)?
+                .checked_mul(height as usize)?
+                .checked_add(total)
+        })
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Undo the filter of `line` given the previous unfiltered line
+fn unfilter(filter: u8, line: &mut [u8], previous: &[u8], bpp: usize) -> Result<()> {
+    for i in 0..line.len() {
+        let left = if i >= bpp { line[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        line[i] = line[i].wrapping_add(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(Status::INVALID_PARAMETER),
+        });
+    }
+    Ok(())
+}
+
+struct Decoder<'a> {
+    header: &'a Header,
+    palette: &'a [[u8; 4]],
+    /// The transparent gray or RGB value, at full depth
+    transparent: Option<[u16; 3]>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Sample `index` of a line
+    fn sample(&self, line: &[u8], index: usize) -> u16 {
+        match self.header.depth {
+            16 => u16::from_be_bytes([line[index * 2], line[index * 2 + 1]]),
+            8 => u16::from(line[index]),
+            depth => {
+                let bit = index * usize::from(depth);
+                let shift = 8 - usize::from(depth) - bit % 8;
+                u16::from((line[bit / 8] >> shift) & ((1u16 << depth) - 1) as u8)
+            }
+        }
+    }
+
+    /// Scale a sample to 8 bits
+    fn scale(&self, sample: u16) -> u8 {
+        match self.header.depth {
+            16 => (sample >> 8) as u8,
+            depth => (u32::from(sample) * 255 / ((1 << depth) - 1)) as u8,
+        }
+    }
+
+    fn pixel(&self, line: &[u8], x: usize) -> Result<GraphicsBltPixel> {
+        let channels = self.header.channels();
+        let sample = |channel| self.sample(line, x * channels + channel);
+        Ok(match self.header.color {
+            INDEXED => {
+                let [red, green, blue, alpha] = *self
+                    .palette
+                    .get(usize::from(sample(0)))
+                    .ok_or(Status::INVALID_PARAMETER)?;
+                rgba(red, green, blue, alpha)
+            }
+            GRAY => {
+                let gray = sample(0);
+                let alpha = match self.transparent {
+                    Some([transparent, ..]) if transparent == gray => 0,
+                    _ => 255,
+                };
+                let gray = self.scale(gray);
+                rgba(gray, gray, gray, alpha)
+            }
+            GRAY_ALPHA => {
+                let gray = self.scale(sample(0));
+                rgba(gray, gray, gray, self.scale(sample(1)))
+            }
+            RGB => {
+                let rgb = [sample(0), sample(1), sample(2)];
+                let alpha = if self.transparent == Some(rgb) {
+                    0
+                } else {
+                    255
+                };
+                rgba(
+                    self.scale(rgb[0]),
+                    self.scale(rgb[1]),
+                    self.scale(rgb[2]),
+                    alpha,
+                )
+            }
+            _ => rgba(
+                self.scale(sample(0)),
+                self.scale(sample(1)),
+                self.scale(sample(2)),
+                self.scale(sample(3)),
+            ),
+        })
+    }
+}
+
+/// Decode a PNG file of any color type and bit depth, which may be
+/// interlaced. Chunk checksums are verified.
+pub fn decode_png(data: &[u8]) -> Result<Image> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(Status::INVALID_PARAMETER);
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut transparency = None;
+    let mut compressed = Vec::new();
+    let mut offset = SIGNATURE.len();
+    loop {
+        let chunk_header = data
+            .get(offset..offset + 8)
+            .ok_or(Status::INVALID_PARAMETER)?;
+        let len = be32(chunk_header, 0) as usize;
+        let kind: [u8; 4] = chunk_header[4..8].try_into().unwrap();
+        let chunk = data
+            .get(offset + 8..(offset + 8).saturating_add(len))
+            .ok_or(Status::INVALID_PARAMETER)?;
+        let checksum = data
+            .get(offset + 8 + len..offset + 12 + len)
+            .ok_or(Status::INVALID_PARAMETER)?;
+        if crc32(&data[offset + 4..offset + 8 + len]) != be32(checksum, 0) {
+            return Err(Status::CRC_ERROR);
+        }
+        offset += 12 + len;
+
+        match &kind {
+            b"IHDR" => header = Some(Header::parse(chunk)?),
+            b"PLTE" => {
+                palette = chunk
+                    .chunks_exact(3)
+                    .take(256)
+                    .map(|entry| [entry[0], entry[1], entry[2], 255])
+                    .collect();
+            }
+            b"tRNS" => transparency = Some(chunk),
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            // Unknown critical chunks change how the image must be read
+            _ if kind[0] & 0x20 == 0 => return Err(Status::UNSUPPORTED),
+            _ => (),
+        }
+    }
+
+    let header = header.ok_or(Status::INVALID_PARAMETER)?;
+    let mut transparent = None;
+    if let Some(chunk) = transparency {
+        let value = |index: usize| -> Result<u16> {
+            let bytes = chunk
+                .get(index * 2..index * 2 + 2)
+                .ok_or(Status::INVALID_PARAMETER)?;
+            Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+        match header.color {
+            INDEXED => {
+                for (entry, &alpha) in palette.iter_mut().zip(chunk) {
+                    entry[3] = alpha;
+                }
+            }
+            GRAY => transparent = Some([value(0)?, 0, 0]),
+            RGB => transparent = Some([value(0)?, value(1)?, value(2)?]),
+            _ => (),
+        }
+    }
+
+    // Check the data can hold the image before allocating for it. DEFLATE
+    // expands data by at most 1032 times.
+    let raw_len = header.raw_len().ok_or(Status::INVALID_PARAMETER)?;
+    if raw_len.div_ceil(1032) > compressed.len() {
+        return Err(Status::INVALID_PARAMETER);
+    }
+    let raw = match zlib_decompress_limit(&compressed, raw_len) {
+        Err(Status::BUFFER_TOO_SMALL) => return Err(Status::INVALID_PARAMETER),
+        res => res?,
+    };
+    if raw.len() != raw_len {
+        return Err(Status::INVALID_PARAMETER);
+    }
+
+    let decoder = Decoder {
+        header: &header,
+        palette: &palette,
+        transparent,
+    };
+    let mut image = Image::new(header.width, header.height)?;
+    let bpp = header.bits_per_pixel().div_ceil(8);
+
+    let mut position = 0;
+    for ((x0, y0, dx, dy), width, height) in header.passes() {
+        let len = header.line_len(width);
+
+        let mut previous = vec![0; len];
+        for row in 0..height {
+            let filter = *raw.get(position).ok_or(Status::INVALID_PARAMETER)?;
+            let line = raw
+                .get(position + 1..position + 1 + len)
+                .ok_or(Status::INVALID_PARAMETER)?;
+            position += 1 + len;
+
+            let mut line = line.to_vec();
+            unfilter(filter, &mut line, &previous, bpp)?;
+            for column in 0..width {
+                let pixel = decoder.pixel(&line, column as usize)?;
+                image.set(x0 + column * dx, y0 + row * dy, pixel);
+            }
+            previous = line;
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_bytes!(concat!("../../testdata/image/", $name))
+        };
+    }
+
+    /// Replace the width and height in the IHDR chunk of `data`
+    fn resize(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut data = data.to_vec();
+        data[16..20].copy_from_slice(&width.to_be_bytes());
+        data[20..24].copy_from_slice(&height.to_be_bytes());
+        let checksum = crc32(&data[12..29]);
+        data[29..33].copy_from_slice(&checksum.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn palette_with_transparency() {
+        let image = decode_png(fixture!("palette-trns.png")).unwrap();
+        assert_eq!((image.width(), image.height()), (7, 5));
+        let palette = [
+            rgba(0, 0, 0, 0),
+            rgba(255, 0, 0, 128),
+            rgba(0, 255, 0, 64),
+            rgba(0, 0, 255, 255),
+            rgba(255, 255, 255, 255),
+        ];
+        for (index, pixel) in image.pixels().iter().enumerate() {
+            let (x, y) = (index % 7, index / 7);
+            assert_eq!(*pixel, palette[(x + 2 * y) % 5], "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn sixteen_bit() {
+        let image = decode_png(fixture!("rgb-16bit.png")).unwrap();
+        assert_eq!((image.width(), image.height()), (6, 4));
+        for (index, pixel) in image.pixels().iter().enumerate() {
+            let (x, y) = (index % 6, index / 6);
+            let expected = rgba(
+                ((x * 0x1234) >> 8) as u8,
+                ((y * 0x2345) >> 8) as u8,
+                ((0xFFFF - x * y * 0x0101) >> 8) as u8,
+                255,
+            );
+            assert_eq!(*pixel, expected, "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn adam7() {
+        let image = decode_png(fixture!("rgba-adam7.png")).unwrap();
+        assert_eq!((image.width(), image.height()), (11, 9));
+        for (index, pixel) in image.pixels().iter().enumerate() {
+            let (x, y) = (index % 11, index / 11);
+            let expected = rgba(
+                20 * x as u8,
+                20 * y as u8,
+                7 * (x + y) as u8,
+                255 - 10 * x as u8,
+            );
+            assert_eq!(*pixel, expected, "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut data = fixture!("rgb-16bit.png").to_vec();
+        data[20] ^= 1;
+        assert_eq!(decode_png(&data), Err(Status::CRC_ERROR));
+    }
+
+    #[test]
+    fn size_must_match_data() {
+        let data = fixture!("rgba-adam7.png");
+        // The data is too short for a larger image, or too long for a smaller one
+        assert_eq!(
+            decode_png(&resize(data, 11, 10)),
+            Err(Status::INVALID_PARAMETER)
+        );
+        assert_eq!(
+            decode_png(&resize(data, 10, 9)),
+            Err(Status::INVALID_PARAMETER)
+        );
+        // A huge image must be rejected before allocating for it
+        let huge = resize(data, 0x7FFF_FFFF, 0x7FFF_FFFF);
+        assert_eq!(decode_png(&huge), Err(Status::INVALID_PARAMETER));
+    }
+}
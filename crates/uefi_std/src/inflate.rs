@@ -0,0 +1,354 @@
+//! DEFLATE (RFC 1951) and zlib (RFC 1950) decompression
+
+use crate::bytes::le16;
+use crate::prelude::*;
+
+const MAX_BITS: usize = 15;
+
+/// Base lengths of length codes 257 to 285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads bits from the least significant end of each byte
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Bits {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or(Status::INVALID_PARAMETER)?;
+            self.position += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Discard bits up to the next byte boundary
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let data = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or(Status::INVALID_PARAMETER)?;
+        self.position += len;
+        Ok(data)
+    }
+}
+
+/// A canonical Huffman code
+struct Huffman {
+    /// The number of codes of each length
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[usize::from(len)] += 1;
+        }
+        counts[0] = 0;
+
+        // Reject codes with more codes of a length than can exist
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err(Status::INVALID_PARAMETER);
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[usize::from(offsets[usize::from(len)])] = symbol as u16;
+                offsets[usize::from(len)] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for len in 1..=MAX_BITS {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(self.counts[len]);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Status::INVALID_PARAMETER)
+    }
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err(Status::INVALID_PARAMETER);
+    }
+
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = bits.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; literals + distances];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *index
+                    .checked_sub(1)
+                    .and_then(|previous| lengths.get(previous))
+                    .ok_or(Status::INVALID_PARAMETER)?;
+                (previous, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        let end = index + repeat;
+        lengths
+            .get_mut(index..end)
+            .ok_or(Status::INVALID_PARAMETER)?
+            .fill(value);
+        index = end;
+    }
+    if lengths[256] == 0 {
+        return Err(Status::INVALID_PARAMETER);
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..])?,
+    ))
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = usize::from(literals.decode(bits)?);
+        match symbol {
+            0..=255 if output.len() >= limit => return Err(Status::BUFFER_TOO_SMALL),
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(Status::INVALID_PARAMETER);
+                }
+                let len = usize::from(LENGTH_BASE[index])
+                    + bits.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+
+                let index = usize::from(distances.decode(bits)?);
+                if index >= DIST_BASE.len() {
+                    return Err(Status::INVALID_PARAMETER);
+                }
+                let distance = usize::from(DIST_BASE[index])
+                    + bits.bits(u32::from(DIST_EXTRA[index]))? as usize;
+                if distance > output.len() {
+                    return Err(Status::INVALID_PARAMETER);
+                }
+                if len > limit - output.len() {
+                    return Err(Status::BUFFER_TOO_SMALL);
+                }
+
+                // The copy may overlap the bytes it produces
+                let start = output.len() - distance;
+                for offset in 0..len {
+                    output.push(output[start + offset]);
+                }
+            }
+        }
+    }
+}
+
+/// Decompress raw DEFLATE data
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    inflate_limit(data, usize::MAX)
+}
+
+/// Decompress raw DEFLATE data, failing with `BUFFER_TOO_SMALL` as soon as
+/// the output would exceed `limit` bytes
+pub fn inflate_limit(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut bits = Bits::new(data);
+    let mut output = Vec::with_capacity(data.len().saturating_mul(4).min(limit));
+    loop {
+        let last = bits.bits(1)? != 0;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = bits.bytes(4)?;
+                let len = le16(header, 0);
+                let complement = le16(header, 2);
+                if len != !complement {
+                    return Err(Status::INVALID_PARAMETER);
+                }
+                if usize::from(len) > limit - output.len() {
+                    return Err(Status::BUFFER_TOO_SMALL);
+                }
+                output.extend_from_slice(bits.bytes(usize::from(len))?);
+            }
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                inflate_block(&mut bits, &mut output, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut output, limit, &literals, &distances)?;
+            }
+            _ => return Err(Status::INVALID_PARAMETER),
+        }
+
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums of 5552 bytes cannot overflow before the modulo
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Decompress a zlib stream, checking its Adler-32 checksum
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    zlib_decompress_limit(data, usize::MAX)
+}
+
+/// Decompress a zlib stream like [`zlib_decompress`], failing with
+/// `BUFFER_TOO_SMALL` as soon as the output would exceed `limit` bytes
+pub fn zlib_decompress_limit(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    if data.len() < 6 {
+        return Err(Status::INVALID_PARAMETER);
+    }
+    let (cmf, flags) = (data[0], data[1]);
+    if cmf & 0xF != 8 || cmf >> 4 > 7 || (u16::from(cmf) << 8 | u16::from(flags)) % 31 != 0 {
+        return Err(Status::INVALID_PARAMETER);
+    }
+    // A preset dictionary is never used by PNG
+    if flags & 0x20 != 0 {
+        return Err(Status::UNSUPPORTED);
+    }
+
+    let output = inflate_limit(&data[2..], limit)?;
+    let checksum = data[data.len() - 4..].try_into().unwrap();
+    if adler32(&output) != u32::from_be_bytes(checksum) {
+        return Err(Status::CRC_ERROR);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "abcabcabcabc" with fixed codes, as a zlib stream
+    const ZLIB: &[u8] = &[
+        0x78, 0x9C, 0x4B, 0x4C, 0x4A, 0x4E, 0x84, 0x21, 0x00, 0x1D, 0xE0, 0x04, 0x99,
+    ];
+
+    #[test]
+    fn zlib_round_trip() {
+        assert_eq!(zlib_decompress(ZLIB).unwrap(), b"abcabcabcabc");
+        assert_eq!(zlib_decompress_limit(ZLIB, 12).unwrap(), b"abcabcabcabc");
+    }
+
+    #[test]
+    fn output_stops_at_limit() {
+        assert_eq!(
+            zlib_decompress_limit(ZLIB, 11),
+            Err(Status::BUFFER_TOO_SMALL)
+        );
+        assert_eq!(
+            zlib_decompress_limit(ZLIB, 2),
+            Err(Status::BUFFER_TOO_SMALL)
+        );
+        // Stored blocks are checked before copying
+        let stored = [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'];
+        assert_eq!(inflate_limit(&stored, 3).unwrap(), b"abc");
+        assert_eq!(inflate_limit(&stored, 2), Err(Status::BUFFER_TOO_SMALL));
+    }
+}
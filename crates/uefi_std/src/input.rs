@@ -0,0 +1,502 @@
+//! Keyboard input from the console or another text input device
+
+use core::time::Duration;
+use uefi::boot::{TimerDelay, EVT_TIMER};
+use uefi::text::{
+    self, KeyData, KeyState as UefiKeyState, TextInput as UefiTextInput, TextInputEx, TextInputKey,
+    CAPS_LOCK_ACTIVE, LEFT_ALT_PRESSED, LEFT_CONTROL_PRESSED, LEFT_LOGO_PRESSED,
+    LEFT_SHIFT_PRESSED, MENU_KEY_PRESSED, NUM_LOCK_ACTIVE, RIGHT_ALT_PRESSED,
+    RIGHT_CONTROL_PRESSED, RIGHT_LOGO_PRESSED, RIGHT_SHIFT_PRESSED, SCROLL_LOCK_ACTIVE,
+    SHIFT_STATE_VALID, SYS_REQ_PRESSED, TOGGLE_STATE_VALID,
+};
+
+use crate::event::{wait_for_event, OwnedEvent};
+use crate::prelude::*;
+use crate::system_table;
+
+/// The character given for UCS-2 values that are not characters
+const REPLACEMENT: char = '\u{FFFD}';
+
+/// The scan codes of [`ScanCode`] variants
+const SCAN_CODES: [(u16, ScanCode); 45] = [
+    (text::SCAN_UP, ScanCode::Up),
+    (text::SCAN_DOWN, ScanCode::Down),
+    (text::SCAN_RIGHT, ScanCode::Right),
+    (text::SCAN_LEFT, ScanCode::Left),
+    (text::SCAN_HOME, ScanCode::Home),
+    (text::SCAN_END, ScanCode::End),
+    (text::SCAN_INSERT, ScanCode::Insert),
+    (text::SCAN_DELETE, ScanCode::Delete),
+    (text::SCAN_PAGE_UP, ScanCode::PageUp),
+    (text::SCAN_PAGE_DOWN, ScanCode::PageDown),
+    (text::SCAN_F1, ScanCode::F1),
+    (text::SCAN_F2, ScanCode::F2),
+    (text::SCAN_F3, ScanCode::F3),
+    (text::SCAN_F4, ScanCode::F4),
+    (text::SCAN_F5, ScanCode::F5),
+    (text::SCAN_F6, ScanCode::F6),
+    (text::SCAN_F7, ScanCode::F7),
+    (text::SCAN_F8, ScanCode::F8),
+    (text::SCAN_F9, ScanCode::F9),
+    (text::SCAN_F10, ScanCode::F10),
+    (text::SCAN_F11, ScanCode::F11),
+    (text::SCAN_F12, ScanCode::F12),
+    (text::SCAN_F13, ScanCode::F13),
+    (text::SCAN_F14, ScanCode::F14),
+    (text::SCAN_F15, ScanCode::F15),
+    (text::SCAN_F16, ScanCode::F16),
+    (text::SCAN_F17, ScanCode::F17),
+    (text::SCAN_F18, ScanCode::F18),
+    (text::SCAN_F19, ScanCode::F19),
+    (text::SCAN_F20, ScanCode::F20),
+    (text::SCAN_F21, ScanCode::F21),
+    (text::SCAN_F22, ScanCode::F22),
+    (text::SCAN_F23, ScanCode::F23),
+    (text::SCAN_F24, ScanCode::F24),
+    (text::SCAN_ESC, ScanCode::Escape),
+    (text::SCAN_MUTE, ScanCode::Mute),
+    (text::SCAN_VOLUME_UP, ScanCode::VolumeUp),
+    (text::SCAN_VOLUME_DOWN, ScanCode::VolumeDown),
+    (text::SCAN_BRIGHTNESS_UP, ScanCode::BrightnessUp),
+    (text::SCAN_BRIGHTNESS_DOWN, ScanCode::BrightnessDown),
+    (text::SCAN_SUSPEND, ScanCode::Suspend),
+    (text::SCAN_HIBERNATE, ScanCode::Hibernate),
+    (text::SCAN_TOGGLE_DISPLAY, ScanCode::ToggleDisplay),
+    (text::SCAN_RECOVERY, ScanCode::Recovery),
+    (text::SCAN_EJECT, ScanCode::Eject),
+];
+
+/// A key without a character
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ScanCode {
+    Up,
+    Down,
+    Right,
+    Left,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Escape,
+    Mute,
+    VolumeUp,
+    VolumeDown,
+    BrightnessUp,
+    BrightnessDown,
+    Suspend,
+    Hibernate,
+    ToggleDisplay,
+    Recovery,
+    Eject,
+    /// A scan code without a variant, such as an OEM key
+    Other(u16),
+}
+
+impl From<u16> for ScanCode {
+    fn from(code: u16) -> Self {
+        SCAN_CODES
+            .iter()
+            .find(|(value, _)| *value == code)
+            .map_or(ScanCode::Other(code), |&(_, scan_code)| scan_code)
+    }
+}
+
+impl From<ScanCode> for u16 {
+    fn from(scan_code: ScanCode) -> Self {
+        match scan_code {
+            ScanCode::Other(code) => code,
+            _ => SCAN_CODES
+                .iter()
+                .find(|(_, value)| *value == scan_code)
+                .map_or(text::SCAN_NULL, |&(code, _)| code),
+        }
+    }
+}
+
+/// A key press
+///
+/// Enter is reported as `Char('\r')`, Backspace as `Char('\x08')` and Tab as
+/// `Char('\t')`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+    Char(char),
+    Special(ScanCode),
+}
+
+/// The modifier keys held and the lock keys active during a key press
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct KeyState {
+    /// The `*_PRESSED` flags of `uefi::text`, valid if `SHIFT_STATE_VALID` is
+    /// set
+    pub shift: u32,
+    /// The `*_ACTIVE` flags of `uefi::text`, valid if `TOGGLE_STATE_VALID`
+    /// is set
+    pub toggle: u8,
+}
+
+impl KeyState {
+    /// Returns true if the modifier flags are known, which they never are
+    /// without the extended protocol
+    pub fn has_modifiers(&self) -> bool {
+        self.shift & SHIFT_STATE_VALID != 0
+    }
+
+    /// Returns true if the lock flags are known
+    pub fn has_toggles(&self) -> bool {
+        self.toggle & TOGGLE_STATE_VALID != 0
+    }
+
+    fn pressed(&self, flags: u32) -> bool {
+        self.has_modifiers() && self.shift & flags != 0
+    }
+
+    fn active(&self, flag: u8) -> bool {
+        self.has_toggles() && self.toggle & flag != 0
+    }
+
+    pub fn shift(&self) -> bool {
+        self.pressed(LEFT_SHIFT_PRESSED | RIGHT_SHIFT_PRESSED)
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.pressed(LEFT_CONTROL_PRESSED | RIGHT_CONTROL_PRESSED)
+    }
+
+    pub fn alt(&self) -> bool {
+        self.pressed(LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED)
+    }
+
+    pub fn logo(&self) -> bool {
+        self.pressed(LEFT_LOGO_PRESSED | RIGHT_LOGO_PRESSED)
+    }
+
+    pub fn menu(&self) -> bool {
+        self.pressed(MENU_KEY_PRESSED)
+    }
+
+    pub fn sys_req(&self) -> bool {
+        self.pressed(SYS_REQ_PRESSED)
+    }
+
+    pub fn caps_lock(&self) -> bool {
+        self.active(CAPS_LOCK_ACTIVE)
+    }
+
+    pub fn num_lock(&self) -> bool {
+        self.active(NUM_LOCK_ACTIVE)
+    }
+
+    pub fn scroll_lock(&self) -> bool {
+        self.active(SCROLL_LOCK_ACTIVE)
+    }
+}
+
+/// A key press as reported by firmware
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct KeyStroke {
+    /// The scan code of a key without a character, or 0
+    pub scan_code: u16,
+    /// The UCS-2 character of the key, or 0
+    pub unicode: u16,
+    pub state: KeyState,
+}
+
+impl KeyStroke {
+    /// The key pressed, or `None` if the stroke only reports a change of
+    /// [`KeyState`]
+    pub fn key(&self) -> Option<Key> {
+        if self.scan_code != text::SCAN_NULL {
+            Some(Key::Special(ScanCode::from(self.scan_code)))
+        } else if self.unicode != 0 {
+            Some(Key::Char(
+                char::from_u32(u32::from(self.unicode)).unwrap_or(REPLACEMENT),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// The letter of a Ctrl+letter combination, in lowercase
+    ///
+    /// Without modifier flags this relies on the firmware reporting the
+    /// ASCII control character, so Ctrl+H, Ctrl+I, Ctrl+J and Ctrl+M cannot
+    /// be told apart from Backspace, Tab and Enter.
+    pub fn ctrl_letter(&self) -> Option<char> {
+        let c = char::from_u32(u32::from(self.unicode))?;
+        if self.state.has_modifiers() {
+            if self.state.ctrl() && c.is_ascii_alphabetic() {
+                return Some(c.to_ascii_lowercase());
+            }
+            // Some drivers report the control character along with the flag
+            if !self.state.ctrl() {
+                return None;
+            }
+        }
+        match self.unicode {
+            0x08 | 0x09 | 0x0A | 0x0D => None,
+            0x01..=0x1A => Some((b'a' - 1 + self.unicode as u8) as char),
+            _ => None,
+        }
+    }
+}
+
+impl From<TextInputKey> for KeyStroke {
+    fn from(key: TextInputKey) -> Self {
+        KeyStroke {
+            scan_code: key.ScanCode,
+            unicode: key.UnicodeChar,
+            state: KeyState::default(),
+        }
+    }
+}
+
+impl From<KeyData> for KeyStroke {
+    fn from(data: KeyData) -> Self {
+        KeyStroke {
+            scan_code: data.Key.ScanCode,
+            unicode: data.Key.UnicodeChar,
+            state: KeyState {
+                shift: data.KeyState.KeyShiftState,
+                toggle: data.KeyState.KeyToggleState,
+            },
+        }
+    }
+}
+
+impl From<KeyStroke> for KeyData {
+    fn from(stroke: KeyStroke) -> Self {
+        KeyData {
+            Key: TextInputKey {
+                ScanCode: stroke.scan_code,
+                UnicodeChar: stroke.unicode,
+            },
+            KeyState: UefiKeyState {
+                KeyShiftState: stroke.state.shift,
+                KeyToggleState: stroke.state.toggle,
+            },
+        }
+    }
+}
+
+enum Inner {
+    Ex(&'static TextInputEx),
+    Simple(&'static UefiTextInput),
+}
+
+/// A text input device, using the extended protocol when firmware provides
+/// it
+pub struct Input(Inner);
+
+impl Input {
+    /// The console input device
+    pub fn console() -> Self {
+        let st = system_table();
+        Self::ex(st.ConsoleInHandle).unwrap_or(Input(Inner::Simple(st.ConsoleIn)))
+    }
+
+    /// The input device on `handle`
+    pub fn from_handle(handle: Handle) -> Result<Self> {
+        Self::ex(handle).or_else(|_| {
+            let mut interface = 0;
+            let status = (system_table().BootServices.HandleProtocol)(
+                handle,
+                &UefiTextInput::GUID,
+                &mut interface,
+            );
+            match status {
+                Status::SUCCESS => Ok(Input(Inner::Simple(unsafe {
+                    &*(interface as *const UefiTextInput)
+                }))),
+                _ => Err(status),
+            }
+        })
+    }
+
+    fn ex(handle: Handle) -> Result<Self> {
+        let mut interface = 0;
+        let status = (system_table().BootServices.HandleProtocol)(
+            handle,
+            &TextInputEx::GUID,
+            &mut interface,
+        );
+        match status {
+            Status::SUCCESS => Ok(Input(Inner::Ex(unsafe {
+                &*(interface as *const TextInputEx)
+            }))),
+            _ => Err(status),
+        }
+    }
+
+    /// Returns true if the extended protocol is in use, so that key states,
+    /// [`Input::set_state`] and key notifications are available
+    pub fn is_extended(&self) -> bool {
+        matches!(self.0, Inner::Ex(_))
+    }
+
+    /// Reset the device, discarding pending keys
+    pub fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        match self.0 {
+            Inner::Ex(ex) => (ex.Reset)(ex, extended_verification),
+            Inner::Simple(simple) => (simple.Reset)(simple, extended_verification),
+        }
+        .into()
+    }
+
+    /// The event signaled when a key is available
+    pub fn wait_event(&self) -> Event {
+        match self.0 {
+            Inner::Ex(ex) => ex.WaitForKeyEx,
+            Inner::Simple(simple) => simple.WaitForKey,
+        }
+    }
+
+    /// Read a pending key stroke, or `None` if there is none
+    pub fn read_key_stroke(&mut self) -> Result<Option<KeyStroke>> {
+        let (status, stroke) = match self.0 {
+            Inner::Ex(ex) => {
+                let mut data = KeyData::default();
+                let status = (ex.ReadKeyStrokeEx)(ex, &mut data);
+                (status, KeyStroke::from(data))
+            }
+            Inner::Simple(simple) => {
+                let mut key = TextInputKey::default();
+                let status = (simple.ReadKeyStroke)(simple, &mut key);
+                (status, KeyStroke::from(key))
+            }
+        };
+
+        match status {
+            Status::SUCCESS => Ok(Some(stroke)),
+            Status::NOT_READY => Ok(None),
+            _ => Err(status),
+        }
+    }
+
+    /// Block until a key is pressed
+    pub fn read_key(&mut self) -> Result<Key> {
+        loop {
+            if let Some(key) = self.try_read_key()? {
+                return Ok(key);
+            }
+            wait_for_event(&[self.wait_event()])?;
+        }
+    }
+
+    /// Read a pending key press, or `None` if there is none
+    pub fn try_read_key(&mut self) -> Result<Option<Key>> {
+        // Skip strokes that only report a change of state
+        while let Some(stroke) = self.read_key_stroke()? {
+            if let Some(key) = stroke.key() {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Block until a key is pressed or `timeout` passes, returning `None` on
+    /// timeout
+    pub fn read_key_timeout(&mut self, timeout: Duration) -> Result<Option<Key>> {
+        if let Some(key) = self.try_read_key()? {
+            return Ok(Some(key));
+        }
+
+        let timer = OwnedEvent::new(EVT_TIMER)?;
+        // The timer counts in 100ns units
+        let trigger_time = u64::try_from(timeout.as_nanos() / 100).unwrap_or(u64::MAX);
+        timer.set_timer(TimerDelay::Relative, trigger_time)?;
+        loop {
+            if wait_for_event(&[self.wait_event(), timer.event()])? == 1 {
+                return Ok(None);
+            }
+            if let Some(key) = self.try_read_key()? {
+                return Ok(Some(key));
+            }
+        }
+    }
+
+    /// Set the lock keys to the `*_ACTIVE` flags of `uefi::text` in `toggle`
+    pub fn set_state(&mut self, toggle: u8) -> Result<()> {
+        match self.0 {
+            Inner::Ex(ex) => (ex.SetState)(ex, &(toggle | TOGGLE_STATE_VALID)).into(),
+            Inner::Simple(_) => Err(Status::UNSUPPORTED),
+        }
+    }
+
+    /// Call `notify` whenever `key` is pressed, until the returned
+    /// [`KeyNotify`] is dropped
+    ///
+    /// Firmware compares the scan code and character, and the state flags
+    /// that are marked valid. `notify` runs at `TPL_CALLBACK` or above, in
+    /// the middle of whatever the application is doing, so it should only
+    /// record the key press.
+    pub fn register_key_notify(
+        &mut self,
+        key: KeyStroke,
+        notify: extern "efiapi" fn(&KeyData) -> Status,
+    ) -> Result<KeyNotify> {
+        let Inner::Ex(ex) = self.0 else {
+            return Err(Status::UNSUPPORTED);
+        };
+
+        let mut handle = 0;
+        let status = (ex.RegisterKeyNotify)(ex, &KeyData::from(key), notify, &mut handle);
+        match status {
+            Status::SUCCESS => Ok(KeyNotify { ex, handle }),
+            _ => Err(status),
+        }
+    }
+}
+
+/// A registered key notification, unregistered on drop
+pub struct KeyNotify {
+    ex: &'static TextInputEx,
+    handle: usize,
+}
+
+impl Drop for KeyNotify {
+    fn drop(&mut self) {
+        let _ = (self.ex.UnregisterKeyNotify)(self.ex, self.handle);
+    }
+}
+
+/// Block until a key is pressed on the console
+pub fn read_key() -> Result<Key> {
+    Input::console().read_key()
+}
+
+/// Read a pending key press from the console, or `None` if there is none
+pub fn try_read_key() -> Result<Option<Key>> {
+    Input::console().try_read_key()
+}
+
+/// Block until a key is pressed on the console or `timeout` passes, returning
+/// `None` on timeout
+pub fn read_key_timeout(timeout: Duration) -> Result<Option<Key>> {
+    Input::console().read_key_timeout(timeout)
+}
@@ -0,0 +1,182 @@
+use core::cmp;
+
+use super::{BufRead, Read, Result, Seek, SeekFrom, Write, DEFAULT_BUF_SIZE};
+use crate::prelude::*;
+
+/// Adds buffering to a [`Read`], reducing the number of calls made to it
+pub struct BufReader<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: Read> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        BufReader {
+            inner,
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+}
+
+impl<R> BufReader<R> {
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Access the inner reader. Reading from it directly will skip buffered data.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the inner reader, discarding buffered data
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// The data that has been read from the inner reader but not consumed
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    fn discard_buffer(&mut self) {
+        self.pos = 0;
+        self.filled = 0;
+    }
+}
+
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Bypass the buffer for large reads when it is empty
+        if self.pos == self.filled && buf.len() >= self.buf.len() {
+            self.discard_buffer();
+            return self.inner.read(buf);
+        }
+
+        let count = self.fill_buf()?.read(buf)?;
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl<R: Read> BufRead for BufReader<R> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        if self.pos >= self.filled {
+            self.filled = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.filled);
+    }
+}
+
+impl<R: Read + Seek> Seek for BufReader<R> {
+    /// Seeking always discards the buffer. `SeekFrom::Current` is relative to
+    /// the position of the next byte that would be read from the buffer.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let result = match pos {
+            SeekFrom::Current(offset) => {
+                let remaining = (self.filled - self.pos) as i64;
+                self.inner.seek(SeekFrom::Current(offset - remaining))?
+            }
+            pos => self.inner.seek(pos)?,
+        };
+        self.discard_buffer();
+        Ok(result)
+    }
+}
+
+/// Adds buffering to a [`Write`], collecting small writes into larger ones
+///
+/// Buffered data is written when the buffer is full, on [`Write::flush`], and
+/// on drop, where errors are ignored.
+pub struct BufWriter<W: Write> {
+    inner: Option<W>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> BufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        BufWriter {
+            inner: Some(inner),
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Access the inner writer. Writing to it directly will skip buffered data.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// The data that has been written but not passed to the inner writer
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Write the buffer and return the inner writer
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush_buf()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn flush_buf(&mut self) -> Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        let res = inner.write_all(&self.buf);
+        self.buf.clear();
+        res
+    }
+}
+
+impl<W: Write> Write for BufWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.buf.len() + buf.len() > self.buf.capacity() {
+            self.flush_buf()?;
+        }
+
+        if buf.len() >= self.buf.capacity() {
+            self.get_mut().write(buf)
+        } else {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.flush_buf()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for BufWriter<W> {
+    /// Seeking writes the buffer first
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.flush_buf()?;
+        self.get_mut().seek(pos)
+    }
+}
+
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.flush_buf();
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use core::cmp;
+
+use super::{BufRead, Error, Read, Result, Seek, SeekFrom, Write};
+use crate::prelude::*;
+
+/// Wraps an in-memory buffer to provide [`Read`], [`Write`] and [`Seek`]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cursor<T> {
+    inner: T,
+    pos: u64,
+}
+
+impl<T> Cursor<T> {
+    pub fn new(inner: T) -> Self {
+        Cursor { inner, pos: 0 }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+}
+
+impl<T: AsRef<[u8]>> Cursor<T> {
+    /// The bytes from the current position to the end
+    fn remaining(&self) -> &[u8] {
+        let data = self.inner.as_ref();
+        let start = cmp::min(self.pos, data.len() as u64) as usize;
+        &data[start..]
+    }
+}
+
+impl<T: AsRef<[u8]>> Read for Cursor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let count = self.remaining().read(buf)?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<T: AsRef<[u8]>> BufRead for Cursor<T> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        Ok(self.remaining())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl<T: AsRef<[u8]>> Seek for Cursor<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::End(offset) => (self.inner.as_ref().len() as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+
+        self.pos = base
+            .checked_add_signed(offset)
+            .ok_or(Error::INVALID_INPUT)?;
+        Ok(self.pos)
+    }
+}
+
+/// Write into a fixed buffer, stopping at its end
+fn slice_write(pos: &mut u64, data: &mut [u8], buf: &[u8]) -> Result<usize> {
+    let start = cmp::min(*pos, data.len() as u64) as usize;
+    let count = (&mut data[start..]).write(buf)?;
+    *pos += count as u64;
+    Ok(count)
+}
+
+impl Write for Cursor<&mut [u8]> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        slice_write(&mut self.pos, self.inner, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for Cursor<Box<[u8]>> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        slice_write(&mut self.pos, &mut self.inner, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writing past the end of the vector extends it, filling any gap with zeros
+impl Write for Cursor<Vec<u8>> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let start = usize::try_from(self.pos).map_err(|_| Error::INVALID_INPUT)?;
+        let end = start + buf.len();
+        if self.inner.len() < end {
+            self.inner.resize(end, 0);
+        }
+        self.inner[start..end].copy_from_slice(buf);
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,421 @@
+//! Traits and helpers for byte-oriented I/O, modeled on `std::io`
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::{cmp, fmt, str};
+
+use crate::prelude::*;
+
+pub use self::buffered::{BufReader, BufWriter};
+pub use self::cursor::Cursor;
+pub use self::region::Region;
+pub(crate) use self::utf8::PartialUtf8;
+
+mod buffered;
+mod cursor;
+mod impls;
+mod region;
+mod utf8;
+
+/// The size of the buffers used by [`copy`] and the buffered wrappers
+pub const DEFAULT_BUF_SIZE: usize = 8192;
+
+/// An I/O error, wrapping the UEFI [`Status`] that caused it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Error(Status);
+
+impl Error {
+    /// A read could not be completed because the end of the data was reached
+    pub const UNEXPECTED_EOF: Self = Self(Status::END_OF_FILE);
+    /// A write could not be completed because no more bytes were accepted
+    pub const WRITE_ZERO: Self = Self(Status::END_OF_MEDIA);
+    /// A seek or buffer size was outside the valid range
+    pub const INVALID_INPUT: Self = Self(Status::INVALID_PARAMETER);
+    /// The data read was not in the expected format
+    pub const INVALID_DATA: Self = Self(Status::VOLUME_CORRUPTED);
+
+    pub fn new(status: Status) -> Self {
+        Self(status)
+    }
+
+    pub fn status(&self) -> Status {
+        self.0
+    }
+}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        Self(status)
+    }
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        err.0
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Enumeration of possible methods to seek within a file
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeekFrom {
+    /// Sets the offset to the provided number of bytes
+    Start(u64),
+    /// Sets the offset to the size of the file plus the specified number of bytes
+    End(i64),
+    /// Sets the offset to the current position plus the specified number of bytes
+    Current(i64),
+}
+
+/// A source of bytes
+pub trait Read {
+    /// Read some bytes into `buf`, returning how many were read. Zero means
+    /// the end of the data was reached or `buf` was empty.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Read exactly enough bytes to fill `buf`
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(Error::UNEXPECTED_EOF),
+                count => buf = &mut buf[count..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Read all bytes until the end of the data, appending them to `buf`
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        read_to_end(self, buf)
+    }
+
+    /// Read all bytes until the end of the data, appending them to `buf` if
+    /// they are valid UTF-8
+    fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
+        let mut data = Vec::new();
+        let count = self.read_to_end(&mut data)?;
+        let string = String::from_utf8(data).map_err(|_| Error::INVALID_DATA)?;
+        buf.push_str(&string);
+        Ok(count)
+    }
+
+    fn by_ref(&mut self) -> &mut Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+
+/// A sink for bytes
+pub trait Write {
+    /// Write some bytes from `buf`, returning how many were written
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    /// Make sure all buffered bytes reach their destination
+    fn flush(&mut self) -> Result<()>;
+
+    /// Write all bytes from `buf`
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(Error::WRITE_ZERO),
+                count => buf = &buf[count..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Write formatted text, used by the `write!` macro
+    fn write_fmt(&mut self, args: fmt::Arguments) -> Result<()> {
+        struct Adapter<'a, T: ?Sized> {
+            inner: &'a mut T,
+            error: Result<()>,
+        }
+
+        impl<'a, T: Write + ?Sized> fmt::Write for Adapter<'a, T> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.inner.write_all(s.as_bytes()).map_err(|err| {
+                    self.error = Err(err);
+                    fmt::Error
+                })
+            }
+        }
+
+        let mut adapter = Adapter {
+            inner: self,
+            error: Ok(()),
+        };
+        match fmt::write(&mut adapter, args) {
+            Ok(()) => Ok(()),
+            Err(_) => adapter.error.and(Err(Error::INVALID_DATA)),
+        }
+    }
+
+    fn by_ref(&mut self) -> &mut Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+
+/// A cursor that can be moved within a stream of bytes
+pub trait Seek {
+    /// Seek to an offset, returning the new position from the start
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+    /// Seek to the start
+    fn rewind(&mut self) -> Result<()> {
+        self.seek(SeekFrom::Start(0)).map(|_| ())
+    }
+
+    /// Returns the current position from the start
+    fn stream_position(&mut self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
+
+/// A [`Read`] with an internal buffer, allowing lines to be read
+pub trait BufRead: Read {
+    /// Returns the buffered data, reading more if the buffer is empty
+    fn fill_buf(&mut self) -> Result<&[u8]>;
+
+    /// Mark `amt` bytes of the buffer as read
+    fn consume(&mut self, amt: usize);
+
+    /// Read bytes until `byte` or the end of the data, appending them to `buf`
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut total = 0;
+        loop {
+            let (done, used) = {
+                let available = self.fill_buf()?;
+                match available.iter().position(|&b| b == byte) {
+                    Some(i) => {
+                        buf.extend_from_slice(&available[..=i]);
+                        (true, i + 1)
+                    }
+                    None => {
+                        buf.extend_from_slice(available);
+                        (available.is_empty(), available.len())
+                    }
+                }
+            };
+            self.consume(used);
+            total += used;
+            if done {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Read a line including its `\n`, appending it to `buf`
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let mut data = Vec::new();
+        let count = self.read_until(b'\n', &mut data)?;
+        let line = str::from_utf8(&data).map_err(|_| Error::INVALID_DATA)?;
+        buf.push_str(line);
+        Ok(count)
+    }
+
+    /// Iterate over the lines, without their `\n` or `\r\n` endings
+    fn lines(self) -> Lines<Self>
+    where
+        Self: Sized,
+    {
+        Lines { inner: self }
+    }
+}
+
+/// Iterator over the lines of a [`BufRead`]
+pub struct Lines<B> {
+    inner: B,
+}
+
+impl<B: BufRead> Iterator for Lines<B> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.inner.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Some(Ok(line))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// The default [`Read::read_to_end`], for implementations that override it
+/// to reserve space first
+///
+/// Reads go into a zeroed chunk of the spare capacity, which starts at
+/// [`DEFAULT_BUF_SIZE`] and doubles while reads fill it, so each byte is only
+/// zeroed about once.
+pub(crate) fn read_to_end<R: Read + ?Sized>(reader: &mut R, buf: &mut Vec<u8>) -> Result<usize> {
+    let start = buf.len();
+    let mut chunk = DEFAULT_BUF_SIZE;
+    loop {
+        if buf.capacity() - buf.len() < DEFAULT_BUF_SIZE {
+            buf.reserve(cmp::max(DEFAULT_BUF_SIZE, buf.len()));
+        }
+
+        let len = buf.len();
+        let read_len = cmp::min(buf.capacity() - len, chunk);
+        buf.resize(len + read_len, 0);
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => {
+                buf.truncate(len);
+                return Ok(len - start);
+            }
+            Ok(count) => {
+                buf.truncate(len + count);
+                if count == read_len {
+                    chunk = chunk.saturating_mul(2);
+                }
+            }
+            Err(err) => {
+                buf.truncate(len);
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// Copy all bytes from `reader` to `writer`, returning how many were copied
+pub fn copy<R: Read + ?Sized, W: Write + ?Sized>(reader: &mut R, writer: &mut W) -> Result<u64> {
+    let mut buf = vec![0; DEFAULT_BUF_SIZE];
+    let mut total = 0;
+    loop {
+        let count = reader.read(&mut buf)?;
+        if count == 0 {
+            return Ok(total);
+        }
+
+        writer.write_all(&buf[..count])?;
+        total += count as u64;
+    }
+}
+
+pub struct Stdout;
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, string: &str) -> core::result::Result<(), fmt::Error> {
+        let st = crate::system_table();
+
+        // Output is sent in chunks, which are flushed while there is still
+        // room for a carriage return, a character and the terminator
+        let mut buf = [0u16; 128];
+        let mut len = 0;
+        for c in string.chars() {
+            if c == '\n' {
+                buf[len] = u16::from(b'\r');
+                len += 1;
+            }
+            // The console only shows the Basic Multilingual Plane
+            buf[len] = u16::try_from(u32::from(c)).unwrap_or(0xFFFD);
+            len += 1;
+            if len + 3 > buf.len() {
+                buf[len] = 0;
+                let _ = (st.ConsoleOut.OutputString)(st.ConsoleOut, buf.as_ptr());
+                len = 0;
+            }
+        }
+        if len > 0 {
+            buf[len] = 0;
+            let _ = (st.ConsoleOut.OutputString)(st.ConsoleOut, buf.as_ptr());
+        }
+
+        Ok(())
+    }
+}
+
+/// The start of a UTF-8 sequence split across writes to [`Stdout`], which has
+/// nowhere else to keep it, packed by [`PartialUtf8::to_bits`]
+static STDOUT_PARTIAL: AtomicU32 = AtomicU32::new(0);
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut partial = PartialUtf8::from_bits(STDOUT_PARTIAL.swap(0, Ordering::Relaxed));
+        let res = partial.decode(buf, |text| {
+            fmt::Write::write_str(self, text).map_err(|_| Status::DEVICE_ERROR)
+        });
+        STDOUT_PARTIAL.store(partial.to_bits(), Ordering::Relaxed);
+        res?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub fn _print(args: fmt::Arguments) {
+    fmt::Write::write_fmt(&mut Stdout, args).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader returning at most `max` bytes at a time, recording the
+    /// largest buffer it was given
+    struct Short<'a> {
+        data: &'a [u8],
+        max: usize,
+        largest: usize,
+    }
+
+    impl<'a> Read for Short<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            self.largest = self.largest.max(buf.len());
+            let count = buf.len().min(self.max).min(self.data.len());
+            buf[..count].copy_from_slice(&self.data[..count]);
+            self.data = &self.data[count..];
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn read_to_end_appends_everything() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let mut reader = Short {
+            data: &data,
+            max: 1000,
+            largest: 0,
+        };
+        let mut buf = b"head".to_vec();
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), data.len());
+        assert_eq!(&buf[..4], b"head");
+        assert_eq!(&buf[4..], &data[..]);
+        // Short reads never fill a chunk, so it does not grow
+        assert_eq!(reader.largest, DEFAULT_BUF_SIZE);
+    }
+
+    #[test]
+    fn read_to_end_grows_chunks_on_full_reads() {
+        let data = vec![7; 1 << 20];
+        let mut reader = Short {
+            data: &data,
+            max: usize::MAX,
+            largest: 0,
+        };
+        let mut buf = Vec::new();
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), data.len());
+        assert_eq!(buf, data);
+        assert!(reader.largest > DEFAULT_BUF_SIZE);
+    }
+}
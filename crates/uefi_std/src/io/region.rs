@@ -0,0 +1,105 @@
+use core::cmp;
+
+use super::{Error, Read, Result, Seek, SeekFrom, Write};
+
+/// A window of `len` bytes starting at `offset` in a seekable stream, such
+/// as a partition of a disk
+///
+/// Positions are relative to the start of the window. Reads and writes stop
+/// at its end.
+pub struct Region<T> {
+    inner: T,
+    offset: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<T> Region<T> {
+    pub fn new(inner: T, offset: u64, len: u64) -> Self {
+        Region {
+            inner,
+            offset,
+            len,
+            pos: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bytes from the current position to the end
+    fn remaining(&self, max: usize) -> usize {
+        cmp::min(self.len.saturating_sub(self.pos), max as u64) as usize
+    }
+}
+
+impl<T: Read + Seek> Read for Region<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let count = self.remaining(buf.len());
+        if count == 0 {
+            return Ok(0);
+        }
+
+        self.inner.seek(SeekFrom::Start(self.offset + self.pos))?;
+        let count = self.inner.read(&mut buf[..count])?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<T: Write + Seek> Write for Region<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let count = self.remaining(buf.len());
+        if count == 0 {
+            return Ok(0);
+        }
+
+        self.inner.seek(SeekFrom::Start(self.offset + self.pos))?;
+        let count = self.inner.write(&buf[..count])?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T> Seek for Region<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::End(offset) => (self.len, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+
+        self.pos = base
+            .checked_add_signed(offset)
+            .ok_or(Error::INVALID_INPUT)?;
+        Ok(self.pos)
+    }
+}
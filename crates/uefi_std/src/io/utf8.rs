@@ -0,0 +1,125 @@
+use core::str;
+
+use crate::prelude::*;
+
+/// The start of a UTF-8 sequence split across calls to `io::Write::write`
+#[derive(Default)]
+pub(crate) struct PartialUtf8 {
+    bytes: [u8; 3],
+    len: usize,
+}
+
+impl PartialUtf8 {
+    /// Pack the kept bytes into a `u32`, for storing in an atomic
+    pub(crate) fn to_bits(&self) -> u32 {
+        let mut bits = [0; 4];
+        bits[..self.len].copy_from_slice(&self.bytes[..self.len]);
+        bits[3] = self.len as u8;
+        u32::from_le_bytes(bits)
+    }
+
+    pub(crate) fn from_bits(bits: u32) -> Self {
+        let [a, b, c, len] = bits.to_le_bytes();
+        PartialUtf8 {
+            bytes: [a, b, c],
+            len: usize::from(len),
+        }
+    }
+
+    /// Pass the text in `buf`, after any bytes kept from the last call, to
+    /// `f`, replacing invalid sequences and keeping an incomplete one at the
+    /// end for the next call
+    pub(crate) fn decode<F: FnMut(&str) -> Result<()>>(
+        &mut self,
+        buf: &[u8],
+        mut f: F,
+    ) -> Result<()> {
+        let mut joined = Vec::new();
+        let mut rest = if self.len > 0 {
+            joined.extend_from_slice(&self.bytes[..self.len]);
+            joined.extend_from_slice(buf);
+            self.len = 0;
+            &joined[..]
+        } else {
+            buf
+        };
+
+        loop {
+            match str::from_utf8(rest) {
+                Ok(text) => return f(text),
+                Err(err) => {
+                    let (valid, after) = rest.split_at(err.valid_up_to());
+                    // SAFETY: checked by from_utf8
+                    f(unsafe { str::from_utf8_unchecked(valid) })?;
+                    match err.error_len() {
+                        Some(len) => {
+                            f("\u{FFFD}")?;
+                            rest = &after[len..];
+                        }
+                        None => {
+                            // An incomplete sequence is at most 3 bytes
+                            self.bytes[..after.len()].copy_from_slice(after);
+                            self.len = after.len();
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(chunks: &[&[u8]]) -> String {
+        let mut partial = PartialUtf8::default();
+        let mut out = String::new();
+        for chunk in chunks {
+            partial
+                .decode(chunk, |text| {
+                    out.push_str(text);
+                    Ok(())
+                })
+                .unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn split_sequences_are_joined() {
+        let text = "a\u{E9}\u{2500}\u{1F600}b".as_bytes();
+        for i in 0..text.len() {
+            let (a, b) = text.split_at(i);
+            assert_eq!(decode_all(&[a, b]), "a\u{E9}\u{2500}\u{1F600}b");
+        }
+        let bytes = "\u{1F600}".as_bytes();
+        let chunks: Vec<&[u8]> = bytes.chunks(1).collect();
+        assert_eq!(decode_all(&chunks), "\u{1F600}");
+    }
+
+    #[test]
+    fn packed_in_bits() {
+        let mut partial = PartialUtf8::default();
+        partial.decode(b"a\xF0\x9F\x98", |_| Ok(())).unwrap();
+        let mut partial = PartialUtf8::from_bits(partial.to_bits());
+        let mut out = String::new();
+        partial
+            .decode(b"\x80", |text| {
+                out.push_str(text);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(out, "\u{1F600}");
+        assert_eq!(partial.to_bits(), 0);
+    }
+
+    #[test]
+    fn invalid_sequences_are_replaced() {
+        assert_eq!(decode_all(&[b"a\xFFb"]), "a\u{FFFD}b");
+        assert_eq!(decode_all(&[b"a\xE2\x94", b"b"]), "a\u{FFFD}b");
+        // An incomplete sequence at the end is kept, not replaced
+        assert_eq!(decode_all(&[b"a\xE2\x94"]), "a");
+    }
+}
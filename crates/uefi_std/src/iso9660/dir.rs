@@ -0,0 +1,241 @@
+use core::char;
+use uefi::time::Time;
+
+use crate::bytes::le32;
+use crate::prelude::*;
+
+const FLAG_HIDDEN: u8 = 0x01;
+pub(super) const FLAG_DIRECTORY: u8 = 0x02;
+pub(super) const FLAG_MULTI_EXTENT: u8 = 0x80;
+/// The size of a directory record before its name
+const RECORD_SIZE: usize = 33;
+/// The most continuation areas followed for one record
+const MAX_CONTINUATIONS: usize = 16;
+
+/// The names read from directory records
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NameKind {
+    /// Uppercase 8.3-style names from the primary volume descriptor
+    Iso,
+    /// UCS-2 names from a Joliet supplementary volume descriptor
+    Joliet,
+    /// POSIX names from Rock Ridge entries in the primary directories
+    RockRidge,
+}
+
+/// Convert a seven byte recording date to a UEFI time
+fn record_time(data: &[u8]) -> Time {
+    let mut time = Time::default();
+    if data[..6].iter().all(|&b| b == 0) {
+        return time;
+    }
+
+    time.Year = 1900 + u16::from(data[0]);
+    time.Month = data[1];
+    time.Day = data[2];
+    time.Hour = data[3];
+    time.Minute = data[4];
+    time.Second = data[5];
+    // The offset from UTC is in 15 minute units
+    time.TimeZone = (i16::from(data[6] as i8) * 15) as u16;
+    time
+}
+
+/// The fields of one directory record
+pub(super) struct Record<'a> {
+    pub extent: u32,
+    pub len: u32,
+    pub flags: u8,
+    pub recorded: Time,
+    pub name: &'a [u8],
+    pub system_use: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Parse a record, where `data` is exactly as long as the record
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let name_len = usize::from(*data.get(32).ok_or(Status::VOLUME_CORRUPTED)?);
+        if data.len() < RECORD_SIZE + name_len {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        // A padding byte keeps the system use area at an even offset
+        let system_use = (RECORD_SIZE + name_len + 1) & !1;
+        Ok(Record {
+            extent: le32(data, 2),
+            len: le32(data, 10),
+            flags: data[25],
+            recorded: record_time(&data[18..25]),
+            name: &data[RECORD_SIZE..RECORD_SIZE + name_len],
+            system_use: data.get(system_use..).unwrap_or(&[]),
+        })
+    }
+
+    /// The name, without the `;1` version or the dot ending names without an
+    /// extension
+    pub fn name(&self, kind: NameKind) -> String {
+        let name = match self.name {
+            [0] => return ".".to_string(),
+            [1] => return "..".to_string(),
+            name if kind == NameKind::Joliet => char::decode_utf16(
+                name.chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]])),
+            )
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>(),
+            name => name.iter().map(|&b| char::from(b)).collect(),
+        };
+
+        let name = name.split(';').next().unwrap_or("");
+        name.strip_suffix('.').unwrap_or(name).to_string()
+    }
+}
+
+/// A file or directory in an ISO 9660 directory
+#[derive(Clone, Debug)]
+pub struct IsoDirEntry {
+    pub(super) name: String,
+    pub(super) flags: u8,
+    /// The first block and byte length of each extent of the data
+    pub(super) extents: Vec<(u32, u32)>,
+    pub(super) modified: Time,
+    pub(super) mode: Option<u32>,
+}
+
+impl IsoDirEntry {
+    pub(super) fn new(name: String, record: &Record) -> Self {
+        IsoDirEntry {
+            name,
+            flags: record.flags,
+            extents: vec![(record.extent, record.len)],
+            modified: record.recorded,
+            mode: None,
+        }
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.flags & FLAG_DIRECTORY != 0
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.flags & FLAG_HIDDEN != 0
+    }
+
+    /// Returns true for the `.` and `..` entries
+    pub fn is_dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+
+    /// The length of the data in bytes, across every extent
+    pub fn len(&self) -> u64 {
+        self.extents.iter().map(|&(_, len)| u64::from(len)).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The first block of the data
+    pub fn location(&self) -> u32 {
+        self.extents[0].0
+    }
+
+    pub fn modified(&self) -> Time {
+        self.modified
+    }
+
+    /// The POSIX file mode from Rock Ridge, if present
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// Returns true if `name` matches the name, ignoring case
+    pub(super) fn matches(&self, name: &str) -> bool {
+        self.name.len() == name.len()
+            && self
+                .name
+                .chars()
+                .zip(name.chars())
+                .all(|(a, b)| a == b || a.to_uppercase().eq(b.to_uppercase()))
+    }
+}
+
+/// The skip length declared by the SUSP `SP` entry, which begins the system
+/// use area of the root's `.` record when Rock Ridge is in use
+pub(super) fn susp_skip(area: &[u8]) -> Option<usize> {
+    if area.len() >= 7 && &area[..2] == b"SP" && area[4..6] == [0xBE, 0xEF] {
+        Some(usize::from(area[6]))
+    } else {
+        None
+    }
+}
+
+/// The Rock Ridge fields of a record
+#[derive(Default)]
+pub(super) struct RockRidge {
+    pub name: Option<String>,
+    pub mode: Option<u32>,
+    /// The directory was moved here from deeper in the tree, and is hidden
+    pub relocated: bool,
+    /// The record stands in for a directory relocated to this block
+    pub child_link: Option<u32>,
+}
+
+/// Parse the SUSP entries of a system use area after `skip` bytes, reading
+/// continuation areas with `read`, which is given a block, offset and length
+pub(super) fn rock_ridge<F>(area: &[u8], skip: usize, mut read: F) -> Result<RockRidge>
+where
+    F: FnMut(u32, u32, u32) -> Result<Vec<u8>>,
+{
+    let mut rr = RockRidge::default();
+    let mut name = Vec::new();
+    let mut has_name = false;
+
+    let mut data = area.get(skip..).unwrap_or(&[]).to_vec();
+    for _ in 0..MAX_CONTINUATIONS {
+        let mut next = None;
+        let mut offset = 0;
+        while offset + 4 <= data.len() {
+            let len = usize::from(data[offset + 2]);
+            if len < 4 || offset + len > data.len() {
+                break;
+            }
+
+            let entry = &data[offset..offset + len];
+            match &entry[..2] {
+                // Names of `.` and `..` are flagged rather than stored
+                b"NM" if len >= 5 && entry[4] & 0x06 == 0 => {
+                    name.extend_from_slice(&entry[5..]);
+                    has_name = true;
+                }
+                b"PX" if len >= 8 => rr.mode = Some(le32(entry, 4)),
+                b"RE" => rr.relocated = true,
+                b"CL" if len >= 8 => rr.child_link = Some(le32(entry, 4)),
+                b"CE" if len >= 28 => {
+                    next = Some((le32(entry, 4), le32(entry, 12), le32(entry, 20)))
+                }
+                b"ST" => break,
+                _ => (),
+            }
+            offset += len;
+        }
+
+        match next {
+            Some((block, offset, len)) => data = read(block, offset, len)?,
+            None => break,
+        }
+    }
+
+    if has_name {
+        rr.name = Some(String::from_utf8_lossy(&name).into_owned());
+    }
+    Ok(rr)
+}
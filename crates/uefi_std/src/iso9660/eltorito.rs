@@ -0,0 +1,164 @@
+use crate::bytes::{le16, le32};
+use crate::prelude::*;
+
+pub const PLATFORM_X86: u8 = 0x00;
+pub const PLATFORM_POWERPC: u8 = 0x01;
+pub const PLATFORM_MAC: u8 = 0x02;
+pub const PLATFORM_EFI: u8 = 0xEF;
+
+/// The size of the sectors counted by boot entries
+pub const VIRTUAL_SECTOR_SIZE: u64 = 512;
+
+const ENTRY_SIZE: usize = 32;
+const BOOTABLE: u8 = 0x88;
+const HEADER_MORE: u8 = 0x90;
+const HEADER_FINAL: u8 = 0x91;
+const EXTENSION: u8 = 0x44;
+
+/// An initial or section entry of the boot catalog
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BootEntry {
+    pub bootable: bool,
+    /// The platform of the section holding the entry
+    pub platform: u8,
+    /// The emulation type, where 0 loads the image without emulating a disk
+    pub media: u8,
+    pub load_segment: u16,
+    pub system_type: u8,
+    /// The length in virtual sectors, which is 0 or 1 for some large images
+    pub sector_count: u16,
+    /// The first block of the image
+    pub load_rba: u32,
+}
+
+impl BootEntry {
+    fn parse(data: &[u8], platform: u8) -> Self {
+        BootEntry {
+            bootable: data[0] == BOOTABLE,
+            platform,
+            media: data[1],
+            load_segment: le16(data, 2),
+            system_type: data[4],
+            sector_count: le16(data, 6),
+            load_rba: le32(data, 8),
+        }
+    }
+
+    pub fn is_no_emulation(&self) -> bool {
+        self.media & 0xF == 0
+    }
+}
+
+/// A parsed El Torito boot catalog
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BootCatalog {
+    /// The platform of the validation entry, which the initial entry uses
+    pub platform: u8,
+    /// The manufacturer ID string
+    pub id: String,
+    /// The initial entry, then the entries of each section
+    pub entries: Vec<BootEntry>,
+}
+
+impl BootCatalog {
+    /// Parse a catalog, failing with `BUFFER_TOO_SMALL` if its sections
+    /// continue past the end of `data`
+    pub(super) fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 2 * ENTRY_SIZE {
+            return Err(Status::BUFFER_TOO_SMALL);
+        }
+
+        // The words of the validation entry sum to zero
+        let validation = &data[..ENTRY_SIZE];
+        let sum = validation.chunks_exact(2).fold(0u16, |sum, w| {
+            sum.wrapping_add(u16::from_le_bytes([w[0], w[1]]))
+        });
+        if validation[0] != 1 || validation[30..32] != [0x55, 0xAA] || sum != 0 {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        let platform = validation[1];
+        let id: String = validation[4..28]
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| char::from(b))
+            .collect();
+        let mut entries = vec![BootEntry::parse(&data[ENTRY_SIZE..], platform)];
+
+        let mut offset = 2 * ENTRY_SIZE;
+        loop {
+            let header = data
+                .get(offset..offset + ENTRY_SIZE)
+                .ok_or(Status::BUFFER_TOO_SMALL)?;
+            let more = match header[0] {
+                HEADER_MORE => true,
+                HEADER_FINAL => false,
+                _ => break,
+            };
+            let section_platform = header[1];
+            let mut remaining = le16(header, 2);
+            offset += ENTRY_SIZE;
+
+            while remaining > 0 {
+                let entry = data
+                    .get(offset..offset + ENTRY_SIZE)
+                    .ok_or(Status::BUFFER_TOO_SMALL)?;
+                offset += ENTRY_SIZE;
+
+                // Extensions hold more selection criteria for the entry before
+                if entry[0] != EXTENSION {
+                    entries.push(BootEntry::parse(entry, section_platform));
+                    remaining -= 1;
+                }
+            }
+
+            if !more {
+                break;
+            }
+        }
+
+        Ok(BootCatalog {
+            platform,
+            id: id.trim_end().to_string(),
+            entries,
+        })
+    }
+
+    pub fn initial_entry(&self) -> &BootEntry {
+        &self.entries[0]
+    }
+
+    /// The bootable entries for UEFI
+    pub fn efi_entries(&self) -> impl Iterator<Item = &BootEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.bootable && entry.platform == PLATFORM_EFI)
+    }
+}
+
+/// A boot image located in the volume
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BootImage {
+    pub entry: BootEntry,
+    /// The start of the image in bytes
+    pub offset: u64,
+    /// The length of the image in bytes
+    pub len: u64,
+}
+
+/// The length of a FAT image from its boot sector
+pub(super) fn fat_image_len(boot: &[u8]) -> Option<u64> {
+    if boot[510..512] != [0x55, 0xAA] {
+        return None;
+    }
+
+    let sector_size = u64::from(le16(boot, 11));
+    let sectors = match le16(boot, 19) {
+        0 => u64::from(le32(boot, 32)),
+        sectors => u64::from(sectors),
+    };
+    if !sector_size.is_power_of_two() || sector_size < 512 || sectors == 0 {
+        return None;
+    }
+    Some(sectors * sector_size)
+}
@@ -0,0 +1,658 @@
+//! An ISO 9660 reader with Rock Ridge, Joliet and El Torito support
+//!
+//! [`Iso9660`] works over any [`Read`] + [`Seek`] source positioned at the
+//! start of the image, such as a CD-ROM or USB disk with a hybrid image, or an
+//! image file opened with [`crate::fs`]. The El Torito boot catalog locates
+//! the EFI boot image, which is usually a FAT image for
+//! [`FatFs`](crate::fat::FatFs).
+
+use core::cmp;
+
+use self::dir::{Record, FLAG_DIRECTORY, FLAG_MULTI_EXTENT};
+use crate::bytes::{le16, le32};
+use crate::io::{self, Read, Region, Seek, SeekFrom};
+use crate::path::{Component, Path};
+use crate::prelude::*;
+
+pub use self::dir::{IsoDirEntry, NameKind};
+pub use self::eltorito::{
+    BootCatalog, BootEntry, BootImage, PLATFORM_EFI, PLATFORM_MAC, PLATFORM_POWERPC, PLATFORM_X86,
+    VIRTUAL_SECTOR_SIZE,
+};
+
+mod dir;
+mod eltorito;
+
+/// Volume descriptors are in 2048 byte sectors after the system area
+const DESCRIPTOR_SIZE: usize = 2048;
+const DESCRIPTOR_START: u64 = 16;
+const MAX_DESCRIPTORS: u64 = 64;
+
+const BOOT_RECORD: u8 = 0;
+const PRIMARY: u8 = 1;
+const SUPPLEMENTARY: u8 = 2;
+const TERMINATOR: u8 = 255;
+
+const STANDARD_ID: &[u8] = b"CD001";
+const EL_TORITO_ID: &[u8] = b"EL TORITO SPECIFICATION";
+
+/// The largest directory, continuation area or boot catalog that will be read
+const MAX_DIR_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_CONTINUATION_SIZE: u32 = 64 * 1024;
+const MAX_CATALOG_SIZE: usize = 64 * 1024;
+
+/// Returns true for a supplementary descriptor with a Joliet escape sequence
+fn is_joliet(descriptor: &[u8]) -> bool {
+    &descriptor[88..90] == b"%/" && matches!(descriptor[90], b'@' | b'C' | b'E')
+}
+
+/// The root directory entry of a volume descriptor
+fn root_entry(descriptor: &[u8]) -> Result<IsoDirEntry> {
+    let record = Record::parse(&descriptor[156..190])?;
+    let mut root = IsoDirEntry::new(String::new(), &record);
+    root.flags |= FLAG_DIRECTORY;
+    Ok(root)
+}
+
+/// A volume identifier, which is UCS-2 for Joliet
+fn volume_id(descriptor: &[u8], kind: NameKind) -> String {
+    let data = &descriptor[40..72];
+    let id: String = match kind {
+        NameKind::Joliet => char::decode_utf16(
+            data.chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]])),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect(),
+        _ => data.iter().map(|&b| char::from(b)).collect(),
+    };
+    id.trim_end_matches([' ', '\0']).to_string()
+}
+
+/// An ISO 9660 volume
+pub struct Iso9660<R> {
+    inner: R,
+    names: NameKind,
+    block_size: u32,
+    blocks: u32,
+    volume_id: String,
+    root: IsoDirEntry,
+    /// The bytes skipped at the start of each system use area
+    susp_skip: usize,
+    /// The block of the El Torito boot catalog
+    catalog: Option<u32>,
+}
+
+impl<R: Read + Seek> Iso9660<R> {
+    /// Read the volume descriptors of the image in `inner`, preferring Rock
+    /// Ridge names, then Joliet names
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut primary = None;
+        let mut joliet = None;
+        let mut catalog = None;
+
+        let mut descriptor = [0; DESCRIPTOR_SIZE];
+        for sector in DESCRIPTOR_START..DESCRIPTOR_START + MAX_DESCRIPTORS {
+            inner.seek(SeekFrom::Start(sector * DESCRIPTOR_SIZE as u64))?;
+            inner.read_exact(&mut descriptor)?;
+            if &descriptor[1..6] != STANDARD_ID {
+                break;
+            }
+
+            match descriptor[0] {
+                BOOT_RECORD if descriptor[7..7 + EL_TORITO_ID.len()] == *EL_TORITO_ID => {
+                    catalog = Some(le32(&descriptor, 0x47));
+                }
+                PRIMARY if primary.is_none() => primary = Some(descriptor.to_vec()),
+                SUPPLEMENTARY if joliet.is_none() && is_joliet(&descriptor) => {
+                    joliet = Some(descriptor.to_vec())
+                }
+                TERMINATOR => break,
+                _ => (),
+            }
+        }
+
+        let primary = primary.ok_or(Status::UNSUPPORTED)?;
+        let block_size = u32::from(le16(&primary, 128));
+        if !block_size.is_power_of_two() || !(512..=2048).contains(&block_size) {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        let mut iso = Iso9660 {
+            inner,
+            names: NameKind::Iso,
+            block_size,
+            blocks: le32(&primary, 80),
+            volume_id: volume_id(&primary, NameKind::Iso),
+            root: root_entry(&primary)?,
+            susp_skip: 0,
+            catalog,
+        };
+
+        // Rock Ridge is announced by an `SP` entry in the root's `.` record
+        let mut first = vec![0; block_size as usize];
+        iso.read_at(iso.block_offset(iso.root.location()), &mut first)?;
+        let len = usize::from(first[0]);
+        let record = Record::parse(first.get(..len).ok_or(Status::VOLUME_CORRUPTED)?)?;
+        if let Some(skip) = dir::susp_skip(record.system_use) {
+            iso.names = NameKind::RockRidge;
+            iso.susp_skip = skip;
+        } else if let Some(joliet) = joliet {
+            iso.names = NameKind::Joliet;
+            iso.volume_id = volume_id(&joliet, NameKind::Joliet);
+            iso.root = root_entry(&joliet)?;
+        }
+
+        Ok(iso)
+    }
+
+    /// The kind of names in use
+    pub fn names(&self) -> NameKind {
+        self.names
+    }
+
+    pub fn volume_id(&self) -> &str {
+        &self.volume_id
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// The size of the volume in bytes
+    pub fn volume_size(&self) -> u64 {
+        u64::from(self.blocks) * u64::from(self.block_size)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn block_offset(&self, block: u32) -> u64 {
+        u64::from(block) * u64::from(self.block_size)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(buf)?;
+        Ok(())
+    }
+
+    /// Read a SUSP continuation area
+    fn read_continuation(&mut self, block: u32, offset: u32, len: u32) -> Result<Vec<u8>> {
+        if len > MAX_CONTINUATION_SIZE {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        let mut data = vec![0; len as usize];
+        self.read_at(self.block_offset(block) + u64::from(offset), &mut data)?;
+        Ok(data)
+    }
+
+    /// Read the entries of the directory `dir`, including `.` and `..`
+    fn dir_entries(&mut self, dir: &IsoDirEntry) -> Result<Vec<IsoDirEntry>> {
+        if !dir.is_dir() {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        if dir.len() > MAX_DIR_SIZE {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        let mut data = vec![0; dir.len() as usize];
+        self.read_entry(dir, 0, &mut data)?;
+
+        let sector = self.block_size as usize;
+        let mut entries: Vec<IsoDirEntry> = Vec::new();
+        let mut continuing = false;
+        let mut offset = 0;
+        while offset < data.len() {
+            // Records never cross a sector, leaving padding at the end
+            let len = usize::from(data[offset]);
+            if len == 0 {
+                offset = (offset / sector + 1) * sector;
+                continue;
+            }
+
+            let record = Record::parse(
+                data.get(offset..offset + len)
+                    .ok_or(Status::VOLUME_CORRUPTED)?,
+            )?;
+            offset += len;
+
+            // Files over 4 GiB have a record for each extent
+            if continuing {
+                if let Some(last) = entries.last_mut() {
+                    last.extents.push((record.extent, record.len));
+                }
+                continuing = record.flags & FLAG_MULTI_EXTENT != 0;
+                continue;
+            }
+            continuing = record.flags & FLAG_MULTI_EXTENT != 0;
+
+            let mut entry = IsoDirEntry::new(record.name(self.names), &record);
+            if self.names == NameKind::RockRidge {
+                let skip = self.susp_skip;
+                let rr = dir::rock_ridge(record.system_use, skip, |block, offset, len| {
+                    self.read_continuation(block, offset, len)
+                })?;
+                if rr.relocated {
+                    continue;
+                }
+                if let Some(name) = rr.name {
+                    entry.name = name;
+                }
+                entry.mode = rr.mode;
+
+                // The `.` record of a relocated directory describes it
+                if let Some(block) = rr.child_link {
+                    let mut first = vec![0; sector];
+                    self.read_at(self.block_offset(block), &mut first)?;
+                    let len = usize::from(first[0]);
+                    let moved = Record::parse(first.get(..len).ok_or(Status::VOLUME_CORRUPTED)?)?;
+                    entry.flags |= FLAG_DIRECTORY;
+                    entry.extents = vec![(moved.extent, moved.len)];
+                }
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    pub fn root(&self) -> IsoDirEntry {
+        self.root.clone()
+    }
+
+    /// Look up `path`, relative to the root directory. Exact names are
+    /// preferred, then names that differ only in case.
+    pub fn entry<P: AsRef<Path>>(&mut self, path: P) -> Result<IsoDirEntry> {
+        let path = path.as_ref().normalize();
+        let mut entry = self.root();
+        for component in path.components() {
+            let name = match component {
+                Component::Normal(name) => name,
+                _ => continue,
+            };
+            if !entry.is_dir() {
+                return Err(Status::NOT_FOUND);
+            }
+
+            let mut children = self.dir_entries(&entry)?;
+            children.retain(|child| !child.is_dot());
+            let index = children
+                .iter()
+                .position(|child| child.file_name() == name)
+                .or_else(|| children.iter().position(|child| child.matches(name)))
+                .ok_or(Status::NOT_FOUND)?;
+            entry = children.swap_remove(index);
+        }
+        Ok(entry)
+    }
+
+    /// List the directory at `path`, without `.` and `..`
+    pub fn read_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<IsoDirEntry>> {
+        let dir = self.entry(path)?;
+        let mut entries = self.dir_entries(&dir)?;
+        entries.retain(|entry| !entry.is_dot());
+        Ok(entries)
+    }
+
+    /// Open the file at `path` for reading
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> Result<IsoFile<'_, R>> {
+        let entry = self.entry(path)?;
+        if entry.is_dir() {
+            return Err(Status::ACCESS_DENIED);
+        }
+        Ok(IsoFile {
+            iso: self,
+            entry,
+            position: 0,
+        })
+    }
+
+    /// Read the whole file at `path`
+    pub fn read<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open(path)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Read the data of `entry` at `position`, across its extents
+    fn read_entry(&mut self, entry: &IsoDirEntry, position: u64, buf: &mut [u8]) -> Result<usize> {
+        let count = cmp::min(buf.len() as u64, entry.len().saturating_sub(position)) as usize;
+
+        let mut done = 0;
+        let mut start = 0;
+        for &(block, len) in &entry.extents {
+            if done == count {
+                break;
+            }
+
+            let len = u64::from(len);
+            let pos = position + done as u64;
+            if pos < start + len {
+                let offset = pos - start;
+                let chunk = cmp::min((count - done) as u64, len - offset) as usize;
+                self.read_at(
+                    self.block_offset(block) + offset,
+                    &mut buf[done..done + chunk],
+                )?;
+                done += chunk;
+            }
+            start += len;
+        }
+        Ok(done)
+    }
+
+    /// Read the El Torito boot catalog, if the image has one
+    pub fn boot_catalog(&mut self) -> Result<Option<BootCatalog>> {
+        let block = match self.catalog {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+
+        let mut data = Vec::new();
+        loop {
+            let start = data.len();
+            data.resize(start + self.block_size as usize, 0);
+            let offset = self.block_offset(block) + start as u64;
+            self.read_at(offset, &mut data[start..])?;
+
+            match BootCatalog::parse(&data) {
+                // The sections continue in the next block
+                Err(Status::BUFFER_TOO_SMALL) if data.len() < MAX_CATALOG_SIZE => (),
+                result => return result.map(Some),
+            }
+        }
+    }
+
+    /// Locate the first boot image for UEFI
+    pub fn efi_boot_image(&mut self) -> Result<Option<BootImage>> {
+        let entry = match self.boot_catalog()? {
+            Some(catalog) => match catalog.efi_entries().next() {
+                Some(entry) => entry.clone(),
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+
+        // A sector count of 0 or 1 is recorded for images too large to count,
+        // so the boot sector of the FAT image gives the length instead
+        let offset = self.block_offset(entry.load_rba);
+        let mut len = u64::from(entry.sector_count) * VIRTUAL_SECTOR_SIZE;
+        if entry.sector_count <= 1 {
+            let mut boot = [0; 512];
+            self.read_at(offset, &mut boot)?;
+            if let Some(image_len) = eltorito::fat_image_len(&boot) {
+                len = image_len;
+            }
+        }
+
+        Ok(Some(BootImage { entry, offset, len }))
+    }
+
+    /// A reader over the data of a boot image
+    pub fn boot_image_reader(&mut self, image: &BootImage) -> Region<&mut R> {
+        Region::new(&mut self.inner, image.offset, image.len)
+    }
+}
+
+/// A file opened for reading
+pub struct IsoFile<'a, R> {
+    iso: &'a mut Iso9660<R>,
+    entry: IsoDirEntry,
+    position: u64,
+}
+
+impl<'a, R: Read + Seek> IsoFile<'a, R> {
+    pub fn entry(&self) -> &IsoDirEntry {
+        &self.entry
+    }
+
+    pub fn len(&self) -> u64 {
+        self.entry.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry.is_empty()
+    }
+}
+
+impl<'a, R: Read + Seek> Read for IsoFile<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.iso.read_entry(&self.entry, self.position, buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for IsoFile<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.len(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        self.position = base
+            .checked_add_signed(offset)
+            .ok_or(io::Error::INVALID_INPUT)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fat::tests::{FAT12, LINE};
+    use crate::fat::FatFs;
+    use crate::inflate::zlib_decompress;
+    use crate::io::Cursor;
+
+    const LONG_NAME: &str = "long-name-long-name-long-name-long-name-long-name-long-name-\
+        long-name-long-name-long-name-long-name-long-name-long-name-long-name-long-name-\
+        long-name-long-name-long-name-long-name-long-name-long-name-long-name-long-name-.txt";
+
+    fn image() -> Vec<u8> {
+        zlib_decompress(include_bytes!("../../testdata/iso9660/test.iso.z")).unwrap()
+    }
+
+    /// Hide the Rock Ridge `SP` entry, and also the Joliet descriptor if
+    /// `joliet` is false
+    fn without_extensions(joliet: bool) -> Iso9660<Cursor<Vec<u8>>> {
+        let mut image = image();
+        let primary = DESCRIPTOR_START as usize * DESCRIPTOR_SIZE;
+        let root = le32(&image, primary + 156 + 2) as usize * DESCRIPTOR_SIZE;
+        image[root + 34] = b'X';
+        if !joliet {
+            image[primary + 2 * DESCRIPTOR_SIZE + 88] = b'X';
+        }
+        Iso9660::new(Cursor::new(image)).unwrap()
+    }
+
+    /// The block of the boot catalog and the FAT image it points to
+    fn boot_blocks(image: &[u8]) -> (usize, u32) {
+        let mut iso = Iso9660::new(Cursor::new(image.to_vec())).unwrap();
+        let efiboot = iso.entry("efiboot.img").unwrap().location();
+        (iso.catalog.unwrap() as usize, efiboot)
+    }
+
+    /// The sorted names, leaving out the directory that holds relocated ones,
+    /// which only some tools write
+    fn names(entries: &[IsoDirEntry]) -> Vec<&str> {
+        let mut names = entries
+            .iter()
+            .map(|entry| entry.file_name())
+            .filter(|name| !name.eq_ignore_ascii_case("rr_moved"))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rock_ridge() {
+        let mut iso = Iso9660::new(Cursor::new(image())).unwrap();
+        assert_eq!(iso.names(), NameKind::RockRidge);
+        assert_eq!(iso.volume_id(), "REDOX_TEST");
+        assert_eq!(iso.block_size(), 2048);
+        assert!(iso.volume_size() > 1000 * 1024);
+
+        let root = iso.read_dir("/").unwrap();
+        assert_eq!(
+            names(&root),
+            ["EFI", "a", "boot.cat", "docs", "efiboot.img"]
+        );
+        let docs = iso.read_dir("docs").unwrap();
+        assert_eq!(
+            names(&docs),
+            ["Read Me.txt", LONG_NAME, "\u{DC}n\u{EF}c\u{F6}d\u{E9}.txt"]
+        );
+
+        let entry = iso.entry("docs/Read Me.txt").unwrap();
+        assert_eq!(entry.mode(), Some(0o100444));
+        assert_eq!(entry.len(), 2800);
+        assert_eq!(iso.entry("EFI").unwrap().mode(), Some(0o40555));
+        assert_eq!(iso.read("docs/Read Me.txt").unwrap(), LINE.repeat(200));
+        // The name continues in a continuation area
+        assert_eq!(iso.read(format!("docs/{LONG_NAME}")).unwrap(), b"long\n");
+        assert_eq!(iso.read("EFI/BOOT/BOOTX64.EFI").unwrap().len(), 64);
+    }
+
+    #[test]
+    fn relocated_directory() {
+        let mut iso = Iso9660::new(Cursor::new(image())).unwrap();
+        let dir = iso.entry("a/b/c/d/e/f/g/h").unwrap();
+        assert!(dir.is_dir());
+        // The relocated directory is only listed where it belongs
+        if let Ok(moved) = iso.read_dir("rr_moved") {
+            assert!(moved.is_empty());
+        }
+        assert_eq!(names(&iso.read_dir("a/b/c/d/e/f/g/h").unwrap()), ["i"]);
+        assert_eq!(
+            names(&iso.read_dir("a/b/c/d/e/f/g/h/i").unwrap()),
+            ["deep.txt"]
+        );
+        assert_eq!(iso.read("a/b/c/d/e/f/g/h/i/deep.txt").unwrap(), b"deep\n");
+    }
+
+    #[test]
+    fn joliet() {
+        let mut iso = without_extensions(true);
+        assert_eq!(iso.names(), NameKind::Joliet);
+        assert_eq!(iso.volume_id(), "REDOX_TEST");
+        let docs = iso.read_dir("docs").unwrap();
+        assert!(docs.iter().all(|entry| entry.mode().is_none()));
+        // Joliet names are limited to 64 characters
+        assert_eq!(
+            names(&docs),
+            [
+                "Read Me.txt",
+                &LONG_NAME[..64],
+                "\u{DC}n\u{EF}c\u{F6}d\u{E9}.txt"
+            ]
+        );
+        assert_eq!(iso.read("docs/Read Me.txt").unwrap(), LINE.repeat(200));
+        assert_eq!(iso.read("A/B/C/D/E/F/G/H/I/DEEP.TXT").unwrap(), b"deep\n");
+    }
+
+    #[test]
+    fn iso_names() {
+        let mut iso = without_extensions(false);
+        assert_eq!(iso.names(), NameKind::Iso);
+        let root = iso.read_dir("/").unwrap();
+        assert_eq!(
+            names(&root),
+            ["A", "BOOT.CAT", "DOCS", "EFI", "EFIBOOT.IMG"]
+        );
+        // Names match ignoring case
+        assert_eq!(iso.read("efi/boot/bootx64.efi").unwrap().len(), 64);
+    }
+
+    #[test]
+    fn open_errors() {
+        let mut iso = Iso9660::new(Cursor::new(image())).unwrap();
+        assert_eq!(iso.entry("docs/missing").err(), Some(Status::NOT_FOUND));
+        assert_eq!(
+            iso.entry("docs/Read Me.txt/x").err(),
+            Some(Status::NOT_FOUND)
+        );
+        assert_eq!(iso.open("docs").err(), Some(Status::ACCESS_DENIED));
+        assert_eq!(
+            iso.read_dir("docs/Read Me.txt").err(),
+            Some(Status::INVALID_PARAMETER)
+        );
+
+        let mut image = image();
+        image[DESCRIPTOR_START as usize * DESCRIPTOR_SIZE + 1] = b'X';
+        assert_eq!(
+            Iso9660::new(Cursor::new(image)).err(),
+            Some(Status::UNSUPPORTED)
+        );
+    }
+
+    #[test]
+    fn seek_and_read() {
+        let mut iso = Iso9660::new(Cursor::new(image())).unwrap();
+        let mut file = iso.open("docs/Read Me.txt").unwrap();
+        assert_eq!(file.len(), 2800);
+        file.seek(SeekFrom::End(-(LINE.len() as i64))).unwrap();
+        let mut line = [0; 14];
+        file.read_exact(&mut line).unwrap();
+        assert_eq!(line, LINE);
+        assert_eq!(file.read(&mut line).unwrap(), 0);
+    }
+
+    #[test]
+    fn el_torito() {
+        let mut iso = Iso9660::new(Cursor::new(image())).unwrap();
+        let catalog = iso.boot_catalog().unwrap().unwrap();
+        assert!(catalog.initial_entry().bootable);
+        assert_eq!(catalog.efi_entries().count(), 1);
+
+        let image = iso.efi_boot_image().unwrap().unwrap();
+        assert!(image.entry.is_no_emulation());
+        assert_eq!(image.len, 1000 * 1024);
+        assert_eq!(
+            image.offset,
+            iso.entry("efiboot.img").unwrap().location() as u64 * 2048
+        );
+
+        let mut data = Vec::new();
+        iso.boot_image_reader(&image)
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, zlib_decompress(FAT12).unwrap());
+        let mut fs = FatFs::new(iso.boot_image_reader(&image)).unwrap();
+        assert_eq!(fs.read("short.txt").unwrap(), LINE);
+    }
+
+    #[test]
+    fn uncounted_boot_image() {
+        // Record one sector for the image, as some tools do for large ones
+        let mut image = image();
+        let (catalog, efiboot) = boot_blocks(&image);
+        let entries = &mut image[catalog * 2048..(catalog + 1) * 2048];
+        for entry in entries.chunks_exact_mut(32) {
+            if entry[0] == 0x88 && le32(entry, 8) == efiboot {
+                entry[6..8].copy_from_slice(&1u16.to_le_bytes());
+            }
+        }
+
+        // The length comes from the FAT boot sector instead
+        let mut iso = Iso9660::new(Cursor::new(image)).unwrap();
+        let image = iso.efi_boot_image().unwrap().unwrap();
+        assert_eq!(image.entry.sector_count, 1);
+        assert_eq!(image.len, 1000 * 1024);
+    }
+
+    #[test]
+    fn bad_boot_catalog() {
+        let mut image = image();
+        let (catalog, _) = boot_blocks(&image);
+        image[catalog * 2048 + 4] = b'X';
+        let mut iso = Iso9660::new(Cursor::new(image)).unwrap();
+        assert_eq!(iso.boot_catalog().err(), Some(Status::VOLUME_CORRUPTED));
+    }
+}
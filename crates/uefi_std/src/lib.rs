@@ -1,27 +1,50 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 extern crate alloc as alloc_crate;
 
 #[macro_use]
 mod macros;
 
+mod bytes;
+
 // Re-export uefi and uefi_alloc
 pub use uefi;
+// Host tests use the allocator of std, which this would replace
+#[cfg(not(test))]
 pub use uefi_alloc;
 
-// Runtime support
+// Runtime support, which host tests get from std
+#[cfg(not(test))]
 pub mod rt;
 
 // Public modules
+pub mod block_io;
+pub mod console;
+pub mod crc32;
+pub mod device_path;
+pub mod disk_io;
+pub mod edid;
+pub mod event;
 pub mod exec;
+pub mod fat;
 pub mod ffi;
 pub mod fs;
+pub mod graphics;
+pub mod image;
+pub mod inflate;
+pub mod input;
 pub mod io;
+pub mod iso9660;
 pub mod loaded_image;
+pub mod partition;
+pub mod pass_thru;
+pub mod path;
 pub mod pointer;
 pub mod prelude;
 pub mod proto;
+pub mod ram_disk;
 pub mod shell;
+pub mod storage_security;
 pub mod vars;
 
 use uefi::prelude::*;
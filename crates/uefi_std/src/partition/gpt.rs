@@ -0,0 +1,239 @@
+use core::char;
+
+use super::{guid_from_bytes, read_block};
+use crate::bytes::{le32, le64};
+use crate::crc32::crc32;
+use crate::io::{Read, Seek, SeekFrom};
+use crate::prelude::*;
+
+pub const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// The size of the header fields defined by revision 1.0
+const HEADER_SIZE: usize = 92;
+/// The largest entry array that will be read, far above the usual 16 KiB
+const MAX_ENTRIES_SIZE: usize = 1024 * 1024;
+/// The number of UTF-16 units in an entry name
+const NAME_LEN: usize = 36;
+
+/// A GPT header, from either the primary or backup location
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GptHeader {
+    pub revision: u32,
+    pub header_size: u32,
+    pub current_lba: u64,
+    pub backup_lba: u64,
+    pub first_usable_lba: u64,
+    pub last_usable_lba: u64,
+    pub disk_guid: Guid,
+    pub entries_lba: u64,
+    pub num_entries: u32,
+    pub entry_size: u32,
+    pub entries_crc32: u32,
+}
+
+impl GptHeader {
+    /// Parse and verify the header in `block`, which was read from `lba`
+    fn parse(block: &[u8], lba: u64) -> Result<Self> {
+        if &block[..8] != GPT_SIGNATURE {
+            return Err(Status::NOT_FOUND);
+        }
+
+        let header_size = le32(block, 12);
+        if (header_size as usize) < HEADER_SIZE || header_size as usize > block.len() {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        // The CRC covers the header with its own CRC field zeroed
+        let mut header = block[..header_size as usize].to_vec();
+        header[16..20].fill(0);
+        if crc32(&header) != le32(block, 16) {
+            return Err(Status::CRC_ERROR);
+        }
+
+        let header = GptHeader {
+            revision: le32(block, 8),
+            header_size,
+            current_lba: le64(block, 24),
+            backup_lba: le64(block, 32),
+            first_usable_lba: le64(block, 40),
+            last_usable_lba: le64(block, 48),
+            disk_guid: guid_from_bytes(&block[56..72]),
+            entries_lba: le64(block, 72),
+            num_entries: le32(block, 80),
+            entry_size: le32(block, 84),
+            entries_crc32: le32(block, 88),
+        };
+
+        if header.current_lba != lba
+            || header.first_usable_lba > header.last_usable_lba
+            || header.entry_size < 128
+            || header.entry_size % 8 != 0
+            || header
+                .entries_size()
+                .map_or(true, |size| size > MAX_ENTRIES_SIZE)
+        {
+            return Err(Status::VOLUME_CORRUPTED);
+        }
+
+        Ok(header)
+    }
+
+    fn entries_size(&self) -> Option<usize> {
+        (self.num_entries as usize).checked_mul(self.entry_size as usize)
+    }
+}
+
+/// A used entry in the GPT partition entry array
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GptEntry {
+    /// The position of the entry in the array, from 0
+    pub index: u32,
+    pub type_guid: Guid,
+    pub unique_guid: Guid,
+    pub first_lba: u64,
+    /// The last block of the partition, inclusive
+    pub last_lba: u64,
+    pub attributes: u64,
+    pub name: String,
+}
+
+impl GptEntry {
+    /// The partition is required for the platform to function
+    pub const REQUIRED: u64 = 1 << 0;
+    /// Firmware must not produce a Block I/O protocol for the partition
+    pub const NO_BLOCK_IO: u64 = 1 << 1;
+    /// Legacy BIOS bootable
+    pub const LEGACY_BIOS_BOOTABLE: u64 = 1 << 2;
+
+    pub(super) fn parse(index: u32, data: &[u8]) -> Self {
+        let name = char::decode_utf16(
+            data[56..56 + NAME_LEN * 2]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+
+        GptEntry {
+            index,
+            type_guid: guid_from_bytes(&data[0..16]),
+            unique_guid: guid_from_bytes(&data[16..32]),
+            first_lba: le64(data, 32),
+            last_lba: le64(data, 40),
+            attributes: le64(data, 48),
+            name,
+        }
+    }
+
+    /// The number of blocks in the partition
+    pub fn blocks(&self) -> u64 {
+        (self.last_lba + 1).saturating_sub(self.first_lba)
+    }
+}
+
+/// A verified GUID partition table
+#[derive(Clone, Debug)]
+pub struct Gpt {
+    header: GptHeader,
+    entries: Vec<GptEntry>,
+    block_size: u64,
+    backup: bool,
+}
+
+impl Gpt {
+    /// Read the primary GPT, falling back to the backup if the primary header
+    /// or its entries are damaged
+    pub fn read<R: Read + Seek>(reader: &mut R, block_size: u64) -> Result<Self> {
+        if block_size < 512 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let primary = read_header(reader, 1, block_size);
+        let err = match primary.clone().and_then(|header| {
+            read_entries(reader, &header, block_size).map(|entries| (header, entries))
+        }) {
+            Ok((header, entries)) => {
+                return Ok(Gpt {
+                    header,
+                    entries,
+                    block_size,
+                    backup: false,
+                })
+            }
+            Err(err) => err,
+        };
+
+        // The backup header is normally in the last block
+        let backup_lba = match primary {
+            Ok(header) => header.backup_lba,
+            Err(_) => (reader.seek(SeekFrom::End(0))? / block_size)
+                .checked_sub(1)
+                .ok_or(err)?,
+        };
+        let header = read_header(reader, backup_lba, block_size).map_err(|_| err)?;
+        let entries = read_entries(reader, &header, block_size).map_err(|_| err)?;
+        Ok(Gpt {
+            header,
+            entries,
+            block_size,
+            backup: true,
+        })
+    }
+
+    pub fn header(&self) -> &GptHeader {
+        &self.header
+    }
+
+    /// The used entries, in the order of the entry array
+    pub fn entries(&self) -> &[GptEntry] {
+        &self.entries
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// Returns true if the primary table was damaged and the backup was used
+    pub fn used_backup(&self) -> bool {
+        self.backup
+    }
+
+    /// Find the entries with the partition type `guid`
+    pub fn find_type<'a>(&'a self, guid: &'a Guid) -> impl Iterator<Item = &'a GptEntry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.type_guid == *guid)
+    }
+
+    /// Find the entry with the unique partition `guid`
+    pub fn find_unique(&self, guid: &Guid) -> Option<&GptEntry> {
+        self.entries.iter().find(|entry| entry.unique_guid == *guid)
+    }
+}
+
+fn read_header<R: Read + Seek>(reader: &mut R, lba: u64, block_size: u64) -> Result<GptHeader> {
+    let mut block = vec![0; block_size as usize];
+    read_block(reader, lba, block_size, &mut block)?;
+    GptHeader::parse(&block, lba)
+}
+
+fn read_entries<R: Read + Seek>(
+    reader: &mut R,
+    header: &GptHeader,
+    block_size: u64,
+) -> Result<Vec<GptEntry>> {
+    let mut data = vec![0; header.entries_size().ok_or(Status::VOLUME_CORRUPTED)?];
+    read_block(reader, header.entries_lba, block_size, &mut data)?;
+    if crc32(&data) != header.entries_crc32 {
+        return Err(Status::CRC_ERROR);
+    }
+
+    let entries = data
+        .chunks_exact(header.entry_size as usize)
+        .enumerate()
+        .map(|(i, data)| GptEntry::parse(i as u32, data))
+        .filter(|entry| entry.type_guid != Guid::NIL)
+        .collect();
+    Ok(entries)
+}
@@ -0,0 +1,110 @@
+use core::{mem, slice};
+use uefi::partition::{
+    MbrPartitionRecord, PartitionEntry, PartitionInfo as UefiPartitionInfo, PARTITION_TYPE_GPT,
+    PARTITION_TYPE_MBR,
+};
+
+use super::{GptEntry, MbrPartition, EFI_SYSTEM_PARTITION_GUID, MBR_TYPE_EFI_SYSTEM};
+use crate::block_io::BlockDevice;
+use crate::device_path::DevicePath;
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+pub struct PartitionInfo(pub &'static mut UefiPartitionInfo);
+
+impl Protocol<UefiPartitionInfo> for PartitionInfo {
+    fn guid() -> Guid {
+        UefiPartitionInfo::GUID
+    }
+
+    fn new(inner: &'static mut UefiPartitionInfo) -> Self {
+        PartitionInfo(inner)
+    }
+}
+
+impl PartitionInfo {
+    /// Returns true if firmware flagged this as an EFI system partition
+    pub fn is_system(&self) -> bool {
+        self.0.System != 0
+    }
+
+    /// The GPT entry, if this is a GPT partition
+    pub fn gpt_entry(&self) -> Option<GptEntry> {
+        if self.0.Type != PARTITION_TYPE_GPT {
+            return None;
+        }
+
+        let entry = unsafe { self.0.Info.Gpt };
+        let data = unsafe {
+            slice::from_raw_parts(
+                &entry as *const PartitionEntry as *const u8,
+                mem::size_of::<PartitionEntry>(),
+            )
+        };
+        Some(GptEntry::parse(0, data))
+    }
+
+    /// The MBR record, if this is an MBR partition
+    pub fn mbr_partition(&self) -> Option<MbrPartition> {
+        if self.0.Type != PARTITION_TYPE_MBR {
+            return None;
+        }
+
+        let record = unsafe { self.0.Info.Mbr };
+        let data = unsafe {
+            slice::from_raw_parts(
+                &record as *const MbrPartitionRecord as *const u8,
+                mem::size_of::<MbrPartitionRecord>(),
+            )
+        };
+        MbrPartition::parse(0, data, 0)
+    }
+
+    /// Returns true for an EFI system partition, by flag or by type
+    pub fn is_efi_system(&self) -> bool {
+        self.is_system()
+            || self
+                .gpt_entry()
+                .map_or(false, |entry| entry.type_guid == EFI_SYSTEM_PARTITION_GUID)
+            || self
+                .mbr_partition()
+                .map_or(false, |partition| partition.kind == MBR_TYPE_EFI_SYSTEM)
+    }
+}
+
+/// Find the whole-disk Block I/O handle holding the partition on `handle`,
+/// whose device path is the partition's path up to its first media node
+pub fn parent_disk(handle: Handle) -> Result<Handle> {
+    let path = DevicePath::handle_protocol(handle)?;
+    let prefix = path.disk_prefix().ok_or(Status::NOT_FOUND)?;
+
+    for disk in BlockDevice::locate_handle()? {
+        if disk == handle {
+            continue;
+        }
+
+        match BlockDevice::handle_protocol(disk) {
+            Ok(block) if !block.media().logical_partition => (),
+            _ => continue,
+        }
+
+        if let Ok(disk_path) = DevicePath::handle_protocol(disk) {
+            if disk_path.as_bytes() == prefix {
+                return Ok(disk);
+            }
+        }
+    }
+
+    Err(Status::NOT_FOUND)
+}
+
+/// Find the handles of every EFI system partition
+pub fn system_partitions() -> Vec<Handle> {
+    PartitionInfo::locate_handle()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|&handle| {
+            PartitionInfo::handle_protocol(handle).map_or(false, |info| info.is_efi_system())
+        })
+        .collect()
+}
@@ -0,0 +1,142 @@
+use super::read_block;
+use crate::bytes::le32;
+use crate::io::{Read, Seek};
+use crate::prelude::*;
+
+/// The partition type of the single partition in a protective MBR
+pub const MBR_TYPE_PROTECTIVE: u8 = 0xEE;
+/// The partition type of an EFI system partition
+pub const MBR_TYPE_EFI_SYSTEM: u8 = 0xEF;
+
+const MBR_SIZE: usize = 512;
+const TABLE_OFFSET: usize = 446;
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+/// The most logical partitions followed, in case the chain loops
+const MAX_LOGICAL: usize = 128;
+
+/// A partition from the MBR or from an extended partition chain
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MbrPartition {
+    /// 1 to 4 for primary partitions, and from 5 for logical partitions
+    pub index: u32,
+    pub bootable: bool,
+    pub kind: u8,
+    /// The first block of the partition, from the start of the disk
+    pub first_lba: u64,
+    pub blocks: u64,
+}
+
+impl MbrPartition {
+    pub(super) fn parse(index: u32, data: &[u8], base_lba: u64) -> Option<Self> {
+        let kind = data[4];
+        let blocks = u64::from(le32(data, 12));
+        if kind == 0 || blocks == 0 {
+            return None;
+        }
+
+        Some(MbrPartition {
+            index,
+            bootable: data[0] & 0x80 != 0,
+            kind,
+            first_lba: base_lba + u64::from(le32(data, 8)),
+            blocks,
+        })
+    }
+
+    /// Returns true for an extended partition, which holds logical partitions
+    pub fn is_extended(&self) -> bool {
+        matches!(self.kind, 0x05 | 0x0F | 0x85)
+    }
+
+    pub fn is_logical(&self) -> bool {
+        self.index > 4
+    }
+}
+
+/// A master boot record, with logical partitions from any extended partition
+#[derive(Clone, Debug)]
+pub struct Mbr {
+    disk_signature: u32,
+    block_size: u64,
+    partitions: Vec<MbrPartition>,
+}
+
+impl Mbr {
+    /// Read the MBR in the first block, following any extended partition
+    pub fn read<R: Read + Seek>(reader: &mut R, block_size: u64) -> Result<Self> {
+        if block_size < 512 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let mut sector = [0; MBR_SIZE];
+        read_block(reader, 0, block_size, &mut sector)?;
+        if sector[510..] != BOOT_SIGNATURE {
+            return Err(Status::NOT_FOUND);
+        }
+
+        let mut partitions = Vec::new();
+        for (i, data) in sector[TABLE_OFFSET..510].chunks_exact(16).enumerate() {
+            partitions.extend(MbrPartition::parse(i as u32 + 1, data, 0));
+        }
+
+        let mut mbr = Mbr {
+            disk_signature: le32(&sector, 440),
+            block_size,
+            partitions,
+        };
+        if !mbr.is_protective() {
+            mbr.read_logical(reader, block_size)?;
+        }
+        Ok(mbr)
+    }
+
+    /// Follow the chain of extended boot records in the extended partition
+    fn read_logical<R: Read + Seek>(&mut self, reader: &mut R, block_size: u64) -> Result<()> {
+        let extended = match self.partitions.iter().find(|p| p.is_extended()) {
+            Some(extended) => extended.first_lba,
+            None => return Ok(()),
+        };
+
+        let mut ebr_lba = extended;
+        let mut sector = [0; MBR_SIZE];
+        for index in 5..5 + MAX_LOGICAL as u32 {
+            read_block(reader, ebr_lba, block_size, &mut sector)?;
+            if sector[510..] != BOOT_SIGNATURE {
+                return Err(Status::VOLUME_CORRUPTED);
+            }
+
+            // The first entry is relative to this EBR, the second to the
+            // start of the extended partition
+            let table = &sector[TABLE_OFFSET..];
+            self.partitions
+                .extend(MbrPartition::parse(index, &table[..16], ebr_lba));
+            match MbrPartition::parse(0, &table[16..32], extended) {
+                Some(next) if next.is_extended() && next.first_lba > ebr_lba => {
+                    ebr_lba = next.first_lba
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn disk_signature(&self) -> u32 {
+        self.disk_signature
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// The primary partitions, followed by any logical partitions
+    pub fn partitions(&self) -> &[MbrPartition] {
+        &self.partitions
+    }
+
+    /// Returns true if this MBR only protects a GPT
+    pub fn is_protective(&self) -> bool {
+        self.partitions
+            .iter()
+            .any(|p| !p.is_logical() && p.kind == MBR_TYPE_PROTECTIVE)
+    }
+}
@@ -0,0 +1,315 @@
+//! GPT and MBR partition tables, read from any [`Read`] + [`Seek`] source, and
+//! the partitions firmware has found
+
+use uefi::guid;
+
+use crate::bytes::le32;
+use crate::io::{Read, Seek, SeekFrom};
+use crate::prelude::*;
+
+pub use self::gpt::{Gpt, GptEntry, GptHeader, GPT_SIGNATURE};
+pub use self::info::{parent_disk, system_partitions, PartitionInfo};
+pub use self::mbr::{Mbr, MbrPartition, MBR_TYPE_EFI_SYSTEM, MBR_TYPE_PROTECTIVE};
+
+mod gpt;
+mod info;
+mod mbr;
+
+pub const EFI_SYSTEM_PARTITION_GUID: Guid = guid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b");
+pub const BASIC_DATA_PARTITION_GUID: Guid = guid!("ebd0a0a2-b9e5-4433-87c0-68b6b72699c7");
+pub const LINUX_FILESYSTEM_PARTITION_GUID: Guid = guid!("0fc63daf-8483-4772-8e79-3d69d8477de4");
+
+/// Decode a GUID in its on-disk mixed-endian form
+fn guid_from_bytes(data: &[u8]) -> Guid {
+    Guid(
+        le32(data, 0),
+        u16::from_le_bytes([data[4], data[5]]),
+        u16::from_le_bytes([data[6], data[7]]),
+        data[8..16].try_into().unwrap(),
+    )
+}
+
+/// Fill `buf` from the start of block `lba`
+fn read_block<R: Read + Seek>(
+    reader: &mut R,
+    lba: u64,
+    block_size: u64,
+    buf: &mut [u8],
+) -> Result<()> {
+    let offset = lba
+        .checked_mul(block_size)
+        .ok_or(Status::VOLUME_CORRUPTED)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(buf)?;
+    Ok(())
+}
+
+/// The partition table of a disk
+#[derive(Clone, Debug)]
+pub enum PartitionTable {
+    Gpt(Gpt),
+    Mbr(Mbr),
+}
+
+impl PartitionTable {
+    /// Read a GPT if the MBR is protective or missing, and the MBR otherwise
+    pub fn read<R: Read + Seek>(reader: &mut R, block_size: u64) -> Result<Self> {
+        match Mbr::read(reader, block_size) {
+            Ok(mbr) if !mbr.is_protective() => Ok(PartitionTable::Mbr(mbr)),
+            _ => Gpt::read(reader, block_size).map(PartitionTable::Gpt),
+        }
+    }
+
+    /// Returns every partition, with offsets in bytes
+    pub fn partitions(&self) -> Vec<Partition> {
+        match self {
+            PartitionTable::Gpt(gpt) => gpt
+                .entries()
+                .iter()
+                .map(|entry| Partition {
+                    index: entry.index + 1,
+                    offset: entry.first_lba * gpt.block_size(),
+                    len: entry.blocks() * gpt.block_size(),
+                    kind: PartitionKind::Gpt(entry.clone()),
+                })
+                .collect(),
+            PartitionTable::Mbr(mbr) => mbr
+                .partitions()
+                .iter()
+                .filter(|partition| !partition.is_extended())
+                .map(|partition| Partition {
+                    index: partition.index,
+                    offset: partition.first_lba * mbr.block_size(),
+                    len: partition.blocks * mbr.block_size(),
+                    kind: PartitionKind::Mbr(partition.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Find the first partition with the GPT type `guid`
+    pub fn find_by_type(&self, guid: &Guid) -> Option<Partition> {
+        self.partitions()
+            .into_iter()
+            .find(|partition| partition.type_guid() == Some(*guid))
+    }
+
+    /// Find the partition with the unique GPT `guid`
+    pub fn find_by_unique(&self, guid: &Guid) -> Option<Partition> {
+        self.partitions()
+            .into_iter()
+            .find(|partition| partition.unique_guid() == Some(*guid))
+    }
+}
+
+/// The table entry a partition came from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PartitionKind {
+    Gpt(GptEntry),
+    Mbr(MbrPartition),
+}
+
+/// A partition of either table type
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Partition {
+    /// The partition number, from 1
+    pub index: u32,
+    /// The start of the partition in bytes
+    pub offset: u64,
+    /// The length of the partition in bytes
+    pub len: u64,
+    pub kind: PartitionKind,
+}
+
+impl Partition {
+    pub fn type_guid(&self) -> Option<Guid> {
+        match &self.kind {
+            PartitionKind::Gpt(entry) => Some(entry.type_guid),
+            PartitionKind::Mbr(_) => None,
+        }
+    }
+
+    pub fn unique_guid(&self) -> Option<Guid> {
+        match &self.kind {
+            PartitionKind::Gpt(entry) => Some(entry.unique_guid),
+            PartitionKind::Mbr(_) => None,
+        }
+    }
+
+    /// The GPT partition name, which may be empty
+    pub fn name(&self) -> Option<&str> {
+        match &self.kind {
+            PartitionKind::Gpt(entry) => Some(&entry.name),
+            PartitionKind::Mbr(_) => None,
+        }
+    }
+
+    /// Returns true for an EFI system partition of either table type
+    pub fn is_efi_system(&self) -> bool {
+        match &self.kind {
+            PartitionKind::Gpt(entry) => entry.type_guid == EFI_SYSTEM_PARTITION_GUID,
+            PartitionKind::Mbr(partition) => partition.kind == MBR_TYPE_EFI_SYSTEM,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inflate::zlib_decompress;
+    use crate::io::Cursor;
+
+    const ROOT_GUID: Guid = guid!("aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee");
+
+    fn image(data: &[u8]) -> Vec<u8> {
+        zlib_decompress(data).unwrap()
+    }
+
+    fn gpt_image() -> Vec<u8> {
+        image(include_bytes!("../../testdata/partition/gpt.img.z"))
+    }
+
+    fn read_gpt(image: &[u8]) -> Result<Gpt> {
+        Gpt::read(&mut Cursor::new(image), 512)
+    }
+
+    #[test]
+    fn gpt_entries() {
+        let image = gpt_image();
+        let table = PartitionTable::read(&mut Cursor::new(&image), 512).unwrap();
+        let gpt = match &table {
+            PartitionTable::Gpt(gpt) => gpt,
+            PartitionTable::Mbr(_) => panic!("protective MBR not detected"),
+        };
+        assert!(!gpt.used_backup());
+        assert_eq!(gpt.header().current_lba, 1);
+        assert_eq!(gpt.header().backup_lba, 8191);
+        assert_eq!(
+            gpt.header().disk_guid,
+            guid!("6913f4b6-6690-4a57-a202-f1b53c56dbdf")
+        );
+
+        let entries = gpt.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].type_guid, EFI_SYSTEM_PARTITION_GUID);
+        assert_eq!(
+            entries[0].unique_guid,
+            guid!("11111111-2222-3333-4444-555555555555")
+        );
+        assert_eq!((entries[0].first_lba, entries[0].last_lba), (40, 2087));
+        assert_eq!(entries[0].name, "EFI System");
+        assert_eq!(entries[1].type_guid, LINUX_FILESYSTEM_PARTITION_GUID);
+        assert_eq!(entries[1].blocks(), 4096);
+        assert_eq!(entries[1].name, "Redox r\u{E4}ksm\u{F6}rg\u{E5}s");
+
+        let esp = table.find_by_type(&EFI_SYSTEM_PARTITION_GUID).unwrap();
+        assert!(esp.is_efi_system());
+        assert_eq!((esp.index, esp.offset, esp.len), (1, 40 * 512, 2048 * 512));
+        let root = table.find_by_unique(&ROOT_GUID).unwrap();
+        assert_eq!((root.index, root.offset), (2, 2088 * 512));
+        assert_eq!(table.find_by_type(&BASIC_DATA_PARTITION_GUID), None);
+    }
+
+    #[test]
+    fn gpt_bad_header_crc_uses_backup() {
+        let mut image = gpt_image();
+        // A byte of the disk GUID in the primary header
+        image[512 + 60] ^= 1;
+        let gpt = read_gpt(&image).unwrap();
+        assert!(gpt.used_backup());
+        assert_eq!(gpt.header().current_lba, 8191);
+        assert_eq!(gpt.header().entries_lba, 8159);
+        assert_eq!(gpt.find_unique(&ROOT_GUID).unwrap().first_lba, 2088);
+    }
+
+    #[test]
+    fn gpt_bad_entries_crc_uses_backup() {
+        let mut image = gpt_image();
+        // The first character of the name of the first primary entry
+        image[2 * 512 + 56] = b'X';
+        let gpt = read_gpt(&image).unwrap();
+        assert!(gpt.used_backup());
+        assert_eq!(gpt.entries()[0].name, "EFI System");
+    }
+
+    #[test]
+    fn gpt_missing_header_uses_last_block() {
+        let mut image = gpt_image();
+        image[512..1024].fill(0);
+        let gpt = read_gpt(&image).unwrap();
+        assert!(gpt.used_backup());
+        assert_eq!(gpt.entries().len(), 2);
+    }
+
+    #[test]
+    fn gpt_both_damaged() {
+        let mut image = gpt_image();
+        image[512 + 60] ^= 1;
+        let backup = image.len() - 512;
+        image[backup + 60] ^= 1;
+        assert_eq!(read_gpt(&image).err(), Some(Status::CRC_ERROR));
+
+        // A header pointing at a damaged entry array fails with the CRC of the array
+        let mut image = gpt_image();
+        image[2 * 512 + 56] = b'X';
+        image[8159 * 512 + 56] = b'X';
+        assert_eq!(read_gpt(&image).err(), Some(Status::CRC_ERROR));
+    }
+
+    #[test]
+    fn bad_block_size() {
+        let image = gpt_image();
+        for block_size in [0, 7, 15, 511] {
+            let mut reader = Cursor::new(&image);
+            assert_eq!(
+                Gpt::read(&mut reader, block_size).err(),
+                Some(Status::INVALID_PARAMETER)
+            );
+            assert_eq!(
+                Mbr::read(&mut reader, block_size).err(),
+                Some(Status::INVALID_PARAMETER)
+            );
+            assert_eq!(
+                PartitionTable::read(&mut reader, block_size).err(),
+                Some(Status::INVALID_PARAMETER)
+            );
+        }
+    }
+
+    #[test]
+    fn mbr_logical_partitions() {
+        let image = image(include_bytes!("../../testdata/partition/mbr.img.z"));
+        let mbr = Mbr::read(&mut Cursor::new(&image), 512).unwrap();
+        assert!(!mbr.is_protective());
+        assert_eq!(mbr.disk_signature(), 0x505909D0);
+
+        let summary = mbr
+            .partitions()
+            .iter()
+            .map(|p| (p.index, p.kind, p.first_lba, p.blocks, p.bootable))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (1, MBR_TYPE_EFI_SYSTEM, 2048, 2048, true),
+                (2, 0x83, 4096, 2048, false),
+                (4, 0x05, 6144, 8192, false),
+                (5, 0x0C, 8192, 2048, false),
+                (6, 0x83, 12288, 2048, false),
+            ]
+        );
+
+        // Extended partitions are only containers
+        let table = PartitionTable::read(&mut Cursor::new(&image), 512).unwrap();
+        let partitions = table.partitions();
+        let indices = partitions.iter().map(|p| p.index).collect::<Vec<_>>();
+        assert_eq!(indices, [1, 2, 5, 6]);
+        assert!(partitions[0].is_efi_system());
+        assert_eq!(partitions[3].offset, 12288 * 512);
+        assert_eq!(partitions[3].type_guid(), None);
+
+        // The protective MBR of a GPT disk
+        let mbr = Mbr::read(&mut Cursor::new(gpt_image()), 512).unwrap();
+        assert!(mbr.is_protective());
+    }
+}
@@ -0,0 +1,480 @@
+use core::ptr;
+use uefi::ata::{
+    AtaCommandBlock, AtaPassThru as UefiAtaPassThru, AtaPassThruCommandPacket, AtaStatusBlock,
+    ATA_ALL_PORTS, ATA_PASS_THRU_LENGTH_BYTES, ATA_PASS_THRU_LENGTH_NO_DATA_TRANSFER,
+    ATA_PASS_THRU_LENGTH_SECTOR_COUNT, ATA_PASS_THRU_PROTOCOL_ATA_NON_DATA,
+    ATA_PASS_THRU_PROTOCOL_DMA, ATA_PASS_THRU_PROTOCOL_PIO_DATA_IN,
+    ATA_PASS_THRU_PROTOCOL_PIO_DATA_OUT, ATA_PASS_THRU_PROTOCOL_UDMA_DATA_IN,
+    ATA_PASS_THRU_PROTOCOL_UDMA_DATA_OUT,
+};
+
+use super::{read_value, stage_value, Transfer, DEFAULT_TIMEOUT};
+use crate::device_path::DevicePath;
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+/// The size of IDENTIFY DEVICE data, and of security command payloads
+pub const ATA_IDENTIFY_SIZE: usize = 512;
+
+const CMD_SMART: u8 = 0xB0;
+const CMD_IDENTIFY_DEVICE: u8 = 0xEC;
+const CMD_SECURITY_SET_PASSWORD: u8 = 0xF1;
+const CMD_SECURITY_ERASE_PREPARE: u8 = 0xF3;
+const CMD_SECURITY_ERASE_UNIT: u8 = 0xF4;
+
+const SMART_RETURN_STATUS: u16 = 0xDA;
+/// The LBA Mid and LBA High signature of SMART commands
+const SMART_LBA: u64 = 0xC2_4F00;
+/// The LBA Mid and LBA High reported when a threshold is exceeded
+const SMART_LBA_FAILING: u64 = 0x2C_F400;
+
+/// How a command moves its data
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AtaProtocol {
+    NonData,
+    PioDataIn,
+    PioDataOut,
+    Dma,
+    UdmaDataIn,
+    UdmaDataOut,
+}
+
+impl AtaProtocol {
+    fn raw(&self) -> u8 {
+        match self {
+            AtaProtocol::NonData => ATA_PASS_THRU_PROTOCOL_ATA_NON_DATA,
+            AtaProtocol::PioDataIn => ATA_PASS_THRU_PROTOCOL_PIO_DATA_IN,
+            AtaProtocol::PioDataOut => ATA_PASS_THRU_PROTOCOL_PIO_DATA_OUT,
+            AtaProtocol::Dma => ATA_PASS_THRU_PROTOCOL_DMA,
+            AtaProtocol::UdmaDataIn => ATA_PASS_THRU_PROTOCOL_UDMA_DATA_IN,
+            AtaProtocol::UdmaDataOut => ATA_PASS_THRU_PROTOCOL_UDMA_DATA_OUT,
+        }
+    }
+}
+
+/// An ATA command without its data
+#[derive(Clone, Copy, Debug)]
+pub struct AtaCommand {
+    pub command: u8,
+    pub features: u16,
+    pub count: u16,
+    /// The 48-bit LBA, which some commands use for parameters
+    pub lba: u64,
+    pub device: u8,
+    pub protocol: AtaProtocol,
+    /// The timeout in 100ns units, or 0 to wait forever
+    pub timeout: u64,
+}
+
+impl AtaCommand {
+    pub fn new(command: u8, protocol: AtaProtocol) -> Self {
+        AtaCommand {
+            command,
+            features: 0,
+            count: 0,
+            lba: 0,
+            device: 0,
+            protocol,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// IDENTIFY DEVICE, returning [`ATA_IDENTIFY_SIZE`] bytes
+    pub fn identify() -> Self {
+        Self::new(CMD_IDENTIFY_DEVICE, AtaProtocol::PioDataIn)
+    }
+
+    /// SMART RETURN STATUS
+    pub fn smart_return_status() -> Self {
+        let mut command = Self::new(CMD_SMART, AtaProtocol::NonData);
+        command.features = SMART_RETURN_STATUS;
+        command.lba = SMART_LBA;
+        command
+    }
+
+    /// SECURITY SET PASSWORD, with a payload from [`security_payload`]
+    pub fn security_set_password() -> Self {
+        Self::new(CMD_SECURITY_SET_PASSWORD, AtaProtocol::PioDataOut)
+    }
+
+    /// SECURITY ERASE PREPARE, which must come right before
+    /// [`security_erase_unit`](Self::security_erase_unit)
+    pub fn security_erase_prepare() -> Self {
+        Self::new(CMD_SECURITY_ERASE_PREPARE, AtaProtocol::NonData)
+    }
+
+    /// SECURITY ERASE UNIT, with a payload from [`security_payload`]
+    pub fn security_erase_unit() -> Self {
+        let mut command = Self::new(CMD_SECURITY_ERASE_UNIT, AtaProtocol::PioDataOut);
+        // Erasing takes minutes to hours
+        command.timeout = 0;
+        command
+    }
+
+    fn raw(&self) -> AtaCommandBlock {
+        let lba = self.lba.to_le_bytes();
+        AtaCommandBlock {
+            AtaCommand: self.command,
+            AtaFeatures: self.features as u8,
+            AtaFeaturesExp: (self.features >> 8) as u8,
+            AtaSectorCount: self.count as u8,
+            AtaSectorCountExp: (self.count >> 8) as u8,
+            AtaSectorNumber: lba[0],
+            AtaCylinderLow: lba[1],
+            AtaCylinderHigh: lba[2],
+            AtaSectorNumberExp: lba[3],
+            AtaCylinderLowExp: lba[4],
+            AtaCylinderHighExp: lba[5],
+            AtaDeviceHead: self.device,
+            ..Default::default()
+        }
+    }
+}
+
+/// The payload of SECURITY SET PASSWORD and SECURITY ERASE UNIT. `master`
+/// selects the master password, and `enhanced` requests an enhanced erase.
+pub fn security_payload(password: &[u8], master: bool, enhanced: bool) -> [u8; ATA_IDENTIFY_SIZE] {
+    let mut payload = [0; ATA_IDENTIFY_SIZE];
+    payload[0] = u8::from(master) | u8::from(enhanced) << 1;
+    let len = password.len().min(32);
+    payload[2..2 + len].copy_from_slice(&password[..len]);
+    payload
+}
+
+/// The registers returned by a command
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AtaStatus {
+    pub status: u8,
+    pub error: u8,
+    pub count: u16,
+    pub lba: u64,
+    pub device: u8,
+}
+
+impl AtaStatus {
+    fn from_raw(raw: &AtaStatusBlock) -> Self {
+        AtaStatus {
+            status: raw.AtaStatus,
+            error: raw.AtaError,
+            count: u16::from_le_bytes([raw.AtaSectorCount, raw.AtaSectorCountExp]),
+            lba: u64::from_le_bytes([
+                raw.AtaSectorNumber,
+                raw.AtaCylinderLow,
+                raw.AtaCylinderHigh,
+                raw.AtaSectorNumberExp,
+                raw.AtaCylinderLowExp,
+                raw.AtaCylinderHighExp,
+                0,
+                0,
+            ]),
+            device: raw.AtaDeviceHead,
+        }
+    }
+}
+
+/// The Security word of IDENTIFY DEVICE data
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AtaSecurity(pub u16);
+
+impl AtaSecurity {
+    pub fn supported(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    pub fn locked(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    /// Frozen devices reject security commands until they are power cycled
+    pub fn frozen(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    pub fn count_expired(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn enhanced_erase_supported(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+}
+
+/// IDENTIFY DEVICE data
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AtaIdentify {
+    pub serial: String,
+    pub firmware: String,
+    pub model: String,
+    /// The number of logical sectors
+    pub sectors: u64,
+    pub lba48: bool,
+    pub logical_sector_size: u32,
+    pub physical_sector_size: u32,
+    pub smart_supported: bool,
+    pub smart_enabled: bool,
+    pub security: AtaSecurity,
+    /// The estimated time of a normal security erase
+    pub erase_minutes: Option<u32>,
+    /// The estimated time of an enhanced security erase
+    pub enhanced_erase_minutes: Option<u32>,
+    pub trim: bool,
+    /// The rotation rate in RPM, 1 for solid state media, or 0 if unreported
+    pub rotation_rate: u16,
+}
+
+impl AtaIdentify {
+    /// Parse IDENTIFY DEVICE data, verifying its checksum if present
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < ATA_IDENTIFY_SIZE {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let word = |index: usize| u16::from_le_bytes([data[index * 2], data[index * 2 + 1]]);
+        let dword = |index: usize| u32::from(word(index)) | u32::from(word(index + 1)) << 16;
+
+        // The integrity word has a signature of 0xA5 and makes the sum zero
+        if data[510] == 0xA5
+            && data[..ATA_IDENTIFY_SIZE]
+                .iter()
+                .fold(0u8, |a, &b| a.wrapping_add(b))
+                != 0
+        {
+            return Err(Status::CRC_ERROR);
+        }
+
+        let lba48 = word(83) & 0x0400 != 0;
+        let sectors = if lba48 {
+            u64::from(dword(100)) | u64::from(dword(102)) << 32
+        } else {
+            u64::from(dword(60))
+        };
+
+        // Word 106 is valid when bit 14 is set and bit 15 is clear
+        let sector_info = word(106);
+        let (logical_sector_size, physical_sector_size) = if sector_info & 0xC000 == 0x4000 {
+            let logical = if sector_info & 0x1000 != 0 {
+                dword(117) * 2
+            } else {
+                512
+            };
+            let physical = if sector_info & 0x2000 != 0 {
+                logical << (sector_info & 0xF)
+            } else {
+                logical
+            };
+            (logical, physical)
+        } else {
+            (512, 512)
+        };
+
+        let erase_time = |word: u16| {
+            let minutes = if word & 0x8000 != 0 {
+                word & 0x7FFF
+            } else {
+                word & 0xFF
+            };
+            match minutes {
+                0 => None,
+                minutes => Some(u32::from(minutes) * 2),
+            }
+        };
+
+        Ok(AtaIdentify {
+            serial: ata_string(&data[20..40]),
+            firmware: ata_string(&data[46..54]),
+            model: ata_string(&data[54..94]),
+            sectors,
+            lba48,
+            logical_sector_size,
+            physical_sector_size,
+            smart_supported: word(82) & 0x0001 != 0,
+            smart_enabled: word(85) & 0x0001 != 0,
+            security: AtaSecurity(word(128)),
+            erase_minutes: erase_time(word(89)),
+            enhanced_erase_minutes: erase_time(word(90)),
+            trim: word(169) & 0x0001 != 0,
+            rotation_rate: word(217),
+        })
+    }
+
+    /// The capacity in bytes
+    pub fn size(&self) -> u64 {
+        self.sectors * u64::from(self.logical_sector_size)
+    }
+}
+
+/// Decode an ATA string, which has the bytes of each word swapped
+fn ata_string(data: &[u8]) -> String {
+    let text: String = data
+        .chunks_exact(2)
+        .flat_map(|pair| [pair[1], pair[0]])
+        .map(char::from)
+        .collect();
+    text.trim().to_string()
+}
+
+/// A device on a port, with a port multiplier port of 0xFFFF if it is
+/// attached directly
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AtaDevice {
+    pub port: u16,
+    pub multiplier: u16,
+}
+
+pub struct AtaPassThru(pub &'static mut UefiAtaPassThru);
+
+impl Protocol<UefiAtaPassThru> for AtaPassThru {
+    fn guid() -> Guid {
+        UefiAtaPassThru::GUID
+    }
+
+    fn new(inner: &'static mut UefiAtaPassThru) -> Self {
+        AtaPassThru(inner)
+    }
+}
+
+impl AtaPassThru {
+    pub fn io_align(&self) -> usize {
+        self.0.Mode.IoAlign as usize
+    }
+
+    /// Every device on every port
+    pub fn devices(&self) -> Result<Vec<AtaDevice>> {
+        let mut devices = Vec::new();
+        let mut port = ATA_ALL_PORTS;
+        loop {
+            match (self.0.GetNextPort)(self.0, &mut port) {
+                Status::SUCCESS => (),
+                Status::NOT_FOUND => return Ok(devices),
+                status => return Err(status),
+            }
+
+            let mut multiplier = ATA_ALL_PORTS;
+            loop {
+                match (self.0.GetNextDevice)(self.0, port, &mut multiplier) {
+                    Status::SUCCESS => devices.push(AtaDevice { port, multiplier }),
+                    Status::NOT_FOUND => break,
+                    status => return Err(status),
+                }
+            }
+        }
+    }
+
+    pub fn device_path(&self, device: AtaDevice) -> Result<DevicePath> {
+        let mut path = ptr::null_mut();
+        match (self.0.BuildDevicePath)(self.0, device.port, device.multiplier, &mut path) {
+            Status::SUCCESS => Ok(DevicePath(unsafe { &mut *path })),
+            status => Err(status),
+        }
+    }
+
+    pub fn reset(&mut self, device: AtaDevice) -> Result<()> {
+        (self.0.ResetDevice)(self.0, device.port, device.multiplier).into()
+    }
+
+    /// Run `command` and wait for it to complete
+    pub fn execute(
+        &mut self,
+        device: AtaDevice,
+        command: &AtaCommand,
+        mut transfer: Transfer,
+    ) -> Result<AtaStatus> {
+        let len = u32::try_from(transfer.len()).map_err(|_| Status::BAD_BUFFER_SIZE)?;
+        let mut buffer = transfer.stage(self.io_align())?;
+        let mut acb = stage_value(command.raw(), self.io_align())?;
+        let mut asb = stage_value(AtaStatusBlock::default(), self.io_align())?;
+
+        let (in_len, out_len) = match transfer {
+            Transfer::None => (0, 0),
+            Transfer::In(_) => (len, 0),
+            Transfer::Out(_) => (0, len),
+        };
+        let mut packet = AtaPassThruCommandPacket {
+            Asb: asb.as_mut_ptr() as *mut AtaStatusBlock,
+            Acb: acb.as_mut_ptr() as *mut AtaCommandBlock,
+            Timeout: command.timeout,
+            InDataBuffer: if in_len == 0 {
+                ptr::null_mut()
+            } else {
+                buffer.as_mut_ptr()
+            },
+            OutDataBuffer: if out_len == 0 {
+                ptr::null()
+            } else {
+                buffer.as_ptr()
+            },
+            InTransferLength: in_len,
+            OutTransferLength: out_len,
+            Protocol: command.protocol.raw(),
+            Length: if len == 0 {
+                ATA_PASS_THRU_LENGTH_NO_DATA_TRANSFER
+            } else {
+                ATA_PASS_THRU_LENGTH_BYTES | ATA_PASS_THRU_LENGTH_SECTOR_COUNT
+            },
+        };
+
+        let status = (self.0.PassThru)(
+            self.0,
+            device.port,
+            device.multiplier,
+            &mut packet,
+            Event(0),
+        );
+        match status {
+            Status::SUCCESS => {
+                transfer.finish(&buffer, packet.InTransferLength as usize);
+                Ok(AtaStatus::from_raw(&read_value(&asb)))
+            }
+            _ => Err(status),
+        }
+    }
+
+    pub fn identify(&mut self, device: AtaDevice) -> Result<AtaIdentify> {
+        let mut data = [0; ATA_IDENTIFY_SIZE];
+        self.execute(device, &AtaCommand::identify(), Transfer::In(&mut data))?;
+        AtaIdentify::parse(&data)
+    }
+
+    /// Returns false if SMART reports that a threshold was exceeded
+    pub fn smart_healthy(&mut self, device: AtaDevice) -> Result<bool> {
+        let status = self.execute(device, &AtaCommand::smart_return_status(), Transfer::None)?;
+        Ok(status.lba & 0xFF_FF00 != SMART_LBA_FAILING)
+    }
+
+    /// Set the user password, which enables security and is needed to erase
+    pub fn security_set_password(&mut self, device: AtaDevice, password: &[u8]) -> Result<()> {
+        let payload = security_payload(password, false, false);
+        self.execute(
+            device,
+            &AtaCommand::security_set_password(),
+            Transfer::Out(&payload),
+        )?;
+        Ok(())
+    }
+
+    /// Erase the device with the user `password`, waiting up to `timeout` in
+    /// 100ns units, or forever for 0
+    pub fn security_erase(
+        &mut self,
+        device: AtaDevice,
+        password: &[u8],
+        enhanced: bool,
+        timeout: u64,
+    ) -> Result<()> {
+        let payload = security_payload(password, false, enhanced);
+        self.execute(
+            device,
+            &AtaCommand::security_erase_prepare(),
+            Transfer::None,
+        )?;
+
+        let mut command = AtaCommand::security_erase_unit();
+        command.timeout = timeout;
+        self.execute(device, &command, Transfer::Out(&payload))?;
+        Ok(())
+    }
+}
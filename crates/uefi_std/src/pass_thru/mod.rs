@@ -0,0 +1,88 @@
+//! Raw NVMe, ATA and SCSI commands through the pass-through protocols
+//!
+//! Each wrapper copies data through buffers that meet the `IoAlign`
+//! requirement of the controller, so callers can use any slice.
+
+use core::{mem, ptr};
+
+use crate::block_io::AlignedBuffer;
+use crate::prelude::*;
+
+pub use self::ata::{
+    security_payload, AtaCommand, AtaDevice, AtaIdentify, AtaPassThru, AtaProtocol, AtaSecurity,
+    AtaStatus, ATA_IDENTIFY_SIZE,
+};
+pub use self::nvme::{
+    NvmeCommand, NvmeCompletion, NvmeController, NvmeLbaFormat, NvmeNamespace, NvmePassThru,
+    NvmeQueue, NvmeSanitize, NvmeSecureErase, NVME_IDENTIFY_SIZE,
+};
+pub use self::scsi::{ScsiCapacity, ScsiInquiry, ScsiPassThru, ScsiResponse, ScsiTarget};
+
+mod ata;
+mod nvme;
+mod scsi;
+
+/// The timeout of commands that have none given, in 100 ns units: 30 seconds
+pub(crate) const DEFAULT_TIMEOUT: u64 = 300_000_000;
+
+/// The data moved by a command
+pub enum Transfer<'a> {
+    None,
+    /// Data read from the device
+    In(&'a mut [u8]),
+    /// Data written to the device
+    Out(&'a [u8]),
+}
+
+impl<'a> Transfer<'a> {
+    pub fn len(&self) -> usize {
+        match self {
+            Transfer::None => 0,
+            Transfer::In(data) => data.len(),
+            Transfer::Out(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy the data to a buffer aligned to `align`
+    fn stage(&self, align: usize) -> Result<AlignedBuffer> {
+        let mut buffer = AlignedBuffer::new(self.len(), align)?;
+        if let Transfer::Out(data) = self {
+            buffer.copy_from_slice(data);
+        }
+        Ok(buffer)
+    }
+
+    /// Copy the first `count` bytes read by a command back from `buffer`
+    fn finish(&mut self, buffer: &AlignedBuffer, count: usize) {
+        if let Transfer::In(data) = self {
+            let count = count.min(data.len());
+            data[..count].copy_from_slice(&buffer[..count]);
+        }
+    }
+}
+
+/// Copy `value` to a buffer aligned to `align` and its own alignment
+fn stage_value<T: Copy>(value: T, align: usize) -> Result<AlignedBuffer> {
+    let mut buffer = AlignedBuffer::new(mem::size_of::<T>(), align.max(mem::align_of::<T>()))?;
+    unsafe { ptr::write(buffer.as_mut_ptr() as *mut T, value) };
+    Ok(buffer)
+}
+
+/// Read back a value staged with [`stage_value`]
+fn read_value<T: Copy>(buffer: &AlignedBuffer) -> T {
+    unsafe { ptr::read(buffer.as_ptr() as *const T) }
+}
+
+/// Decode a space padded ASCII field
+fn ascii_field(data: &[u8]) -> String {
+    let text: String = data
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| char::from(b))
+        .collect();
+    text.trim().to_string()
+}
@@ -0,0 +1,404 @@
+use core::ptr;
+use uefi::nvme::{
+    NvmExpressPassThru as UefiNvmePassThru, NvmExpressPassThruCommandPacket,
+    NvmeCommand as UefiNvmeCommand, NvmeCompletion as UefiNvmeCompletion, CDW10_VALID, CDW11_VALID,
+    CDW12_VALID, CDW13_VALID, CDW14_VALID, CDW15_VALID, NVME_ADMIN_QUEUE, NVME_ALL_NAMESPACES,
+    NVME_IO_QUEUE,
+};
+
+use super::{ascii_field, read_value, stage_value, Transfer, DEFAULT_TIMEOUT};
+use crate::bytes::{le16, le32, le64};
+use crate::device_path::DevicePath;
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+/// The size of every Identify data structure
+pub const NVME_IDENTIFY_SIZE: usize = 4096;
+
+const ADMIN_GET_LOG_PAGE: u8 = 0x02;
+const ADMIN_IDENTIFY: u8 = 0x06;
+const ADMIN_FORMAT_NVM: u8 = 0x80;
+const ADMIN_SANITIZE: u8 = 0x84;
+
+const CNS_NAMESPACE: u32 = 0x00;
+const CNS_CONTROLLER: u32 = 0x01;
+
+fn u128_at(data: &[u8], offset: usize) -> u128 {
+    u128::from_le_bytes(data[offset..offset + 16].try_into().unwrap())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NvmeQueue {
+    Admin,
+    Io,
+}
+
+/// The Secure Erase Settings of Format NVM
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NvmeSecureErase {
+    None = 0,
+    UserData = 1,
+    Cryptographic = 2,
+}
+
+/// The actions of Sanitize
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NvmeSanitize {
+    ExitFailureMode = 1,
+    BlockErase = 2,
+    Overwrite = 3,
+    CryptoErase = 4,
+}
+
+/// An NVMe command without its data
+#[derive(Clone, Copy, Debug)]
+pub struct NvmeCommand {
+    pub opcode: u8,
+    pub queue: NvmeQueue,
+    pub namespace: u32,
+    /// Command dwords 10 to 15
+    pub cdw: [u32; 6],
+    /// The timeout in 100ns units, or 0 to wait forever
+    pub timeout: u64,
+}
+
+impl NvmeCommand {
+    pub fn new(opcode: u8, queue: NvmeQueue, namespace: u32) -> Self {
+        NvmeCommand {
+            opcode,
+            queue,
+            namespace,
+            cdw: [0; 6],
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Identify the controller, returning [`NVME_IDENTIFY_SIZE`] bytes
+    pub fn identify_controller() -> Self {
+        let mut command = Self::new(ADMIN_IDENTIFY, NvmeQueue::Admin, 0);
+        command.cdw[0] = CNS_CONTROLLER;
+        command
+    }
+
+    /// Identify a namespace, returning [`NVME_IDENTIFY_SIZE`] bytes
+    pub fn identify_namespace(namespace: u32) -> Self {
+        let mut command = Self::new(ADMIN_IDENTIFY, NvmeQueue::Admin, namespace);
+        command.cdw[0] = CNS_NAMESPACE;
+        command
+    }
+
+    /// Read `len` bytes of the log page `log_id`, where `len` is a multiple of
+    /// four. The SMART / Health Information log is 0x02.
+    pub fn get_log_page(log_id: u8, namespace: u32, len: usize) -> Self {
+        let dwords = (len / 4).saturating_sub(1) as u32;
+        let mut command = Self::new(ADMIN_GET_LOG_PAGE, NvmeQueue::Admin, namespace);
+        command.cdw[0] = u32::from(log_id) | (dwords & 0xFFFF) << 16;
+        command.cdw[1] = dwords >> 16;
+        command
+    }
+
+    /// Format a namespace with the LBA format `format`, or every namespace
+    /// with a `namespace` of 0xFFFFFFFF
+    pub fn format(namespace: u32, format: u8, erase: NvmeSecureErase) -> Self {
+        let format = u32::from(format);
+        let mut command = Self::new(ADMIN_FORMAT_NVM, NvmeQueue::Admin, namespace);
+        command.cdw[0] = (format & 0xF) | (erase as u32) << 9 | (format >> 4 & 0x3) << 12;
+        // Formatting may take far longer than other admin commands
+        command.timeout = 0;
+        command
+    }
+
+    /// Start a sanitize operation, which continues in the background
+    pub fn sanitize(action: NvmeSanitize) -> Self {
+        let mut command = Self::new(ADMIN_SANITIZE, NvmeQueue::Admin, 0);
+        command.cdw[0] = action as u32;
+        command
+    }
+
+    fn raw(&self) -> UefiNvmeCommand {
+        UefiNvmeCommand {
+            Cdw0: u32::from(self.opcode),
+            Flags: CDW10_VALID
+                | CDW11_VALID
+                | CDW12_VALID
+                | CDW13_VALID
+                | CDW14_VALID
+                | CDW15_VALID,
+            Nsid: self.namespace,
+            Cdw10: self.cdw[0],
+            Cdw11: self.cdw[1],
+            Cdw12: self.cdw[2],
+            Cdw13: self.cdw[3],
+            Cdw14: self.cdw[4],
+            Cdw15: self.cdw[5],
+            ..Default::default()
+        }
+    }
+}
+
+/// The completion queue entry of a command
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NvmeCompletion {
+    pub dw0: u32,
+    pub dw1: u32,
+    pub dw2: u32,
+    pub dw3: u32,
+}
+
+impl NvmeCompletion {
+    /// The status field, with the status code in bits 0-7 and the status code
+    /// type in bits 8-10
+    pub fn status(&self) -> u16 {
+        (self.dw3 >> 17) as u16 & 0x7FFF
+    }
+}
+
+/// Identify Controller data
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NvmeController {
+    pub vendor_id: u16,
+    pub subsystem_vendor_id: u16,
+    pub serial: String,
+    pub model: String,
+    pub firmware: String,
+    pub controller_id: u16,
+    /// The NVMe version, as major in bits 16-31 and minor in bits 8-15
+    pub version: u32,
+    /// Optional Admin Command Support
+    pub admin_commands: u16,
+    /// Sanitize Capabilities
+    pub sanitize: u32,
+    /// The total NVM capacity in bytes
+    pub total_capacity: u128,
+    /// The unallocated NVM capacity in bytes
+    pub unallocated_capacity: u128,
+    /// The number of namespaces
+    pub namespaces: u32,
+    /// Optional NVM Command Support
+    pub nvm_commands: u16,
+    /// Format NVM Attributes
+    pub format_attributes: u8,
+    pub volatile_write_cache: bool,
+    pub subsystem_nqn: String,
+}
+
+impl NvmeController {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < NVME_IDENTIFY_SIZE {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        Ok(NvmeController {
+            vendor_id: le16(data, 0),
+            subsystem_vendor_id: le16(data, 2),
+            serial: ascii_field(&data[4..24]),
+            model: ascii_field(&data[24..64]),
+            firmware: ascii_field(&data[64..72]),
+            controller_id: le16(data, 78),
+            version: le32(data, 80),
+            admin_commands: le16(data, 256),
+            sanitize: le32(data, 328),
+            total_capacity: u128_at(data, 280),
+            unallocated_capacity: u128_at(data, 296),
+            namespaces: le32(data, 516),
+            nvm_commands: le16(data, 520),
+            format_attributes: data[524],
+            volatile_write_cache: data[525] & 1 != 0,
+            subsystem_nqn: ascii_field(&data[768..1024]),
+        })
+    }
+
+    pub fn supports_security(&self) -> bool {
+        self.admin_commands & 0x01 != 0
+    }
+
+    pub fn supports_format(&self) -> bool {
+        self.admin_commands & 0x02 != 0
+    }
+
+    /// Returns true if Format NVM supports cryptographic erase
+    pub fn supports_crypto_format(&self) -> bool {
+        self.format_attributes & 0x04 != 0
+    }
+
+    pub fn supports_sanitize(&self, action: NvmeSanitize) -> bool {
+        match action {
+            NvmeSanitize::ExitFailureMode => self.sanitize & 0x7 != 0,
+            NvmeSanitize::CryptoErase => self.sanitize & 0x1 != 0,
+            NvmeSanitize::BlockErase => self.sanitize & 0x2 != 0,
+            NvmeSanitize::Overwrite => self.sanitize & 0x4 != 0,
+        }
+    }
+}
+
+/// An LBA format supported by a namespace
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NvmeLbaFormat {
+    pub metadata_size: u16,
+    pub block_size: u64,
+    /// 0 is the best performance, and 3 the worst
+    pub relative_performance: u8,
+}
+
+/// Identify Namespace data
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NvmeNamespace {
+    /// The size in blocks
+    pub size: u64,
+    /// The capacity in blocks, which may be less than the size if thin
+    /// provisioned
+    pub capacity: u64,
+    /// The blocks in use
+    pub utilization: u64,
+    pub formats: Vec<NvmeLbaFormat>,
+    /// The index of the format in use
+    pub format_index: usize,
+    pub nguid: [u8; 16],
+    pub eui64: [u8; 8],
+}
+
+impl NvmeNamespace {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < NVME_IDENTIFY_SIZE {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        // The count is zero based, up to 64 formats
+        let count = usize::from(data[25]) + 1;
+        let formats = (0..count.min(64))
+            .map(|i| {
+                let format = le32(data, 128 + i * 4);
+                NvmeLbaFormat {
+                    metadata_size: format as u16,
+                    block_size: 1 << (format >> 16 & 0xFF).min(63),
+                    relative_performance: (format >> 24 & 0x3) as u8,
+                }
+            })
+            .collect();
+        let flbas = usize::from(data[26]);
+
+        Ok(NvmeNamespace {
+            size: le64(data, 0),
+            capacity: le64(data, 8),
+            utilization: le64(data, 16),
+            formats,
+            format_index: (flbas & 0xF) | (flbas >> 5 & 0x3) << 4,
+            nguid: data[104..120].try_into().unwrap(),
+            eui64: data[120..128].try_into().unwrap(),
+        })
+    }
+
+    /// The format in use
+    pub fn format(&self) -> Option<&NvmeLbaFormat> {
+        self.formats.get(self.format_index)
+    }
+
+    pub fn block_size(&self) -> Option<u64> {
+        self.format().map(|format| format.block_size)
+    }
+}
+
+pub struct NvmePassThru(pub &'static mut UefiNvmePassThru);
+
+impl Protocol<UefiNvmePassThru> for NvmePassThru {
+    fn guid() -> Guid {
+        UefiNvmePassThru::GUID
+    }
+
+    fn new(inner: &'static mut UefiNvmePassThru) -> Self {
+        NvmePassThru(inner)
+    }
+}
+
+impl NvmePassThru {
+    pub fn io_align(&self) -> usize {
+        self.0.Mode.IoAlign as usize
+    }
+
+    /// The NVMe version of the controller
+    pub fn version(&self) -> u32 {
+        self.0.Mode.NvmeVersion
+    }
+
+    /// The IDs of the active namespaces
+    pub fn namespaces(&self) -> Result<Vec<u32>> {
+        let mut namespaces = Vec::new();
+        let mut namespace = NVME_ALL_NAMESPACES;
+        loop {
+            match (self.0.GetNextNamespace)(self.0, &mut namespace) {
+                Status::SUCCESS => namespaces.push(namespace),
+                Status::NOT_FOUND => return Ok(namespaces),
+                status => return Err(status),
+            }
+        }
+    }
+
+    /// The device path of `namespace`
+    pub fn device_path(&self, namespace: u32) -> Result<DevicePath> {
+        let mut path = ptr::null_mut();
+        match (self.0.BuildDevicePath)(self.0, namespace, &mut path) {
+            Status::SUCCESS => Ok(DevicePath(unsafe { &mut *path })),
+            status => Err(status),
+        }
+    }
+
+    /// Run `command` and wait for it to complete
+    pub fn execute(
+        &mut self,
+        command: &NvmeCommand,
+        mut transfer: Transfer,
+    ) -> Result<NvmeCompletion> {
+        let len = u32::try_from(transfer.len()).map_err(|_| Status::BAD_BUFFER_SIZE)?;
+        let mut buffer = transfer.stage(self.io_align())?;
+        let mut raw = stage_value(command.raw(), 0)?;
+        let mut completion = stage_value(UefiNvmeCompletion::default(), 0)?;
+
+        let mut packet = NvmExpressPassThruCommandPacket {
+            CommandTimeout: command.timeout,
+            TransferBuffer: if len == 0 {
+                ptr::null_mut()
+            } else {
+                buffer.as_mut_ptr()
+            },
+            TransferLength: len,
+            MetadataBuffer: ptr::null_mut(),
+            MetadataLength: 0,
+            QueueType: match command.queue {
+                NvmeQueue::Admin => NVME_ADMIN_QUEUE,
+                NvmeQueue::Io => NVME_IO_QUEUE,
+            },
+            NvmeCmd: raw.as_mut_ptr() as *mut UefiNvmeCommand,
+            NvmeCompletion: completion.as_mut_ptr() as *mut UefiNvmeCompletion,
+        };
+
+        let status = (self.0.PassThru)(self.0, command.namespace, &mut packet, Event(0));
+        match status {
+            Status::SUCCESS => {
+                transfer.finish(&buffer, packet.TransferLength as usize);
+                let completion: UefiNvmeCompletion = read_value(&completion);
+                Ok(NvmeCompletion {
+                    dw0: completion.DW0,
+                    dw1: completion.DW1,
+                    dw2: completion.DW2,
+                    dw3: completion.DW3,
+                })
+            }
+            _ => Err(status),
+        }
+    }
+
+    pub fn identify_controller(&mut self) -> Result<NvmeController> {
+        let mut data = vec![0; NVME_IDENTIFY_SIZE];
+        self.execute(&NvmeCommand::identify_controller(), Transfer::In(&mut data))?;
+        NvmeController::parse(&data)
+    }
+
+    pub fn identify_namespace(&mut self, namespace: u32) -> Result<NvmeNamespace> {
+        let mut data = vec![0; NVME_IDENTIFY_SIZE];
+        self.execute(
+            &NvmeCommand::identify_namespace(namespace),
+            Transfer::In(&mut data),
+        )?;
+        NvmeNamespace::parse(&data)
+    }
+}
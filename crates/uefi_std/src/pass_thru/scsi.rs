@@ -0,0 +1,269 @@
+use core::ptr;
+use uefi::scsi::{
+    ExtScsiPassThru as UefiScsiPassThru, ExtScsiPassThruScsiRequestPacket,
+    EXT_SCSI_DATA_DIRECTION_READ, EXT_SCSI_DATA_DIRECTION_WRITE, TARGET_MAX_BYTES,
+};
+
+use super::{ascii_field, Transfer, DEFAULT_TIMEOUT};
+use crate::block_io::AlignedBuffer;
+use crate::device_path::DevicePath;
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+const CMD_TEST_UNIT_READY: u8 = 0x00;
+const CMD_INQUIRY: u8 = 0x12;
+const CMD_SERVICE_ACTION_IN: u8 = 0x9E;
+const SERVICE_READ_CAPACITY_16: u8 = 0x10;
+
+const INQUIRY_SIZE: usize = 96;
+const READ_CAPACITY_16_SIZE: usize = 32;
+/// The most sense data a packet can return
+const SENSE_SIZE: usize = 252;
+
+/// The CHECK CONDITION target status, reported with sense data
+const CHECK_CONDITION: u8 = 0x02;
+
+/// A logical unit of a target
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ScsiTarget {
+    pub target: [u8; TARGET_MAX_BYTES],
+    pub lun: u64,
+}
+
+/// The outcome of a command
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScsiResponse {
+    pub host_adapter_status: u8,
+    pub target_status: u8,
+    /// Sense data, usually present with a CHECK CONDITION status
+    pub sense: Vec<u8>,
+    /// The bytes moved by the command
+    pub transferred: usize,
+}
+
+impl ScsiResponse {
+    pub fn is_good(&self) -> bool {
+        self.host_adapter_status == 0 && self.target_status == 0
+    }
+
+    /// The sense key, additional sense code and qualifier of fixed or
+    /// descriptor format sense data
+    pub fn sense_code(&self) -> Option<(u8, u8, u8)> {
+        let sense = &self.sense;
+        match sense.first()? & 0x7F {
+            0x70 | 0x71 if sense.len() >= 14 => Some((sense[2] & 0xF, sense[12], sense[13])),
+            0x72 | 0x73 if sense.len() >= 4 => Some((sense[1] & 0xF, sense[2], sense[3])),
+            _ => None,
+        }
+    }
+}
+
+/// Standard INQUIRY data
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScsiInquiry {
+    /// The peripheral device type, where 0 is a disk and 5 an optical drive
+    pub device_type: u8,
+    pub removable: bool,
+    pub version: u8,
+    pub vendor: String,
+    pub product: String,
+    pub revision: String,
+}
+
+impl ScsiInquiry {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 36 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        Ok(ScsiInquiry {
+            device_type: data[0] & 0x1F,
+            removable: data[1] & 0x80 != 0,
+            version: data[2],
+            vendor: ascii_field(&data[8..16]),
+            product: ascii_field(&data[16..32]),
+            revision: ascii_field(&data[32..36]),
+        })
+    }
+}
+
+/// READ CAPACITY (16) data
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ScsiCapacity {
+    pub blocks: u64,
+    pub block_size: u32,
+}
+
+impl ScsiCapacity {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 12 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let last = u64::from_be_bytes(data[0..8].try_into().unwrap());
+        Ok(ScsiCapacity {
+            blocks: last + 1,
+            block_size: u32::from_be_bytes(data[8..12].try_into().unwrap()),
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.blocks * u64::from(self.block_size)
+    }
+}
+
+pub struct ScsiPassThru(pub &'static mut UefiScsiPassThru);
+
+impl Protocol<UefiScsiPassThru> for ScsiPassThru {
+    fn guid() -> Guid {
+        UefiScsiPassThru::GUID
+    }
+
+    fn new(inner: &'static mut UefiScsiPassThru) -> Self {
+        ScsiPassThru(inner)
+    }
+}
+
+impl ScsiPassThru {
+    pub fn io_align(&self) -> usize {
+        self.0.Mode.IoAlign as usize
+    }
+
+    pub fn adapter_id(&self) -> u32 {
+        self.0.Mode.AdapterId
+    }
+
+    /// Every logical unit of every target
+    pub fn targets(&self) -> Result<Vec<ScsiTarget>> {
+        let mut targets = Vec::new();
+        let mut target = [0xFF; TARGET_MAX_BYTES];
+        let mut lun = 0;
+        loop {
+            let mut ptr = target.as_mut_ptr();
+            match (self.0.GetNextTargetLun)(self.0, &mut ptr, &mut lun) {
+                Status::SUCCESS => targets.push(ScsiTarget { target, lun }),
+                Status::NOT_FOUND => return Ok(targets),
+                status => return Err(status),
+            }
+        }
+    }
+
+    pub fn device_path(&self, target: &ScsiTarget) -> Result<DevicePath> {
+        let mut path = ptr::null_mut();
+        match (self.0.BuildDevicePath)(self.0, target.target.as_ptr(), target.lun, &mut path) {
+            Status::SUCCESS => Ok(DevicePath(unsafe { &mut *path })),
+            status => Err(status),
+        }
+    }
+
+    pub fn reset(&mut self, target: &ScsiTarget) -> Result<()> {
+        (self.0.ResetTargetLun)(self.0, target.target.as_ptr(), target.lun).into()
+    }
+
+    /// Run the command `cdb` and wait up to `timeout` in 100ns units for it to
+    /// complete, or forever for 0. A CHECK CONDITION is returned as a response
+    /// so that its sense data can be read.
+    pub fn execute(
+        &mut self,
+        target: &ScsiTarget,
+        cdb: &[u8],
+        mut transfer: Transfer,
+        timeout: u64,
+    ) -> Result<ScsiResponse> {
+        let cdb_len = u8::try_from(cdb.len()).map_err(|_| Status::INVALID_PARAMETER)?;
+        let len = u32::try_from(transfer.len()).map_err(|_| Status::BAD_BUFFER_SIZE)?;
+        let mut buffer = transfer.stage(self.io_align())?;
+        let mut staged_cdb = AlignedBuffer::new(cdb.len(), self.io_align())?;
+        staged_cdb.copy_from_slice(cdb);
+        let mut sense = AlignedBuffer::new(SENSE_SIZE, self.io_align())?;
+
+        let (in_len, out_len) = match transfer {
+            Transfer::Out(_) => (0, len),
+            _ => (len, 0),
+        };
+        let mut packet = ExtScsiPassThruScsiRequestPacket {
+            Timeout: timeout,
+            InDataBuffer: if in_len == 0 {
+                ptr::null_mut()
+            } else {
+                buffer.as_mut_ptr()
+            },
+            OutDataBuffer: if out_len == 0 {
+                ptr::null()
+            } else {
+                buffer.as_ptr()
+            },
+            SenseData: sense.as_mut_ptr(),
+            Cdb: staged_cdb.as_ptr(),
+            InTransferLength: in_len,
+            OutTransferLength: out_len,
+            CdbLength: cdb_len,
+            DataDirection: match transfer {
+                Transfer::Out(_) => EXT_SCSI_DATA_DIRECTION_WRITE,
+                _ => EXT_SCSI_DATA_DIRECTION_READ,
+            },
+            HostAdapterStatus: 0,
+            TargetStatus: 0,
+            SenseDataLength: SENSE_SIZE as u8,
+        };
+
+        let status = (self.0.PassThru)(
+            self.0,
+            target.target.as_ptr(),
+            target.lun,
+            &mut packet,
+            Event(0),
+        );
+        let check_condition = packet.TargetStatus == CHECK_CONDITION;
+        match status {
+            Status::SUCCESS => (),
+            Status::DEVICE_ERROR if check_condition => (),
+            _ => return Err(status),
+        }
+
+        let transferred = if out_len == 0 {
+            packet.InTransferLength
+        } else {
+            packet.OutTransferLength
+        } as usize;
+        transfer.finish(&buffer, transferred);
+
+        let sense_len = usize::from(packet.SenseDataLength).min(SENSE_SIZE);
+        Ok(ScsiResponse {
+            host_adapter_status: packet.HostAdapterStatus,
+            target_status: packet.TargetStatus,
+            sense: sense[..sense_len].to_vec(),
+            transferred,
+        })
+    }
+
+    /// Returns true if the unit is ready for media commands
+    pub fn test_unit_ready(&mut self, target: &ScsiTarget) -> Result<bool> {
+        let cdb = [CMD_TEST_UNIT_READY, 0, 0, 0, 0, 0];
+        let response = self.execute(target, &cdb, Transfer::None, DEFAULT_TIMEOUT)?;
+        Ok(response.is_good())
+    }
+
+    pub fn inquiry(&mut self, target: &ScsiTarget) -> Result<ScsiInquiry> {
+        let mut data = [0; INQUIRY_SIZE];
+        let cdb = [CMD_INQUIRY, 0, 0, 0, INQUIRY_SIZE as u8, 0];
+        let response = self.execute(target, &cdb, Transfer::In(&mut data), DEFAULT_TIMEOUT)?;
+        if !response.is_good() {
+            return Err(Status::DEVICE_ERROR);
+        }
+        ScsiInquiry::parse(&data[..response.transferred])
+    }
+
+    pub fn read_capacity(&mut self, target: &ScsiTarget) -> Result<ScsiCapacity> {
+        let mut data = [0; READ_CAPACITY_16_SIZE];
+        let mut cdb = [0; 16];
+        cdb[0] = CMD_SERVICE_ACTION_IN;
+        cdb[1] = SERVICE_READ_CAPACITY_16;
+        cdb[13] = READ_CAPACITY_16_SIZE as u8;
+        let response = self.execute(target, &cdb, Transfer::In(&mut data), DEFAULT_TIMEOUT)?;
+        if !response.is_good() {
+            return Err(Status::DEVICE_ERROR);
+        }
+        ScsiCapacity::parse(&data[..response.transferred])
+    }
+}
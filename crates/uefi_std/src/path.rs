@@ -0,0 +1,380 @@
+//! UEFI file paths
+//!
+//! Paths on UEFI file systems use `\` as the separator. Both `\` and `/` are
+//! accepted when parsing, and [`Path::normalize`] produces the canonical form
+//! passed to the firmware.
+
+use core::borrow::Borrow;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::ffi::wstr;
+use crate::prelude::*;
+
+/// The separator used by UEFI file paths
+pub const SEPARATOR: char = '\\';
+
+fn is_separator(c: char) -> bool {
+    c == '\\' || c == '/'
+}
+
+/// A single component of a path
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Component<'a> {
+    /// The leading separator of an absolute path
+    RootDir,
+    /// A `.` component
+    CurDir,
+    /// A `..` component
+    ParentDir,
+    /// A file or directory name
+    Normal(&'a str),
+}
+
+impl<'a> Component<'a> {
+    pub fn as_str(self) -> &'a str {
+        match self {
+            Component::RootDir => "\\",
+            Component::CurDir => ".",
+            Component::ParentDir => "..",
+            Component::Normal(name) => name,
+        }
+    }
+}
+
+/// Iterator over the components of a [`Path`], skipping empty components
+#[derive(Clone, Debug)]
+pub struct Components<'a> {
+    path: &'a str,
+    root: bool,
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = Component<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.root {
+            self.root = false;
+            return Some(Component::RootDir);
+        }
+
+        let path = self.path.trim_start_matches(is_separator);
+        if path.is_empty() {
+            self.path = path;
+            return None;
+        }
+
+        let (name, rest) = match path.find(is_separator) {
+            Some(i) => path.split_at(i),
+            None => (path, ""),
+        };
+        self.path = rest;
+
+        Some(match name {
+            "." => Component::CurDir,
+            ".." => Component::ParentDir,
+            _ => Component::Normal(name),
+        })
+    }
+}
+
+impl<'a> DoubleEndedIterator for Components<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let path = self.path.trim_end_matches(is_separator);
+        if path.is_empty() {
+            self.path = path;
+            if self.root {
+                self.root = false;
+                return Some(Component::RootDir);
+            }
+            return None;
+        }
+
+        let (rest, name) = match path.rfind(is_separator) {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("", path),
+        };
+        self.path = rest;
+
+        Some(match name {
+            "." => Component::CurDir,
+            ".." => Component::ParentDir,
+            _ => Component::Normal(name),
+        })
+    }
+}
+
+/// A borrowed UEFI path, similar to `std::path::Path`
+#[derive(Debug, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct Path {
+    inner: str,
+}
+
+impl Path {
+    pub fn new<S: AsRef<str> + ?Sized>(s: &S) -> &Path {
+        // Path is a transparent wrapper around str
+        unsafe { &*(s.as_ref() as *const str as *const Path) }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf::from(&self.inner)
+    }
+
+    /// Returns true if the path starts at the root of the volume
+    pub fn is_absolute(&self) -> bool {
+        self.inner.starts_with(is_separator)
+    }
+
+    pub fn is_relative(&self) -> bool {
+        !self.is_absolute()
+    }
+
+    pub fn components(&self) -> Components {
+        let root = self.is_absolute();
+        Components {
+            path: if root { &self.inner[1..] } else { &self.inner },
+            root,
+        }
+    }
+
+    /// Returns the path without its final component, if there is one
+    pub fn parent(&self) -> Option<&Path> {
+        let path = self.inner.trim_end_matches(is_separator);
+        if path.is_empty() {
+            return None;
+        }
+
+        match path.rfind(is_separator) {
+            Some(i) => {
+                let parent = path[..i].trim_end_matches(is_separator);
+                if parent.is_empty() {
+                    // Keep the root separator
+                    Some(Path::new(&path[..1]))
+                } else {
+                    Some(Path::new(parent))
+                }
+            }
+            None => Some(Path::new("")),
+        }
+    }
+
+    /// Returns the final component of the path, if it is a normal file name
+    pub fn file_name(&self) -> Option<&str> {
+        match self.components().next_back() {
+            Some(Component::Normal(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the file name without its extension
+    pub fn file_stem(&self) -> Option<&str> {
+        let name = self.file_name()?;
+        match name.rfind('.') {
+            Some(0) | None => Some(name),
+            Some(i) => Some(&name[..i]),
+        }
+    }
+
+    /// Returns the extension of the file name, without the leading `.`
+    pub fn extension(&self) -> Option<&str> {
+        let name = self.file_name()?;
+        match name.rfind('.') {
+            Some(0) | None => None,
+            Some(i) => Some(&name[i + 1..]),
+        }
+    }
+
+    /// Joins `path` onto this path. If `path` is absolute, it replaces this path.
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let mut buf = self.to_path_buf();
+        buf.push(path);
+        buf
+    }
+
+    /// Returns a path with `\` separators, without empty or `.` components, and
+    /// with `..` components applied. A `..` at the root of an absolute path is
+    /// dropped.
+    pub fn normalize(&self) -> PathBuf {
+        let mut names: Vec<&str> = Vec::new();
+        let mut root = false;
+        for component in self.components() {
+            match component {
+                Component::RootDir => root = true,
+                Component::CurDir => (),
+                Component::ParentDir => match names.last() {
+                    Some(&"..") | None => {
+                        if !root {
+                            names.push("..");
+                        }
+                    }
+                    Some(_) => {
+                        names.pop();
+                    }
+                },
+                Component::Normal(name) => names.push(name),
+            }
+        }
+
+        let mut inner = String::new();
+        if root {
+            inner.push(SEPARATOR);
+        }
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                inner.push(SEPARATOR);
+            }
+            inner.push_str(name);
+        }
+        PathBuf { inner }
+    }
+
+    /// Converts the normalized path to a null-terminated UCS-2 string
+    pub fn to_wstr(&self) -> Vec<u16> {
+        wstr(self.normalize().as_str())
+    }
+}
+
+impl AsRef<Path> for Path {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+impl AsRef<Path> for str {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for String {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<str> for Path {
+    fn as_ref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl ToOwned for Path {
+    type Owned = PathBuf;
+
+    fn to_owned(&self) -> PathBuf {
+        self.to_path_buf()
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.inner)
+    }
+}
+
+/// An owned UEFI path, similar to `std::path::PathBuf`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PathBuf {
+    inner: String,
+}
+
+impl PathBuf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_path(&self) -> &Path {
+        Path::new(&self.inner)
+    }
+
+    /// Extends the path with `path`. If `path` is absolute, it replaces this path.
+    pub fn push<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            self.inner.clear();
+        } else if !self.inner.is_empty() && !self.inner.ends_with(is_separator) {
+            self.inner.push(SEPARATOR);
+        }
+        self.inner.push_str(path.as_str());
+    }
+
+    /// Truncates the path to its parent, returning false if there is no parent
+    pub fn pop(&mut self) -> bool {
+        match self.parent().map(|parent| parent.inner.len()) {
+            Some(len) => {
+                self.inner.truncate(len);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the extension of the file name, or removes it if `extension` is empty
+    pub fn set_extension(&mut self, extension: &str) -> bool {
+        let stem = match self.file_stem() {
+            Some(stem) => stem.as_ptr() as usize - self.inner.as_ptr() as usize + stem.len(),
+            None => return false,
+        };
+        self.inner.truncate(stem);
+        if !extension.is_empty() {
+            self.inner.push('.');
+            self.inner.push_str(extension);
+        }
+        true
+    }
+
+    pub fn into_string(self) -> String {
+        self.inner
+    }
+}
+
+impl Deref for PathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl Borrow<Path> for PathBuf {
+    fn borrow(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<Path> for PathBuf {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl From<&str> for PathBuf {
+    fn from(s: &str) -> Self {
+        PathBuf {
+            inner: s.to_string(),
+        }
+    }
+}
+
+impl From<String> for PathBuf {
+    fn from(inner: String) -> Self {
+        PathBuf { inner }
+    }
+}
+
+impl From<&Path> for PathBuf {
+    fn from(path: &Path) -> Self {
+        path.to_path_buf()
+    }
+}
+
+impl fmt::Display for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.inner)
+    }
+}
@@ -0,0 +1,205 @@
+//! RAM disks, which expose images in memory as Block I/O devices
+
+use core::{ptr, slice};
+use uefi::boot::AllocateType;
+use uefi::device::DevicePath as UefiDevicePath;
+use uefi::memory::{MemoryType, PAGE_SIZE};
+use uefi::ram_disk::{
+    RamDisk as UefiRamDisk, PERSISTENT_VIRTUAL_CD_GUID, PERSISTENT_VIRTUAL_DISK_GUID,
+    VIRTUAL_CD_GUID, VIRTUAL_DISK_GUID,
+};
+
+use crate::device_path::DevicePath;
+use crate::io::{Read, Seek, SeekFrom};
+use crate::prelude::*;
+use crate::proto::Protocol;
+use crate::system_table;
+
+/// The kind of disk a RAM disk presents
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RamDiskType {
+    /// A disk image, such as a FAT or partitioned image
+    VirtualDisk,
+    /// An ISO 9660 image
+    VirtualCd,
+    PersistentVirtualDisk,
+    PersistentVirtualCd,
+}
+
+impl RamDiskType {
+    pub fn guid(&self) -> Guid {
+        match self {
+            RamDiskType::VirtualDisk => VIRTUAL_DISK_GUID,
+            RamDiskType::VirtualCd => VIRTUAL_CD_GUID,
+            RamDiskType::PersistentVirtualDisk => PERSISTENT_VIRTUAL_DISK_GUID,
+            RamDiskType::PersistentVirtualCd => PERSISTENT_VIRTUAL_CD_GUID,
+        }
+    }
+}
+
+pub struct RamDisk(pub &'static mut UefiRamDisk);
+
+impl Protocol<UefiRamDisk> for RamDisk {
+    fn guid() -> Guid {
+        UefiRamDisk::GUID
+    }
+
+    fn new(inner: &'static mut UefiRamDisk) -> Self {
+        RamDisk(inner)
+    }
+}
+
+impl RamDisk {
+    /// Register `size` bytes at `base` as a disk, returning the device path of
+    /// the new Block I/O handle. Firmware allocates the path from pool, and it
+    /// is freed with [`free_path`](Self::free_path).
+    ///
+    /// # Safety
+    ///
+    /// The memory must stay allocated and unused by anything else until the
+    /// disk is unregistered.
+    pub unsafe fn register(
+        &mut self,
+        base: u64,
+        size: u64,
+        kind: RamDiskType,
+        parent: Option<&DevicePath>,
+    ) -> Result<DevicePath> {
+        let parent = parent.map_or(ptr::null(), |parent| &*parent.0 as *const UefiDevicePath);
+        let mut path = ptr::null_mut();
+        let status = (self.0.Register)(base, size, &kind.guid(), parent, &mut path);
+
+        match status {
+            Status::SUCCESS => Ok(DevicePath(&mut *path)),
+            _ => Err(status),
+        }
+    }
+
+    /// Remove the disk with the device path `path`
+    pub fn unregister(&mut self, path: &DevicePath) -> Result<()> {
+        (self.0.Unregister)(&*path.0).into()
+    }
+
+    /// Free a device path returned by [`register`](Self::register)
+    pub fn free_path(path: DevicePath) {
+        let _ = (system_table().BootServices.FreePool)(path.0 as *mut UefiDevicePath as usize);
+    }
+}
+
+/// An image loaded into reserved pages and registered as a RAM disk. The disk
+/// stays registered unless [`unregister`](Self::unregister) is called, so
+/// that a loaded OS can still find it.
+pub struct RamDiskImage {
+    /// Taken when dropped, to free it
+    device_path: Option<DevicePath>,
+    address: usize,
+    pages: usize,
+    len: u64,
+}
+
+impl RamDiskImage {
+    /// Read the whole of `reader` into reserved pages and register it as a
+    /// disk of type `kind`
+    pub fn load<R: Read + Seek>(reader: &mut R, kind: RamDiskType) -> Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        if len == 0 {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        let size = usize::try_from(len).map_err(|_| Status::OUT_OF_RESOURCES)?;
+        let pages = size.div_ceil(PAGE_SIZE);
+        let address = allocate_pages(pages)?;
+
+        let data = unsafe { slice::from_raw_parts_mut(address as *mut u8, pages * PAGE_SIZE) };
+        data[size..].fill(0);
+        let result = reader.read_exact(&mut data[..size]).map_err(Status::from);
+
+        let result = result.and_then(|()| {
+            let mut ram_disk = RamDisk::locate_protocol()?;
+            unsafe { ram_disk.register(address as u64, len, kind, None) }
+        });
+        match result {
+            Ok(device_path) => Ok(RamDiskImage {
+                device_path: Some(device_path),
+                address,
+                pages,
+                len,
+            }),
+            Err(err) => {
+                free_pages(address, pages);
+                Err(err)
+            }
+        }
+    }
+
+    /// The device path of the disk
+    pub fn device_path(&self) -> &DevicePath {
+        self.device_path.as_ref().unwrap()
+    }
+
+    /// The physical address of the image
+    pub fn address(&self) -> u64 {
+        self.address as u64
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The handle firmware created for the disk
+    pub fn handle(&self) -> Result<Handle> {
+        let mut path = &*self.device_path().0 as *const UefiDevicePath;
+        let mut handle = Handle(0);
+        let status = (system_table().BootServices.LocateDevicePath)(
+            &UefiDevicePath::GUID,
+            &mut path,
+            &mut handle,
+        );
+
+        match status {
+            Status::SUCCESS => Ok(handle),
+            _ => Err(status),
+        }
+    }
+
+    /// Remove the disk and free its memory
+    pub fn unregister(self) -> Result<()> {
+        RamDisk::locate_protocol()?.unregister(self.device_path())?;
+        free_pages(self.address, self.pages);
+        Ok(())
+    }
+}
+
+impl Drop for RamDiskImage {
+    /// Free the device path, which firmware copied when registering the disk
+    fn drop(&mut self) {
+        if let Some(path) = self.device_path.take() {
+            RamDisk::free_path(path);
+        }
+    }
+}
+
+/// Allocate pages that firmware and the OS will leave alone
+fn allocate_pages(pages: usize) -> Result<usize> {
+    let mut address = 0;
+    let status = (system_table().BootServices.AllocatePages)(
+        AllocateType::AnyPages as usize,
+        MemoryType::EfiReservedMemoryType,
+        pages,
+        &mut address,
+    );
+
+    match status {
+        Status::SUCCESS => Ok(address),
+        _ => Err(status),
+    }
+}
+
+fn free_pages(address: usize, pages: usize) {
+    let _ = (system_table().BootServices.FreePages)(address, pages);
+}
@@ -0,0 +1,140 @@
+//! Security protocol commands for self-encrypting drives
+//!
+//! Commands are sent to the media of a Block I/O device, identified by the
+//! `MediaId` of the Block I/O protocol on the same handle.
+
+use uefi::storage_security::StorageSecurityCommand;
+
+use crate::bytes::be16;
+use crate::pass_thru::DEFAULT_TIMEOUT;
+use crate::prelude::*;
+use crate::proto::Protocol;
+
+pub use self::opal::{
+    Feature, GeometryFeature, Level0Discovery, LockingFeature, Ssc, SscFeature, TperFeature,
+};
+
+mod opal;
+
+/// Security protocol information, with the list of supported protocols
+pub const SECURITY_PROTOCOL_INFO: u8 = 0x00;
+/// TCG storage, used by Opal and related subsystem classes
+pub const SECURITY_PROTOCOL_TCG: u8 = 0x01;
+/// IEEE 1667 silos
+pub const SECURITY_PROTOCOL_IEEE1667: u8 = 0xEE;
+
+/// The ComID of Level 0 discovery
+const LEVEL0_DISCOVERY_COMID: u16 = 0x0001;
+/// Level 0 discovery data fits in a single 2 KiB transfer on known drives
+const LEVEL0_DISCOVERY_SIZE: usize = 2048;
+/// The reply to a protocol list request is padded to a whole block
+const PROTOCOL_LIST_SIZE: usize = 512;
+
+pub struct StorageSecurity(pub &'static mut StorageSecurityCommand);
+
+impl Protocol<StorageSecurityCommand> for StorageSecurity {
+    fn guid() -> Guid {
+        StorageSecurityCommand::GUID
+    }
+
+    fn new(inner: &'static mut StorageSecurityCommand) -> Self {
+        StorageSecurity(inner)
+    }
+}
+
+impl StorageSecurity {
+    /// Receive data for `protocol` into `buf`, waiting up to `timeout` in
+    /// 100ns units or forever for 0, returning the number of bytes received.
+    ///
+    /// Firmware sends `specific` in memory order, so a TCG ComID has to be
+    /// passed big endian.
+    ///
+    /// ATA devices may require `buf` to be a whole number of 512 byte blocks.
+    pub fn receive(
+        &mut self,
+        media_id: u32,
+        protocol: u8,
+        specific: u16,
+        buf: &mut [u8],
+        timeout: u64,
+    ) -> Result<usize> {
+        let mut transferred = 0;
+        let status = (self.0.ReceiveData)(
+            self.0,
+            media_id,
+            timeout,
+            protocol,
+            specific,
+            buf.len(),
+            buf.as_mut_ptr(),
+            &mut transferred,
+        );
+
+        match status {
+            Status::SUCCESS => Ok(transferred.min(buf.len())),
+            _ => Err(status),
+        }
+    }
+
+    /// Send `data` for `protocol`, waiting up to `timeout` in 100ns units or
+    /// forever for 0
+    pub fn send(
+        &mut self,
+        media_id: u32,
+        protocol: u8,
+        specific: u16,
+        data: &[u8],
+        timeout: u64,
+    ) -> Result<()> {
+        (self.0.SendData)(
+            self.0,
+            media_id,
+            timeout,
+            protocol,
+            specific,
+            data.len(),
+            data.as_ptr(),
+        )
+        .into()
+    }
+
+    /// The security protocols the device supports
+    pub fn supported_protocols(&mut self, media_id: u32) -> Result<Vec<u8>> {
+        let mut data = [0; PROTOCOL_LIST_SIZE];
+        let len = self.receive(
+            media_id,
+            SECURITY_PROTOCOL_INFO,
+            0,
+            &mut data,
+            DEFAULT_TIMEOUT,
+        )?;
+        if len < 8 {
+            return Err(Status::DEVICE_ERROR);
+        }
+
+        let count = usize::from(be16(&data, 6));
+        let end = (8 + count).min(len);
+        Ok(data[8..end].to_vec())
+    }
+
+    /// Returns true if the device speaks TCG storage
+    pub fn supports_tcg(&mut self, media_id: u32) -> Result<bool> {
+        Ok(self
+            .supported_protocols(media_id)?
+            .contains(&SECURITY_PROTOCOL_TCG))
+    }
+
+    /// Run TCG Level 0 discovery, describing the features and locking state
+    /// of the device
+    pub fn level0_discovery(&mut self, media_id: u32) -> Result<Level0Discovery> {
+        let mut data = vec![0; LEVEL0_DISCOVERY_SIZE];
+        let len = self.receive(
+            media_id,
+            SECURITY_PROTOCOL_TCG,
+            LEVEL0_DISCOVERY_COMID.to_be(),
+            &mut data,
+            DEFAULT_TIMEOUT,
+        )?;
+        Level0Discovery::parse(&data[..len])
+    }
+}
@@ -0,0 +1,474 @@
+//! TCG Level 0 discovery, as described by the TCG Storage Architecture Core
+//! Specification and the Opal family of subsystem classes
+
+use crate::bytes::{be16, be32, be64};
+use crate::prelude::*;
+
+const HEADER_SIZE: usize = 48;
+const DESCRIPTOR_HEADER_SIZE: usize = 4;
+
+const FEATURE_TPER: u16 = 0x0001;
+const FEATURE_LOCKING: u16 = 0x0002;
+const FEATURE_GEOMETRY: u16 = 0x0003;
+const FEATURE_ENTERPRISE: u16 = 0x0100;
+const FEATURE_OPAL_V1: u16 = 0x0200;
+const FEATURE_OPAL_V2: u16 = 0x0203;
+const FEATURE_OPALITE: u16 = 0x0301;
+const FEATURE_PYRITE_V1: u16 = 0x0302;
+const FEATURE_PYRITE_V2: u16 = 0x0303;
+const FEATURE_RUBY: u16 = 0x0304;
+
+/// The TPer feature, describing how the device communicates
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TperFeature {
+    pub sync: bool,
+    pub async_: bool,
+    pub ack_nak: bool,
+    pub buffer_management: bool,
+    pub streaming: bool,
+    pub comid_management: bool,
+}
+
+impl TperFeature {
+    fn parse(data: &[u8]) -> Self {
+        let flags = data.first().copied().unwrap_or(0);
+        TperFeature {
+            sync: flags & 0x01 != 0,
+            async_: flags & 0x02 != 0,
+            ack_nak: flags & 0x04 != 0,
+            buffer_management: flags & 0x08 != 0,
+            streaming: flags & 0x10 != 0,
+            comid_management: flags & 0x40 != 0,
+        }
+    }
+}
+
+/// The Locking feature, reporting the locking state of the device
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LockingFeature {
+    pub supported: bool,
+    /// A locking range has been configured with a password
+    pub enabled: bool,
+    /// At least one locking range is locked
+    pub locked: bool,
+    pub media_encryption: bool,
+    /// The shadow MBR is presented in place of the user data
+    pub mbr_enabled: bool,
+    /// The shadow MBR has been dismissed, exposing the user data
+    pub mbr_done: bool,
+    pub mbr_shadowing_supported: bool,
+}
+
+impl LockingFeature {
+    fn parse(data: &[u8]) -> Self {
+        let flags = data.first().copied().unwrap_or(0);
+        LockingFeature {
+            supported: flags & 0x01 != 0,
+            enabled: flags & 0x02 != 0,
+            locked: flags & 0x04 != 0,
+            media_encryption: flags & 0x08 != 0,
+            mbr_enabled: flags & 0x10 != 0,
+            mbr_done: flags & 0x20 != 0,
+            mbr_shadowing_supported: flags & 0x40 == 0,
+        }
+    }
+}
+
+/// The Geometry Reporting feature
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GeometryFeature {
+    /// Locking ranges must start and end on aligned blocks
+    pub align: bool,
+    pub logical_block_size: u32,
+    pub alignment_granularity: u64,
+    pub lowest_aligned_lba: u64,
+}
+
+impl GeometryFeature {
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 28 {
+            return None;
+        }
+
+        Some(GeometryFeature {
+            align: data[0] & 0x01 != 0,
+            logical_block_size: be32(data, 8),
+            alignment_granularity: be64(data, 12),
+            lowest_aligned_lba: be64(data, 20),
+        })
+    }
+}
+
+/// A security subsystem class
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Ssc {
+    Enterprise,
+    OpalV1,
+    OpalV2,
+    Opalite,
+    PyriteV1,
+    PyriteV2,
+    Ruby,
+}
+
+impl Ssc {
+    fn from_code(code: u16) -> Option<Self> {
+        match code {
+            FEATURE_ENTERPRISE => Some(Ssc::Enterprise),
+            FEATURE_OPAL_V1 => Some(Ssc::OpalV1),
+            FEATURE_OPAL_V2 => Some(Ssc::OpalV2),
+            FEATURE_OPALITE => Some(Ssc::Opalite),
+            FEATURE_PYRITE_V1 => Some(Ssc::PyriteV1),
+            FEATURE_PYRITE_V2 => Some(Ssc::PyriteV2),
+            FEATURE_RUBY => Some(Ssc::Ruby),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the class can encrypt user data, which Pyrite cannot
+    pub fn encrypts(&self) -> bool {
+        !matches!(self, Ssc::PyriteV1 | Ssc::PyriteV2)
+    }
+}
+
+/// A subsystem class feature, giving the ComIDs to open sessions on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SscFeature {
+    pub ssc: Ssc,
+    pub base_comid: u16,
+    pub comids: u16,
+    /// Accesses to more than one locking range are rejected, which only
+    /// Enterprise and Opal report
+    pub range_crossing: bool,
+    /// The number of Locking SP admin authorities, where reported
+    pub locking_admins: Option<u16>,
+    /// The number of Locking SP user authorities, where reported
+    pub locking_users: Option<u16>,
+}
+
+impl SscFeature {
+    fn parse(ssc: Ssc, data: &[u8]) -> Option<Self> {
+        if data.len() < 4 {
+            return None;
+        }
+
+        // Only these classes define the flags and authority counts, which are
+        // reserved in the others
+        let range_crossing = matches!(ssc, Ssc::Enterprise | Ssc::OpalV1 | Ssc::OpalV2);
+        let authorities = matches!(ssc, Ssc::OpalV2 | Ssc::Ruby) && data.len() >= 9;
+        Some(SscFeature {
+            ssc,
+            base_comid: be16(data, 0),
+            comids: be16(data, 2),
+            range_crossing: range_crossing && data.get(4).map_or(false, |flags| flags & 0x01 != 0),
+            locking_admins: authorities.then(|| be16(data, 5)),
+            locking_users: authorities.then(|| be16(data, 7)),
+        })
+    }
+}
+
+/// A feature descriptor
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Feature {
+    Tper(TperFeature),
+    Locking(LockingFeature),
+    Geometry(GeometryFeature),
+    Ssc(SscFeature),
+    /// A feature without a decoder, or one too short to decode
+    Other {
+        code: u16,
+        version: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl Feature {
+    fn parse(code: u16, version: u8, data: &[u8]) -> Self {
+        let feature = match code {
+            FEATURE_TPER => Some(Feature::Tper(TperFeature::parse(data))),
+            FEATURE_LOCKING => Some(Feature::Locking(LockingFeature::parse(data))),
+            FEATURE_GEOMETRY => GeometryFeature::parse(data).map(Feature::Geometry),
+            _ => Ssc::from_code(code)
+                .and_then(|ssc| SscFeature::parse(ssc, data))
+                .map(Feature::Ssc),
+        };
+
+        feature.unwrap_or_else(|| Feature::Other {
+            code,
+            version,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// The response to Level 0 discovery
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Level0Discovery {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub vendor: [u8; 32],
+    pub features: Vec<Feature>,
+}
+
+impl Level0Discovery {
+    /// Parse a response, such as one captured from a drive. A descriptor
+    /// running past the end of the data ends the list.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_SIZE {
+            return Err(Status::INVALID_PARAMETER);
+        }
+
+        // The length excludes the length field itself
+        let len = (be32(data, 0) as usize).saturating_add(4);
+        if len < HEADER_SIZE {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        let data = &data[..len.min(data.len())];
+
+        let mut features = Vec::new();
+        let mut offset = HEADER_SIZE;
+        while offset + DESCRIPTOR_HEADER_SIZE <= data.len() {
+            let code = be16(data, offset);
+            let version = data[offset + 2] >> 4;
+            let end = offset + DESCRIPTOR_HEADER_SIZE + usize::from(data[offset + 3]);
+            if end > data.len() {
+                break;
+            }
+
+            features.push(Feature::parse(
+                code,
+                version,
+                &data[offset + DESCRIPTOR_HEADER_SIZE..end],
+            ));
+            offset = end;
+        }
+
+        Ok(Level0Discovery {
+            major_version: be16(data, 4),
+            minor_version: be16(data, 6),
+            vendor: data[16..48].try_into().unwrap(),
+            features,
+        })
+    }
+
+    pub fn tper(&self) -> Option<&TperFeature> {
+        self.features.iter().find_map(|feature| match feature {
+            Feature::Tper(tper) => Some(tper),
+            _ => None,
+        })
+    }
+
+    pub fn locking(&self) -> Option<&LockingFeature> {
+        self.features.iter().find_map(|feature| match feature {
+            Feature::Locking(locking) => Some(locking),
+            _ => None,
+        })
+    }
+
+    pub fn geometry(&self) -> Option<&GeometryFeature> {
+        self.features.iter().find_map(|feature| match feature {
+            Feature::Geometry(geometry) => Some(geometry),
+            _ => None,
+        })
+    }
+
+    /// The subsystem class of the device. Devices report one, but if several
+    /// are present the first is returned.
+    pub fn ssc(&self) -> Option<&SscFeature> {
+        self.features.iter().find_map(|feature| match feature {
+            Feature::Ssc(ssc) => Some(ssc),
+            _ => None,
+        })
+    }
+
+    /// Returns true if locking is enabled, meaning a password has been set
+    pub fn is_locking_enabled(&self) -> bool {
+        self.locking().map_or(false, |locking| locking.enabled)
+    }
+
+    /// Returns true if any locking range is locked and must be unlocked
+    /// before the data can be read
+    pub fn is_locked(&self) -> bool {
+        self.locking()
+            .map_or(false, |locking| locking.enabled && locking.locked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // TPer: sync, streaming
+    const TPER: [u8; 16] = [
+        0x00, 0x01, 0x10, 0x0C, 0x11, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    // Locking: supported, media encryption
+    const LOCKING: [u8; 16] = [
+        0x00, 0x02, 0x10, 0x0C, 0x09, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    // Geometry: aligned, 512 byte blocks, granularity 8, lowest aligned LBA 0
+    const GEOMETRY: [u8; 32] = [
+        0x00, 0x03, 0x10, 0x1C, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 8,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    // Opal SSC V2: ComID 0x1000, 1 ComID, 4 admins, 9 users
+    const OPAL_V2: [u8; 20] = [
+        0x02, 0x03, 0x10, 0x10, 0x10, 0x00, 0x00, 0x01, 0x00, 0x00, 0x04, 0x00, 0x09, 0, 0, 0, 0,
+        0, 0, 0,
+    ];
+    // Block SID, which has no decoder
+    const BLOCK_SID: [u8; 12] = [0x04, 0x02, 0x10, 0x08, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    /// The response of an Opal 2 drive with locking disabled: a version 0.1
+    /// header followed by the descriptors
+    fn response() -> Vec<u8> {
+        let descriptors = [&TPER[..], &LOCKING, &GEOMETRY, &OPAL_V2, &BLOCK_SID].concat();
+        let mut data = vec![0; HEADER_SIZE];
+        data[..4]
+            .copy_from_slice(&(HEADER_SIZE as u32 + descriptors.len() as u32 - 4).to_be_bytes());
+        data[7] = 1;
+        data.extend(descriptors);
+        data
+    }
+
+    fn edited(edit: impl FnOnce(&mut Vec<u8>)) -> Result<Level0Discovery> {
+        let mut data = response();
+        edit(&mut data);
+        Level0Discovery::parse(&data)
+    }
+
+    #[test]
+    fn opal_v2() {
+        let discovery = Level0Discovery::parse(&response()).unwrap();
+        assert_eq!((discovery.major_version, discovery.minor_version), (0, 1));
+        assert_eq!(discovery.features.len(), 5);
+
+        let tper = discovery.tper().unwrap();
+        assert!(tper.sync && tper.streaming);
+        assert!(!tper.async_ && !tper.comid_management);
+
+        let locking = discovery.locking().unwrap();
+        assert!(locking.supported && locking.media_encryption);
+        assert!(locking.mbr_shadowing_supported);
+        assert!(!locking.enabled && !locking.locked);
+        assert!(!discovery.is_locking_enabled());
+        assert!(!discovery.is_locked());
+
+        assert_eq!(
+            discovery.geometry(),
+            Some(&GeometryFeature {
+                align: true,
+                logical_block_size: 512,
+                alignment_granularity: 8,
+                lowest_aligned_lba: 0,
+            })
+        );
+        assert_eq!(
+            discovery.ssc(),
+            Some(&SscFeature {
+                ssc: Ssc::OpalV2,
+                base_comid: 0x1000,
+                comids: 1,
+                range_crossing: false,
+                locking_admins: Some(4),
+                locking_users: Some(9),
+            })
+        );
+        assert_eq!(
+            discovery.features[4],
+            Feature::Other {
+                code: 0x0402,
+                version: 1,
+                data: vec![0; 8],
+            }
+        );
+    }
+
+    #[test]
+    fn locked() {
+        // Locking enabled and locked, shadow MBR enabled and not done
+        let discovery = edited(|data| data[68] = 0x1F).unwrap();
+        assert!(discovery.is_locking_enabled());
+        assert!(discovery.is_locked());
+        let locking = discovery.locking().unwrap();
+        assert!(locking.mbr_enabled && !locking.mbr_done);
+
+        // Unlocked once a password is set
+        let discovery = edited(|data| data[68] = 0x0B).unwrap();
+        assert!(discovery.is_locking_enabled());
+        assert!(!discovery.is_locked());
+    }
+
+    #[test]
+    fn pyrite() {
+        // Pyrite V2 reports no authority counts and does not encrypt
+        let discovery = edited(|data| {
+            data[112..116].copy_from_slice(&[0x03, 0x03, 0x10, 0x10]);
+        })
+        .unwrap();
+        let ssc = discovery.ssc().unwrap();
+        assert_eq!(ssc.ssc, Ssc::PyriteV2);
+        assert!(!ssc.ssc.encrypts());
+        assert_eq!((ssc.locking_admins, ssc.locking_users), (None, None));
+    }
+
+    #[test]
+    fn reserved_ssc_fields() {
+        // The range crossing bit and authority counts are reserved in Opalite
+        // and Pyrite
+        for (code, ssc) in [(0x0301, Ssc::Opalite), (0x0302, Ssc::PyriteV1)] {
+            let discovery = edited(|data| {
+                data[112..114].copy_from_slice(&u16::to_be_bytes(code));
+                data[120] = 0x01;
+            })
+            .unwrap();
+            let feature = discovery.ssc().unwrap();
+            assert_eq!(feature.ssc, ssc);
+            assert!(!feature.range_crossing);
+            assert_eq!(
+                (feature.locking_admins, feature.locking_users),
+                (None, None)
+            );
+        }
+
+        let discovery = edited(|data| data[120] = 0x01).unwrap();
+        assert!(discovery.ssc().unwrap().range_crossing);
+    }
+
+    #[test]
+    fn length_limits_descriptors() {
+        // The length in the header excludes the Block SID descriptor
+        let discovery = edited(|data| data[3] = 0x80).unwrap();
+        assert_eq!(discovery.features.len(), 4);
+
+        // A descriptor running past the end ends the list
+        let discovery = edited(|data| data.truncate(140)).unwrap();
+        assert_eq!(discovery.features.len(), 4);
+        let discovery = edited(|data| data.truncate(84)).unwrap();
+        assert_eq!(discovery.features.len(), 2);
+
+        // A geometry descriptor too short to decode is kept undecoded
+        let discovery = edited(|data| {
+            data[83] = 0x08;
+            data.truncate(96);
+        })
+        .unwrap();
+        assert!(discovery.geometry().is_none());
+        assert!(matches!(
+            discovery.features[2],
+            Feature::Other { code: 0x0003, .. }
+        ));
+    }
+
+    #[test]
+    fn bad_header() {
+        assert_eq!(
+            edited(|data| data.truncate(47)),
+            Err(Status::INVALID_PARAMETER)
+        );
+        assert_eq!(
+            edited(|data| data[3] = 0x2B),
+            Err(Status::INVALID_PARAMETER)
+        );
+    }
+}